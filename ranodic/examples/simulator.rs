@@ -0,0 +1,214 @@
+//! The clock, BG and forecast widgets on a simulated 64x32 panel, fed with
+//! canned data, for layout work without flashing a board. Takes the same
+//! `[TIMEZONE] [CLOCK_FORMAT]` arguments as the build settings.
+//!
+//! With the `sdl` feature it opens a window: Up/Down change the BG by 10,
+//! S makes the reading stale (or fresh again), Left/Right step the clock back
+//! and forth an hour through day and night, and Escape quits.
+//!
+//! Without it there is no SDL2 to link, and it saves the panel as
+//! `simulator.png` instead. A third argument moves the clock that many hours
+//! on, to see the forecast by night.
+
+#[cfg(feature = "sdl")]
+use std::time::{Duration, Instant};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+use embedded_graphics_simulator::{OutputSettings, OutputSettingsBuilder, SimulatorDisplay};
+#[cfg(feature = "sdl")]
+use embedded_graphics_simulator::{SimulatorEvent, Window, sdl2::Keycode};
+use jiff::{SignedDuration, Timestamp, ToSpan, Zoned, tz::TimeZone};
+
+use ranodic::{
+    airquality::AirQualityCache,
+    clockformat::ClockFormat,
+    forecast::{WMOCode, WeatherForecast, WeatherForecastCache},
+    nightscout::BgReading,
+    timers::Timers,
+    timesource::Quality,
+    widget::{self, Context, DISPLAY_H, DISPLAY_W, Widget},
+};
+
+type Display = SimulatorDisplay<Rgb888>;
+
+/// Two days of hourly forecasts from midnight before `now`: warm, dry
+/// afternoons and cool nights with showers, in °F and mph like Open-Meteo is
+/// asked for.
+fn canned_forecasts(now: &Zoned) -> WeatherForecastCache {
+    let mut forecasts = WeatherForecastCache::new();
+    let midnight = now.start_of_day().unwrap();
+    for hour in 0..48 {
+        let timespan = midnight.checked_add(hour.hours()).unwrap();
+        let of_day = timespan.hour();
+        let is_day = (7..19).contains(&of_day);
+        // coldest at 05:00, warmest at 17:00
+        let swing = ((of_day as f32 - 17.0) / 12.0 * std::f32::consts::PI).cos();
+        let temperature = 57.0 + 14.0 * swing;
+        let (weather_code, precipitation, precipitation_probability) = match of_day {
+            0..=4 => (WMOCode::Overcast, 0.0, 20),
+            5..=8 => (WMOCode::SlightRain, 0.6, 70),
+            9..=11 => (WMOCode::PartlyCloudy, 0.0, 10),
+            12..=18 => (WMOCode::ClearSky, 0.0, 0),
+            _ => (WMOCode::MainlyClear, 0.0, 5),
+        };
+        forecasts.add(WeatherForecast {
+            timespan,
+            temperature,
+            relative_humidity: (70.0 - 25.0 * swing) as u8,
+            apparent_temperature: temperature - 3.0,
+            precipitation,
+            precipitation_probability,
+            weather_code,
+            is_day,
+            sunshine_duration: if is_day { 3_000.0 } else { 0.0 },
+            wind_speed: 9.0 + 4.0 * swing,
+            wind_gusts: 18.0 + 8.0 * swing,
+            wind_direction: 240,
+            uv_index: if is_day { 5.0 * swing.max(0.0) } else { 0.0 },
+        });
+    }
+    forecasts
+}
+
+/// What the panel is fed, apart from the time.
+struct Inputs {
+    format: ClockFormat,
+    forecasts: WeatherForecastCache,
+    air_quality: AirQualityCache,
+    timers: Timers,
+    bg: u64,
+    stale: bool,
+}
+
+impl Inputs {
+    /// The clock, BG and forecast as the panel shows them at `now`.
+    fn draw(&self, now: &Zoned, display: &mut Display) -> anyhow::Result<()> {
+        let age = if self.stale {
+            20.minutes()
+        } else {
+            2.minutes()
+        };
+        let reading = BgReading {
+            bg: self.bg,
+            units: "mg/dl".into(),
+            timestamp: now.checked_sub(age).map_err(anyhow::Error::msg)?,
+        };
+        let ctx = Context {
+            now,
+            synced: true,
+            quality: Quality::Good,
+            format: &self.format,
+            locale: &ranodic::locale::ENGLISH,
+            bg: Some(&reading),
+            forecasts: &self.forecasts,
+            air_quality: &self.air_quality,
+            agenda: &[],
+            world: &[],
+            timers: &self.timers,
+            alarm: None,
+        };
+
+        display.clear(Rgb888::BLACK)?;
+        let shown: [&dyn Widget<Display>; 3] = [&widget::Clock, &widget::Bg, &widget::Forecast];
+        for widget in shown.into_iter().filter(|widget| widget.fresh(&ctx)) {
+            widget.draw_clipped(&ctx, display)?;
+        }
+        Ok(())
+    }
+}
+
+/// Each LED a dot with a dark gap around it, as on the panel.
+fn leds() -> OutputSettings {
+    OutputSettingsBuilder::new()
+        .scale(8)
+        .pixel_spacing(2)
+        .build()
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let tz = match args.next() {
+        Some(spec) => ranodic::timezone::parse(&spec)?,
+        None => TimeZone::UTC,
+    };
+    let format = match args.next() {
+        Some(spec) => ClockFormat::parse(&spec)?,
+        None => ClockFormat::default(),
+    };
+
+    let epoch = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    let epoch = Timestamp::from_second(epoch.as_secs() as i64).map_err(anyhow::Error::msg)?;
+    let inputs = Inputs {
+        format,
+        forecasts: canned_forecasts(&epoch.to_zoned(tz.clone())),
+        air_quality: AirQualityCache::new(),
+        timers: Timers::new(),
+        bg: 112,
+        stale: false,
+    };
+    let mut display = Display::new(Size::new(DISPLAY_W, DISPLAY_H));
+
+    #[cfg(feature = "sdl")]
+    return window(inputs, &mut display, epoch, tz);
+
+    #[cfg(not(feature = "sdl"))]
+    {
+        let hours = match args.next() {
+            Some(hours) => hours.parse()?,
+            None => 0,
+        };
+        let now = epoch
+            .checked_add(SignedDuration::from_hours(hours))
+            .map_err(anyhow::Error::msg)?
+            .to_zoned(tz);
+        inputs.draw(&now, &mut display)?;
+        display
+            .to_rgb_output_image(&leds())
+            .save_png("simulator.png")?;
+        println!("{}: saved simulator.png", now);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn window(
+    mut inputs: Inputs,
+    display: &mut Display,
+    epoch: Timestamp,
+    tz: TimeZone,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let mut shift = SignedDuration::ZERO;
+    let mut window = Window::new("ranodic", &leds());
+
+    loop {
+        let elapsed = SignedDuration::try_from(started.elapsed()).map_err(anyhow::Error::msg)?;
+        let now = epoch
+            .checked_add(elapsed + shift)
+            .map_err(anyhow::Error::msg)?
+            .to_zoned(tz.clone());
+        inputs.draw(&now, display)?;
+        window.update(display);
+
+        for event in window.events() {
+            match event {
+                SimulatorEvent::Quit
+                | SimulatorEvent::KeyDown {
+                    keycode: Keycode::ESCAPE,
+                    ..
+                } => return Ok(()),
+                SimulatorEvent::KeyDown { keycode, .. } => match keycode {
+                    Keycode::UP => inputs.bg = (inputs.bg + 10).min(400),
+                    Keycode::DOWN => inputs.bg = inputs.bg.saturating_sub(10).max(40),
+                    Keycode::S => inputs.stale = !inputs.stale,
+                    Keycode::RIGHT => shift += SignedDuration::from_hours(1),
+                    Keycode::LEFT => shift -= SignedDuration::from_hours(1),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,468 @@
+//! The agenda page: the next event of the calendar at `ICAL_URL`, with a
+//! countdown coloured by how soon it starts, and the one after it. The
+//! calendar is fetched every [`AGENDA_SUCCESS_INTERVAL`] seconds and read
+//! with [`crate::ical`], keeping [`HORIZON`] worth of occurrences.
+//!
+//! ```text
+//!   in 1h 05m          countdown, see Warning
+//!   Dentist, Dr.       summary
+//!   Thu 14:00          when
+//!   Fri Standup        and what's after it
+//! ```
+
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use alloc::{format, string::String, string::ToString, vec::Vec};
+
+use crate::log::{debug, error, info};
+use anyhow::{Result, anyhow};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use embassy_time::Timer;
+use embedded_graphics::{
+    Drawable,
+    draw_target::DrawTarget,
+    geometry::Point,
+    mono_font::{
+        MonoTextStyle,
+        iso_8859_1::{FONT_4X6, FONT_5X8},
+    },
+    pixelcolor::Rgb888,
+    text::{Baseline, Text},
+};
+use jiff::{SignedDuration, Timestamp, Zoned};
+use nanofish::{HttpHeader, HttpMethod};
+
+use crate::{
+    clockformat::fit,
+    ical::{Event, Parser},
+    locale::Locale,
+    timers::span_text,
+};
+#[cfg(target_os = "none")]
+use crate::{net::NET_REQUEST_QUEUE, ntp::zgettimeofday};
+
+pub const AGENDA_SUCCESS_INTERVAL: u64 = 900;
+const AGENDA_FAILURE_INTERVAL: u64 = 60;
+
+/// How far ahead occurrences are kept.
+pub const HORIZON: SignedDuration = SignedDuration::from_hours(7 * 24);
+
+// a response: headers and up to a WINDOW of the calendar
+#[cfg(not(feature = "esp32"))]
+const BUFFER_SZ: usize = 16384;
+#[cfg(feature = "esp32")]
+const BUFFER_SZ: usize = 8192;
+/// How much of the calendar each request asks for with `Range`, leaving room
+/// for the headers; the parser is fed one window at a time.
+const WINDOW: usize = BUFFER_SZ - 2048;
+
+pub static AGENDA: Mutex<CriticalSectionRawMutex, Vec<Event>> = Mutex::new(Vec::new());
+
+pub static AGENDA_PRESENT: AtomicBool = AtomicBool::new(false);
+
+pub static QUICKTRIES: AtomicU8 = AtomicU8::new(3);
+
+/// How soon an event starts, for the colour of its countdown.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Warning {
+    /// More than an hour away.
+    Later,
+    /// Within the hour.
+    Soon,
+    /// Within [`IMMINENT`].
+    Imminent,
+    /// Started and not over yet.
+    Started,
+}
+
+/// When the countdown turns red.
+pub const IMMINENT: SignedDuration = SignedDuration::from_mins(10);
+
+impl Warning {
+    pub fn at(event: &Event, now: Timestamp) -> Self {
+        let left = event.start.duration_since(now);
+        if left <= SignedDuration::ZERO {
+            Self::Started
+        } else if left <= IMMINENT {
+            Self::Imminent
+        } else if left <= SignedDuration::from_hours(1) {
+            Self::Soon
+        } else {
+            Self::Later
+        }
+    }
+
+    pub const fn color(self) -> Rgb888 {
+        match self {
+            Self::Later => palette::LATER,
+            Self::Soon => palette::SOON,
+            Self::Imminent => palette::IMMINENT,
+            Self::Started => palette::STARTED,
+        }
+    }
+}
+
+/// Events worth showing at `now`, soonest first: the ones that haven't
+/// ended, except all-day ones already under way, which would otherwise hold
+/// the page for days.
+pub fn upcoming(events: &[Event], now: Timestamp) -> impl Iterator<Item = &Event> {
+    events
+        .iter()
+        .filter(move |event| event.end > now && !(event.all_day && event.start <= now))
+}
+
+/// `in 1h 05m`, or `now` once it has started.
+pub fn countdown_text(event: &Event, now: Timestamp) -> String {
+    match Warning::at(event, now) {
+        Warning::Started => "now".to_string(),
+        _ => format!("in {}", span_text(event.start.duration_since(now))),
+    }
+}
+
+/// When an event is, as short as it can be said: `14:00` today, `Thu 14:00`
+/// on other days and just `Thu 19` for all-day ones.
+pub fn when_text(event: &Event, now: &Zoned, hour12: bool, locale: &Locale) -> String {
+    let start = event.start.to_zoned(now.time_zone().clone());
+    let day = locale.weekday(start.weekday());
+    if event.all_day {
+        return format!("{} {}", day, start.day());
+    }
+    let pattern = if hour12 { "%-I:%M%P" } else { "%H:%M" };
+    let time = jiff::fmt::strtime::format(pattern, &start).unwrap_or_default();
+    if start.date() == now.date() {
+        time
+    } else {
+        format!("{} {}", day, time)
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Query
+// ─────────────────────────────────────────────────────────────────────────────
+
+#[cfg(target_os = "none")]
+#[embassy_executor::task]
+pub async fn agenda_query(stack: embassy_net::Stack<'static>) {
+    debug!("agenda_query alive");
+    loop {
+        stack.wait_config_up().await;
+        debug!("agenda_query: network stack up");
+        match get_agenda(stack).await {
+            Ok(()) => {
+                Timer::after_secs(AGENDA_SUCCESS_INTERVAL).await;
+            }
+            Err(e) => {
+                error!("agenda_query: {}", e.to_string());
+                let qts = QUICKTRIES.load(Ordering::Relaxed);
+                if qts > 0 {
+                    info!("agenda_query: quicktry");
+                    Timer::after_secs(5).await;
+                    QUICKTRIES.store(qts - 1, Ordering::Relaxed);
+                } else {
+                    Timer::after_secs(AGENDA_FAILURE_INTERVAL).await;
+                }
+            }
+        }
+    }
+}
+
+/// `ICAL_URL`, with `webcal://` as the `https://` it stands for.
+#[cfg(target_os = "none")]
+fn url() -> String {
+    match crate::config::ICAL_URL.strip_prefix("webcal://") {
+        Some(rest) => format!("https://{}", rest),
+        None => crate::config::ICAL_URL.to_string(),
+    }
+}
+
+#[cfg(target_os = "none")]
+async fn get_agenda(stack: embassy_net::Stack<'static>) -> Result<()> {
+    let _guard = NET_REQUEST_QUEUE.lock().await;
+    let now = zgettimeofday().await;
+    let mut parser = Parser::new(now.timestamp(), HORIZON, now.time_zone().clone());
+    let mut buffer = [0u8; BUFFER_SZ];
+    let url = url();
+    let mut offset = 0;
+    let truncated = loop {
+        let range = format!("bytes={}-{}", offset, offset + WINDOW - 1);
+        let (response, bytes_read) = crate::net::WorkingClient::new(&stack)
+            .request(
+                HttpMethod::GET,
+                url.as_str(),
+                &[
+                    HttpHeader::user_agent("ranodic/0.1"),
+                    HttpHeader::accept("text/calendar"),
+                    HttpHeader::new("Range", &range),
+                ],
+                None,
+                &mut buffer,
+            )
+            .await
+            .map_err(anyhow::Error::msg)?;
+        debug!("get_agenda: {}: bytes_read: {}", range, bytes_read);
+        if !response.is_success() {
+            return Err(anyhow!(
+                "get_agenda: HTTP response failure: HTTP {} {}",
+                response.status_code.as_u16(),
+                response.status_code.text(),
+            ));
+        }
+        if offset == 0 {
+            crate::ntp::http_date(response.get_header("Date")).await;
+        }
+        // a server that ignores Range sends the whole feed, as much as fits
+        let partial = response.status_code.as_u16() == 206;
+        let total = response
+            .get_header("Content-Range")
+            .and_then(|range| range.rsplit('/').next())
+            .and_then(|total| total.trim().parse::<usize>().ok());
+        let chunked = response
+            .get_header("Transfer-Encoding")
+            .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+        let body_len = response.body.len();
+        let short = response.content_length().is_some_and(|len| len > body_len);
+        // the headers borrow the buffer until the response goes
+        drop(response);
+        let body_start = bytes_read - body_len;
+        let body = &mut buffer[body_start..bytes_read];
+        let (body_len, cut) = if chunked {
+            (dechunk(body), !body.ends_with(b"0\r\n\r\n"))
+        } else {
+            (body_len, short || bytes_read == BUFFER_SZ)
+        };
+        parser.feed_bytes(&body[..body_len]);
+        offset += body_len;
+        if !partial || cut {
+            break cut;
+        }
+        if body_len == 0 || total.is_none_or(|total| offset >= total) {
+            break false;
+        }
+    };
+    if truncated {
+        error!(
+            "get_agenda: the calendar was cut off after {} bytes",
+            offset
+        );
+    }
+    let events = parser.finish()?;
+    info!("get_agenda: {} events in the next week", events.len());
+    *AGENDA.lock().await = events;
+    if !AGENDA_PRESENT.load(Ordering::Relaxed) {
+        debug!("marking agenda present");
+        AGENDA_PRESENT.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// Undo `Transfer-Encoding: chunked` in place, since nanofish leaves the
+/// chunk sizes in the body. Returns how much of `body` is left; a chunk cut
+/// short by the end of the buffer is kept as far as it goes.
+pub fn dechunk(body: &mut [u8]) -> usize {
+    let (mut read, mut written) = (0, 0);
+    while read < body.len() {
+        let Some(eol) = body[read..].windows(2).position(|crlf| crlf == b"\r\n") else {
+            break;
+        };
+        // chunk extensions after a ';' don't matter
+        let size = core::str::from_utf8(&body[read..read + eol])
+            .ok()
+            .and_then(|line| line.split(';').next())
+            .and_then(|size| usize::from_str_radix(size.trim(), 16).ok());
+        let Some(size) = size.filter(|size| *size > 0) else {
+            break;
+        };
+        read += eol + 2;
+        let len = size.min(body.len() - read);
+        body.copy_within(read..read + len, written);
+        written += len;
+        read += len + 2;
+    }
+    written
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Drawing
+// ─────────────────────────────────────────────────────────────────────────────
+
+pub mod palette {
+    use embedded_graphics::pixelcolor::Rgb888;
+
+    pub const SUMMARY: Rgb888 = Rgb888::new(255, 255, 255);
+    pub const LABEL: Rgb888 = Rgb888::new(120, 120, 120);
+    pub const DIM: Rgb888 = Rgb888::new(60, 60, 60);
+
+    pub const LATER: Rgb888 = Rgb888::new(0, 200, 0);
+    pub const SOON: Rgb888 = Rgb888::new(255, 220, 0);
+    pub const IMMINENT: Rgb888 = Rgb888::new(230, 0, 0);
+    pub const STARTED: Rgb888 = Rgb888::new(60, 120, 255);
+}
+
+const DISPLAY_W: u32 = 64;
+const COUNTDOWN_Y: i32 = 0;
+const SUMMARY_Y: i32 = 9;
+const WHEN_Y: i32 = 19;
+const AFTER_Y: i32 = 26;
+
+/// The next event across the whole display, and a line for the one after;
+/// nothing if there's nothing coming up.
+pub fn draw_agenda<D>(
+    now: &Zoned,
+    events: &[Event],
+    hour12: bool,
+    locale: &Locale,
+    target: &mut D,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    let mut upcoming = upcoming(events, now.timestamp());
+    let Some(next) = upcoming.next() else {
+        return Ok(());
+    };
+    let countdown = countdown_text(next, now.timestamp());
+    Text::with_baseline(
+        &countdown,
+        Point::new(0, COUNTDOWN_Y),
+        MonoTextStyle::new(
+            fit(&countdown, &[&FONT_5X8, &FONT_4X6], DISPLAY_W),
+            Warning::at(next, now.timestamp()).color(),
+        ),
+        Baseline::Top,
+    )
+    .draw(target)?;
+    Text::with_baseline(
+        &next.summary,
+        Point::new(0, SUMMARY_Y),
+        MonoTextStyle::new(
+            fit(&next.summary, &[&FONT_5X8, &FONT_4X6], DISPLAY_W),
+            palette::SUMMARY,
+        ),
+        Baseline::Top,
+    )
+    .draw(target)?;
+    Text::with_baseline(
+        &when_text(next, now, hour12, locale),
+        Point::new(0, WHEN_Y),
+        MonoTextStyle::new(&FONT_4X6, palette::LABEL),
+        Baseline::Top,
+    )
+    .draw(target)?;
+    if let Some(after) = upcoming.next() {
+        let start = after.start.to_zoned(now.time_zone().clone());
+        let line = format!("{} {}", locale.weekday(start.weekday()), after.summary);
+        Text::with_baseline(
+            &line,
+            Point::new(0, AFTER_Y),
+            MonoTextStyle::new(&FONT_4X6, palette::DIM),
+            Baseline::Top,
+        )
+        .draw(target)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    fn event(summary: &str, start: &str, end: &str, all_day: bool) -> Event {
+        Event {
+            summary: summary.to_string(),
+            start: ts(start),
+            end: ts(end),
+            all_day,
+        }
+    }
+
+    #[test]
+    fn warnings_by_lead_time() {
+        let dentist = event(
+            "Dentist",
+            "2026-03-16T21:00:00Z",
+            "2026-03-16T22:00:00Z",
+            false,
+        );
+        let at = |now| Warning::at(&dentist, ts(now));
+        assert_eq!(at("2026-03-16T19:30:00Z"), Warning::Later);
+        assert_eq!(at("2026-03-16T20:00:00Z"), Warning::Soon);
+        assert_eq!(at("2026-03-16T20:50:00Z"), Warning::Imminent);
+        assert_eq!(at("2026-03-16T21:00:00Z"), Warning::Started);
+        assert_eq!(
+            countdown_text(&dentist, ts("2026-03-16T19:54:30Z")),
+            "in 1h 05m"
+        );
+        assert_eq!(countdown_text(&dentist, ts("2026-03-16T21:10:00Z")), "now");
+    }
+
+    #[test]
+    fn all_day_events_step_aside_once_started() {
+        let events = [
+            event(
+                "Spring break",
+                "2026-03-14T07:00:00Z",
+                "2026-03-21T07:00:00Z",
+                true,
+            ),
+            event(
+                "Standup",
+                "2026-03-16T16:30:00Z",
+                "2026-03-16T16:45:00Z",
+                false,
+            ),
+            event(
+                "Team lunch",
+                "2026-03-19T19:00:00Z",
+                "2026-03-19T20:00:00Z",
+                false,
+            ),
+        ];
+        let next: Vec<_> = upcoming(&events, ts("2026-03-16T16:40:00Z"))
+            .map(|event| event.summary.as_str())
+            .collect();
+        assert_eq!(next, ["Standup", "Team lunch"]);
+    }
+
+    #[test]
+    fn when_is_short_for_today() {
+        let now: Zoned = "2026-03-16T08:00:00-07:00[-07:00]".parse().unwrap();
+        let locale = &crate::locale::ENGLISH;
+        let today = event(
+            "Dentist",
+            "2026-03-16T21:00:00Z",
+            "2026-03-16T22:00:00Z",
+            false,
+        );
+        assert_eq!(when_text(&today, &now, false, locale), "14:00");
+        assert_eq!(when_text(&today, &now, true, locale), "2:00pm");
+        let thursday = event(
+            "Team lunch",
+            "2026-03-19T19:00:00Z",
+            "2026-03-19T20:00:00Z",
+            false,
+        );
+        assert_eq!(when_text(&thursday, &now, false, locale), "Thu 12:00");
+        let birthday = event(
+            "Birthday",
+            "2026-03-19T07:00:00Z",
+            "2026-03-20T07:00:00Z",
+            true,
+        );
+        assert_eq!(when_text(&birthday, &now, false, locale), "Thu 19");
+    }
+
+    #[test]
+    fn dechunks_in_place() {
+        let mut body = *b"b\r\nBEGIN:VCALE\r\n6;x=y\r\nNDAR\r\n\r\n0\r\n\r\n";
+        let len = dechunk(&mut body);
+        assert_eq!(&body[..len], b"BEGIN:VCALENDAR\r\n");
+        // cut off by the buffer
+        let mut body = *b"b\r\nBEGIN:VC";
+        let len = dechunk(&mut body);
+        assert_eq!(&body[..len], b"BEGIN:VC");
+    }
+}
@@ -0,0 +1,382 @@
+//! DS3231 aging-offset calibration. The chip is a TCXO good to ±2 ppm, but a
+//! given crystal tends to sit steadily off to one side, which the aging offset
+//! register trims in steps of about 0.1 ppm. NTP is the reference: every few
+//! hours [`crate::rtc`] measures the chip's offset to within a few ms, and once
+//! those samples span a couple of days their slope is the drift to trim away.
+//!
+//! The chip keeps the register through power loss; the history behind it is
+//! kept in RTC memory as [`Calibration::to_words`] so it survives our resets.
+
+/// Trim per aging-offset LSB at 25 °C, ppb. Positive offsets slow the chip.
+pub const PPB_PER_LSB: i64 = 100;
+/// Samples closer together than this are skipped, s.
+pub const SAMPLE_INTERVAL: i64 = 4 * 3600;
+/// How long the samples must span before their slope is believed, s.
+pub const MIN_SPAN: i64 = 2 * 86_400;
+pub const MIN_SAMPLES: usize = 4;
+/// Samples kept; at [`SAMPLE_INTERVAL`] that's about five days.
+pub const HISTORY: usize = 32;
+/// Length of [`Calibration::to_words`].
+pub const WORDS: usize = 5 + 2 * HISTORY;
+
+const MAGIC: i64 = 0x4147_494e_4731_0001;
+
+/// The chip's offset from NTP time at one moment.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DriftSample {
+    /// Unix seconds.
+    pub at: i64,
+    /// Chip minus NTP, µs.
+    pub offset: i64,
+}
+
+/// Least-squares slope of `samples`, in ppb; positive means the chip runs
+/// fast. `None` until there are [`MIN_SAMPLES`] spanning [`MIN_SPAN`].
+pub fn drift_ppb(samples: &[DriftSample]) -> Option<i64> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    if samples.len() < MIN_SAMPLES || last.at - first.at < MIN_SPAN {
+        return None;
+    }
+    let n = samples.len() as f64;
+    // relative to the first sample so the squares stay exact in an f64
+    let mean_t = samples
+        .iter()
+        .map(|s| (s.at - first.at) as f64)
+        .sum::<f64>()
+        / n;
+    let mean_o = samples.iter().map(|s| s.offset as f64).sum::<f64>() / n;
+    let (covariance, variance) = samples.iter().fold((0.0, 0.0), |(cov, var), s| {
+        let dt = (s.at - first.at) as f64 - mean_t;
+        (cov + dt * (s.offset as f64 - mean_o), var + dt * dt)
+    });
+    // µs per s is ppm
+    Some((covariance / variance * 1_000.0) as i64)
+}
+
+/// The aging offset that cancels `drift_ppb` on a chip currently trimmed to
+/// `current`.
+pub fn aging_offset(current: i8, drift_ppb: i64) -> i8 {
+    let steps = (drift_ppb + PPB_PER_LSB / 2).div_euclid(PPB_PER_LSB);
+    (current as i64 + steps).clamp(i8::MIN as i64, i8::MAX as i64) as i8
+}
+
+/// What the calibration knows, for the logs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Report {
+    pub aging: i8,
+    pub drift_ppb: Option<i64>,
+    pub samples: usize,
+    /// Seconds between the oldest and newest sample.
+    pub span: i64,
+}
+
+impl core::fmt::Display for Report {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "aging offset {}, ", self.aging)?;
+        match self.drift_ppb {
+            Some(drift) => write!(f, "drift {:+}ppb", drift)?,
+            None => write!(f, "drift not known yet")?,
+        }
+        write!(
+            f,
+            " from {} samples over {}h",
+            self.samples,
+            self.span / 3600
+        )
+    }
+}
+
+/// Drift history since the aging offset was last changed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Calibration {
+    aging: i8,
+    // added to measured offsets so rewriting the chip doesn't look like drift
+    correction: i64,
+    samples: [DriftSample; HISTORY],
+    len: usize,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Calibration {
+    pub const fn new() -> Self {
+        Self {
+            aging: 0,
+            correction: 0,
+            samples: [DriftSample { at: 0, offset: 0 }; HISTORY],
+            len: 0,
+        }
+    }
+
+    pub fn aging(&self) -> i8 {
+        self.aging
+    }
+
+    pub fn samples(&self) -> &[DriftSample] {
+        &self.samples[..self.len]
+    }
+
+    /// Start over if the chip's register isn't what this history was
+    /// measured with.
+    pub fn adopt(&mut self, aging: i8) {
+        if aging != self.aging {
+            *self = Self {
+                aging,
+                ..Self::new()
+            };
+        }
+    }
+
+    pub fn wants_sample(&self, at: i64) -> bool {
+        self.samples()
+            .last()
+            .is_none_or(|last| at - last.at >= SAMPLE_INTERVAL)
+    }
+
+    /// Record the chip `offset` µs from NTP time at unix second `at`, unless
+    /// the last sample is too recent. The oldest sample makes room.
+    pub fn observe(&mut self, at: i64, offset: i64) {
+        if !self.wants_sample(at) {
+            return;
+        }
+        if self.len == HISTORY {
+            self.samples.copy_within(1.., 0);
+            self.len -= 1;
+        }
+        self.samples[self.len] = DriftSample {
+            at,
+            offset: offset + self.correction,
+        };
+        self.len += 1;
+    }
+
+    /// The chip was moved by `by` µs.
+    pub fn rewritten(&mut self, by: i64) {
+        self.correction -= by;
+    }
+
+    /// The chip was set from a clock we can't measure against, or stopped.
+    pub fn restart(&mut self) {
+        self.correction = 0;
+        self.len = 0;
+    }
+
+    pub fn drift_ppb(&self) -> Option<i64> {
+        drift_ppb(self.samples())
+    }
+
+    /// A better aging offset, if the history shows one.
+    pub fn recommend(&self) -> Option<i8> {
+        let aging = aging_offset(self.aging, self.drift_ppb()?);
+        (aging != self.aging).then_some(aging)
+    }
+
+    /// The chip now runs with `aging`; what we knew about it no longer holds.
+    pub fn applied(&mut self, aging: i8) {
+        self.aging = aging;
+        self.restart();
+    }
+
+    pub fn report(&self) -> Report {
+        Report {
+            aging: self.aging,
+            drift_ppb: self.drift_ppb(),
+            samples: self.len,
+            span: match (self.samples().first(), self.samples().last()) {
+                (Some(first), Some(last)) => last.at - first.at,
+                _ => 0,
+            },
+        }
+    }
+
+    pub fn to_words(&self) -> [i64; WORDS] {
+        let mut words = [0; WORDS];
+        words[0] = MAGIC;
+        words[1] = self.aging as i64;
+        words[2] = self.correction;
+        words[3] = self.len as i64;
+        for (i, sample) in self.samples.iter().enumerate() {
+            words[4 + 2 * i] = sample.at;
+            words[5 + 2 * i] = sample.offset;
+        }
+        words[WORDS - 1] = checksum(&words[..WORDS - 1]);
+        words
+    }
+
+    /// `None` for memory that never held a calibration, or was torn by a
+    /// reset mid-write.
+    pub fn from_words(words: &[i64; WORDS]) -> Option<Self> {
+        if words[0] != MAGIC || words[WORDS - 1] != checksum(&words[..WORDS - 1]) {
+            return None;
+        }
+        let len = usize::try_from(words[3])
+            .ok()
+            .filter(|len| *len <= HISTORY)?;
+        let mut samples = [DriftSample::default(); HISTORY];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample = DriftSample {
+                at: words[4 + 2 * i],
+                offset: words[5 + 2 * i],
+            };
+        }
+        Some(Self {
+            aging: i8::try_from(words[1]).ok()?,
+            correction: words[2],
+            samples,
+            len,
+        })
+    }
+}
+
+/// FNV-1a over whole words, for what we keep in RTC memory.
+pub(crate) fn checksum(words: &[i64]) -> i64 {
+    words.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, word| {
+        (hash ^ *word as u64).wrapping_mul(0x0100_0000_01b3)
+    }) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 86_400;
+    const START: i64 = 1_760_000_000;
+
+    // a chip `ppb` fast, sampled every `SAMPLE_INTERVAL` with a few ms of
+    // measurement noise
+    fn drifting(calibration: &mut Calibration, ppb: i64, days: i64) {
+        let noise = [3_000, -4_000, 1_000, -2_500, 4_500, 0, -1_500];
+        let samples = days * DAY / SAMPLE_INTERVAL;
+        for i in 0..=samples {
+            let elapsed = i * SAMPLE_INTERVAL;
+            let offset = elapsed * ppb / 1_000 + noise[i as usize % noise.len()];
+            calibration.observe(START + elapsed, offset);
+        }
+    }
+
+    #[test]
+    fn needs_days_of_history() {
+        let mut calibration = Calibration::new();
+        drifting(&mut calibration, 1_500, 1);
+        assert_eq!(calibration.drift_ppb(), None);
+        assert_eq!(calibration.recommend(), None);
+        assert_eq!(drift_ppb(&[]), None);
+    }
+
+    #[test]
+    fn fast_chip_is_slowed() {
+        let mut calibration = Calibration::new();
+        drifting(&mut calibration, 1_500, 3);
+        let drift = calibration.drift_ppb().unwrap();
+        assert!((drift - 1_500).abs() < 50, "{drift}");
+        assert_eq!(calibration.recommend(), Some(15));
+    }
+
+    #[test]
+    fn slow_chip_is_sped_up_from_its_current_trim() {
+        let mut calibration = Calibration::new();
+        calibration.adopt(10);
+        drifting(&mut calibration, -800, 4);
+        assert_eq!(calibration.recommend(), Some(2));
+    }
+
+    #[test]
+    fn trimmed_chip_is_left_alone() {
+        let mut calibration = Calibration::new();
+        drifting(&mut calibration, 30, 3);
+        assert_eq!(calibration.recommend(), None);
+    }
+
+    #[test]
+    fn aging_offset_rounds_and_saturates() {
+        assert_eq!(aging_offset(0, 149), 1);
+        assert_eq!(aging_offset(0, 150), 2);
+        assert_eq!(aging_offset(0, -149), -1);
+        assert_eq!(aging_offset(120, 2_000), i8::MAX);
+        assert_eq!(aging_offset(-120, -2_000), i8::MIN);
+    }
+
+    #[test]
+    fn rewrites_are_not_drift() {
+        let mut calibration = Calibration::new();
+        let ppb = 2_000;
+        for i in 0..=18 {
+            let elapsed = i * SAMPLE_INTERVAL;
+            // the chip is put back to zero every day
+            let written = elapsed / DAY * DAY;
+            calibration.observe(START + elapsed, (elapsed - written) * ppb / 1_000);
+            if (elapsed + SAMPLE_INTERVAL) % DAY == 0 {
+                let offset = (elapsed + SAMPLE_INTERVAL - written) * ppb / 1_000;
+                calibration.rewritten(-offset);
+            }
+        }
+        let drift = calibration.drift_ppb().unwrap();
+        assert!((drift - ppb).abs() < 10, "{drift}");
+    }
+
+    #[test]
+    fn samples_are_spaced_and_bounded() {
+        let mut calibration = Calibration::new();
+        calibration.observe(START, 0);
+        calibration.observe(START + 60, 1_000_000);
+        assert_eq!(calibration.samples().len(), 1);
+        for i in 1..HISTORY as i64 + 5 {
+            calibration.observe(START + i * SAMPLE_INTERVAL, 0);
+        }
+        assert_eq!(calibration.samples().len(), HISTORY);
+        assert_eq!(calibration.samples()[0].at, START + 5 * SAMPLE_INTERVAL);
+    }
+
+    #[test]
+    fn applying_or_adopting_a_new_trim_starts_over() {
+        let mut calibration = Calibration::new();
+        drifting(&mut calibration, 1_500, 3);
+        calibration.applied(15);
+        assert_eq!(calibration.report().samples, 0);
+        assert_eq!(calibration.aging(), 15);
+        drifting(&mut calibration, 0, 1);
+        calibration.adopt(15);
+        assert!(calibration.report().samples > 0);
+        calibration.adopt(-3);
+        assert_eq!(calibration.report().samples, 0);
+    }
+
+    #[test]
+    fn report_reads_as_a_line() {
+        let report = Report {
+            aging: -3,
+            drift_ppb: Some(1_480),
+            samples: 19,
+            span: 72 * 3600,
+        };
+        assert_eq!(
+            alloc::format!("{}", report),
+            "aging offset -3, drift +1480ppb from 19 samples over 72h"
+        );
+        assert_eq!(
+            alloc::format!("{}", Calibration::new().report()),
+            "aging offset 0, drift not known yet from 0 samples over 0h"
+        );
+    }
+
+    #[test]
+    fn words_round_trip_and_reject_garbage() {
+        let mut calibration = Calibration::new();
+        calibration.adopt(-7);
+        calibration.rewritten(250_000);
+        drifting(&mut calibration, 700, 2);
+        let words = calibration.to_words();
+        assert_eq!(Calibration::from_words(&words), Some(calibration));
+
+        assert_eq!(Calibration::from_words(&[0; WORDS]), None);
+        let mut torn = words;
+        torn[7] += 1;
+        assert_eq!(Calibration::from_words(&torn), None);
+    }
+}
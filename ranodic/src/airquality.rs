@@ -0,0 +1,518 @@
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use alloc::{format, string::ToString, vec::Vec};
+
+use crate::log::{debug, error, info};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use embassy_time::Timer;
+use embedded_graphics::{
+    Drawable,
+    draw_target::DrawTarget,
+    geometry::Point,
+    mono_font::{
+        MonoTextStyle,
+        ascii::{FONT_4X6, FONT_5X8},
+    },
+    pixelcolor::Rgb888,
+    text::{Baseline, Text},
+};
+use itertools::izip;
+use jiff::{ToSpan, Zoned};
+use nanofish::{HttpHeader, HttpMethod, ResponseBody, mime_types};
+use serde_json::Value;
+
+use anyhow::{Result, anyhow};
+
+#[cfg(target_os = "none")]
+use crate::{
+    net::NET_REQUEST_QUEUE,
+    ntp::zgettimeofday,
+    weather::{WEATHER_LATITUDE, WEATHER_LONGITUDE},
+};
+
+const AIRQUALITY_SUCCESS_INTERVAL: u64 = 3600;
+const AIRQUALITY_FAILURE_INTERVAL: u64 = 60;
+
+static BUFFER_SZ: usize = 8192;
+
+pub static AIR_QUALITY: Mutex<CriticalSectionRawMutex, AirQualityCache> =
+    Mutex::new(AirQualityCache::new());
+
+pub static AIR_QUALITY_PRESENT: AtomicBool = AtomicBool::new(false);
+
+pub static QUICKTRIES: AtomicU8 = AtomicU8::new(3);
+
+// ─────────────────────────────────────────────────────────────────────────────
+// AQI scales
+// ─────────────────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AqiScale {
+    /// US EPA AQI, 0–500.
+    Us,
+    /// European Environment Agency AQI, 0–100+.
+    European,
+}
+
+#[cfg(not(feature = "europeanaqi"))]
+pub const AQI_SCALE: AqiScale = AqiScale::Us;
+#[cfg(feature = "europeanaqi")]
+pub const AQI_SCALE: AqiScale = AqiScale::European;
+
+impl AqiScale {
+    /// Open-Meteo hourly variable name for this scale.
+    pub const fn variable(self) -> &'static str {
+        match self {
+            Self::Us => "us_aqi",
+            Self::European => "european_aqi",
+        }
+    }
+
+    /// Six-step category, 0 (good) to 5 (hazardous / extremely poor).
+    pub fn category(self, aqi: u16) -> AqiCategory {
+        let bounds: [u16; 5] = match self {
+            Self::Us => [50, 100, 150, 200, 300],
+            Self::European => [20, 40, 60, 80, 100],
+        };
+        match bounds.iter().position(|bound| aqi <= *bound) {
+            Some(0) => AqiCategory::Good,
+            Some(1) => AqiCategory::Moderate,
+            Some(2) => AqiCategory::Sensitive,
+            Some(3) => AqiCategory::Unhealthy,
+            Some(4) => AqiCategory::VeryUnhealthy,
+            _ => AqiCategory::Hazardous,
+        }
+    }
+}
+
+/// AQI category, named after the US scale; the European "fair", "moderate",
+/// "poor", "very poor" and "extremely poor" bands map on in order.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AqiCategory {
+    Good,
+    Moderate,
+    Sensitive,
+    Unhealthy,
+    VeryUnhealthy,
+    Hazardous,
+}
+
+impl AqiCategory {
+    pub const fn color(self) -> Rgb888 {
+        match self {
+            Self::Good => palette::AQI_GOOD,
+            Self::Moderate => palette::AQI_MODERATE,
+            Self::Sensitive => palette::AQI_SENSITIVE,
+            Self::Unhealthy => palette::AQI_UNHEALTHY,
+            Self::VeryUnhealthy => palette::AQI_VERY_UNHEALTHY,
+            Self::Hazardous => palette::AQI_HAZARDOUS,
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Pollen
+// ─────────────────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Pollen {
+    Alder,
+    Birch,
+    Grass,
+    Mugwort,
+    Olive,
+    Ragweed,
+}
+
+impl Pollen {
+    pub const ALL: [Pollen; 6] = [
+        Self::Alder,
+        Self::Birch,
+        Self::Grass,
+        Self::Mugwort,
+        Self::Olive,
+        Self::Ragweed,
+    ];
+
+    /// Open-Meteo hourly variable name.
+    pub const fn variable(self) -> &'static str {
+        match self {
+            Self::Alder => "alder_pollen",
+            Self::Birch => "birch_pollen",
+            Self::Grass => "grass_pollen",
+            Self::Mugwort => "mugwort_pollen",
+            Self::Olive => "olive_pollen",
+            Self::Ragweed => "ragweed_pollen",
+        }
+    }
+
+    /// Two-letter label that fits the AQI page.
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Alder => "Al",
+            Self::Birch => "Bi",
+            Self::Grass => "Gr",
+            Self::Mugwort => "Mu",
+            Self::Olive => "Ol",
+            Self::Ragweed => "Ra",
+        }
+    }
+}
+
+/// Pollen load in grains/m³, bucketed into the usual none/low/moderate/high/
+/// very high bands.
+pub fn pollen_color(grains: f32) -> Rgb888 {
+    if grains < 1.0 {
+        palette::DIM
+    } else if grains < 20.0 {
+        palette::AQI_GOOD
+    } else if grains < 100.0 {
+        palette::AQI_MODERATE
+    } else if grains < 500.0 {
+        palette::AQI_SENSITIVE
+    } else {
+        palette::AQI_UNHEALTHY
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Readings and cache
+// ─────────────────────────────────────────────────────────────────────────────
+
+#[derive(Debug)]
+pub struct AirQuality {
+    pub timespan: Zoned,
+    pub aqi: u16,
+    pub pm2_5: f32,
+    /// Indexed like [`Pollen::ALL`]; `None` outside the CAMS Europe domain.
+    pub pollen: [Option<f32>; 6],
+}
+
+impl AirQuality {
+    pub fn new(time: &str, aqi: u16, pm2_5: f32, pollen: [Option<f32>; 6]) -> Result<Self> {
+        let timespan = crate::timezone::from_utc_civil(time)?;
+        Ok(Self {
+            timespan,
+            aqi,
+            pm2_5,
+            pollen,
+        })
+    }
+
+    pub fn is_during(&self, timestamp: &Zoned) -> bool {
+        *timestamp >= self.timespan && timestamp < &self.timespan + 1.hour()
+    }
+
+    pub fn is_prior(&self, timestamp: &Zoned) -> bool {
+        timestamp >= &self.timespan + 1.hour()
+    }
+
+    /// Pollen types with data, heaviest load first.
+    pub fn pollen_by_load(&self) -> Vec<(Pollen, f32)> {
+        let mut loads: Vec<(Pollen, f32)> = Pollen::ALL
+            .iter()
+            .zip(self.pollen.iter())
+            .filter_map(|(kind, grains)| grains.map(|grains| (*kind, grains)))
+            .collect();
+        loads.sort_by(|a, b| b.1.total_cmp(&a.1));
+        loads
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for AirQuality {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(
+            fmt,
+            "AirQuality('{}', {}, {}, {})",
+            self.timespan.to_string(),
+            self.aqi,
+            self.pm2_5,
+            self.pollen
+        );
+    }
+}
+
+pub struct AirQualityCache {
+    readings: Vec<AirQuality>,
+}
+
+impl Default for AirQualityCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AirQualityCache {
+    pub const fn new() -> Self {
+        Self {
+            readings: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, timestamp: &Zoned) -> Option<&AirQuality> {
+        self.readings
+            .iter()
+            .find(|reading| reading.is_during(timestamp))
+    }
+
+    pub fn upsert(&mut self, reading: AirQuality) {
+        if let Some(idx) = self
+            .readings
+            .iter()
+            .position(|other| other.timespan == reading.timespan)
+        {
+            self.readings.remove(idx);
+        }
+        self.readings.push(reading);
+        if !AIR_QUALITY_PRESENT.load(Ordering::Relaxed) {
+            debug!("marking air quality present");
+            AIR_QUALITY_PRESENT.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(target_os = "none")]
+    pub async fn expire(&mut self) {
+        let timestamp = zgettimeofday().await;
+        self.remove_before(&timestamp);
+    }
+
+    pub fn remove_before(&mut self, timestamp: &Zoned) {
+        self.readings.retain(|reading| !reading.is_prior(timestamp));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.readings.is_empty()
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Query
+// ─────────────────────────────────────────────────────────────────────────────
+
+#[cfg(target_os = "none")]
+#[embassy_executor::task]
+pub async fn airquality_query(stack: embassy_net::Stack<'static>) {
+    debug!("airquality_query alive");
+    loop {
+        stack.wait_config_up().await;
+        debug!("airquality_query: network stack up");
+        match get_air_quality(stack).await {
+            Ok(()) => {
+                AIR_QUALITY.lock().await.expire().await;
+                Timer::after_secs(AIRQUALITY_SUCCESS_INTERVAL).await;
+            }
+            Err(e) => {
+                error!("airquality_query: {}", e.to_string());
+                let qts = QUICKTRIES.load(Ordering::Relaxed);
+                if qts > 0 {
+                    info!("airquality_query: quicktry");
+                    Timer::after_secs(5).await;
+                    QUICKTRIES.store(qts - 1, Ordering::Relaxed);
+                } else {
+                    Timer::after_secs(AIRQUALITY_FAILURE_INTERVAL).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "none")]
+async fn get_air_quality(stack: embassy_net::Stack<'static>) -> Result<()> {
+    let mut buffer = [0u8; BUFFER_SZ];
+    let _guard = NET_REQUEST_QUEUE.lock().await;
+    let (response, bytes_read) = crate::net::WorkingClient::new(&stack)
+        .request(
+            HttpMethod::GET,
+            format!(
+                "https://air-quality-api.open-meteo.com/v1/air-quality?\
+                latitude={}&\
+                longitude={}&\
+                hourly={},pm2_5,alder_pollen,birch_pollen,grass_pollen,mugwort_pollen,olive_pollen,ragweed_pollen&\
+                timezone=GMT&\
+                forecast_days=1",
+                WEATHER_LATITUDE,
+                WEATHER_LONGITUDE,
+                AQI_SCALE.variable()
+            )
+            .as_str(),
+            &[
+                HttpHeader::user_agent("ranodic/0.1"),
+                HttpHeader::accept(mime_types::JSON),
+            ],
+            None,
+            &mut buffer,
+        )
+        .await
+        .map_err(anyhow::Error::msg)?;
+    debug!("get_air_quality: bytes_read: {}", bytes_read);
+    if !response.is_success() {
+        return Err(anyhow!(
+            "get_air_quality: HTTP response failure: HTTP {} {}",
+            response.status_code.as_u16(),
+            response.status_code.text(),
+        ));
+    }
+    if let ResponseBody::Text(jason) = response.body {
+        digest_body(jason).await
+    } else {
+        Err(anyhow!("get_air_quality: unexpected response format"))
+    }
+}
+
+#[cfg(target_os = "none")]
+async fn digest_body(jason: &str) -> Result<()> {
+    // same shape as the forecast response: a suspect length line, then JSON
+    for line in jason.lines().skip(1) {
+        let jobj = match serde_json::from_str::<Value>(line) {
+            Ok(jobj) => jobj,
+            Err(e) => {
+                debug!(
+                    "airquality digest_body: couldn't deserialize JSON: {}",
+                    e.to_string().as_str()
+                );
+                continue;
+            }
+        };
+        let Some(hourly) = jobj["hourly"].as_object() else {
+            continue;
+        };
+        let (Some(time), Some(aqi), Some(pm2_5)) = (
+            hourly["time"].as_array(),
+            hourly[AQI_SCALE.variable()].as_array(),
+            hourly["pm2_5"].as_array(),
+        ) else {
+            error!("airquality digest_body: missing hourly columns");
+            return Err(anyhow!("air quality JSON missing hourly columns"));
+        };
+        let pollen = Pollen::ALL.map(|kind| hourly[kind.variable()].as_array());
+        let mut cache = AIR_QUALITY.lock().await;
+        for (idx, (time, aqi, pm2_5)) in izip!(time, aqi, pm2_5).enumerate() {
+            // AQI is null for the first hours of a run; skip those rather than fail
+            let (Some(time), Some(aqi), Some(pm2_5)) =
+                (time.as_str(), aqi.as_u64(), pm2_5.as_f64())
+            else {
+                continue;
+            };
+            let grains = pollen.map(|column| {
+                column
+                    .and_then(|column| column.get(idx))
+                    .and_then(Value::as_f64)
+                    .map(|grains| grains as f32)
+            });
+            match AirQuality::new(time, aqi.min(u16::MAX as u64) as u16, pm2_5 as f32, grains) {
+                Ok(reading) => cache.upsert(reading),
+                Err(e) => error!("airquality digest_body: reading bogus: {}", e.to_string()),
+            }
+        }
+        return Ok(());
+    }
+    Err(anyhow!("airquality digest_body: never found data"))
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Drawing
+//
+// Lower 16 rows, same region as the forecast:
+//
+//   row 0:  "AQI" 123      "PM" 35
+//   row 1:  "Gr" 45        "Bi" 12     (two heaviest pollen loads)
+// ─────────────────────────────────────────────────────────────────────────────
+
+pub mod palette {
+    use embedded_graphics::pixelcolor::Rgb888;
+
+    pub const LABEL: Rgb888 = Rgb888::new(120, 120, 120);
+    pub const DIM: Rgb888 = Rgb888::new(60, 60, 60);
+
+    // EPA AQI colours, toned down a little for the panel
+    pub const AQI_GOOD: Rgb888 = Rgb888::new(0, 200, 0);
+    pub const AQI_MODERATE: Rgb888 = Rgb888::new(255, 220, 0);
+    pub const AQI_SENSITIVE: Rgb888 = Rgb888::new(255, 110, 0);
+    pub const AQI_UNHEALTHY: Rgb888 = Rgb888::new(230, 0, 0);
+    pub const AQI_VERY_UNHEALTHY: Rgb888 = Rgb888::new(140, 50, 160);
+    pub const AQI_HAZARDOUS: Rgb888 = Rgb888::new(126, 0, 35);
+}
+
+const REGION_TOP: i32 = 16;
+const ROW0_Y: i32 = 1;
+const ROW1_Y: i32 = 9;
+
+/// Draw the air-quality page into the lower 16 rows of the display.
+pub fn draw_air_quality<D>(reading: &AirQuality, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    let label = MonoTextStyle::new(&FONT_4X6, palette::LABEL);
+    let y = REGION_TOP + ROW0_Y;
+
+    Text::with_baseline("AQI", Point::new(0, y + 1), label, Baseline::Top).draw(target)?;
+    Text::with_baseline(
+        &reading.aqi.min(999).to_string(),
+        Point::new(13, y),
+        MonoTextStyle::new(&FONT_5X8, AQI_SCALE.category(reading.aqi).color()),
+        Baseline::Top,
+    )
+    .draw(target)?;
+
+    Text::with_baseline("PM", Point::new(36, y + 1), label, Baseline::Top).draw(target)?;
+    Text::with_baseline(
+        &(libm::roundf(reading.pm2_5) as u16).min(999).to_string(),
+        Point::new(45, y),
+        MonoTextStyle::new(&FONT_5X8, pm2_5_color(reading.pm2_5)),
+        Baseline::Top,
+    )
+    .draw(target)?;
+
+    let y = REGION_TOP + ROW1_Y;
+    for ((kind, grains), x) in reading.pollen_by_load().into_iter().zip([0, 36]) {
+        Text::with_baseline(kind.label(), Point::new(x, y + 1), label, Baseline::Top)
+            .draw(target)?;
+        Text::with_baseline(
+            &(libm::roundf(grains) as u16).min(999).to_string(),
+            Point::new(x + 9, y),
+            MonoTextStyle::new(&FONT_5X8, pollen_color(grains)),
+            Baseline::Top,
+        )
+        .draw(target)?;
+    }
+    Ok(())
+}
+
+/// PM2.5 in µg/m³, coloured by the US 24-hour breakpoints.
+fn pm2_5_color(pm2_5: f32) -> Rgb888 {
+    if pm2_5 <= 9.0 {
+        AqiCategory::Good.color()
+    } else if pm2_5 <= 35.4 {
+        AqiCategory::Moderate.color()
+    } else if pm2_5 <= 55.4 {
+        AqiCategory::Sensitive.color()
+    } else if pm2_5 <= 125.4 {
+        AqiCategory::Unhealthy.color()
+    } else if pm2_5 <= 225.4 {
+        AqiCategory::VeryUnhealthy.color()
+    } else {
+        AqiCategory::Hazardous.color()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn us_categories() {
+        assert_eq!(AqiScale::Us.category(0), AqiCategory::Good);
+        assert_eq!(AqiScale::Us.category(50), AqiCategory::Good);
+        assert_eq!(AqiScale::Us.category(51), AqiCategory::Moderate);
+        assert_eq!(AqiScale::Us.category(150), AqiCategory::Sensitive);
+        assert_eq!(AqiScale::Us.category(301), AqiCategory::Hazardous);
+    }
+
+    #[test]
+    fn european_categories() {
+        assert_eq!(AqiScale::European.category(15), AqiCategory::Good);
+        assert_eq!(AqiScale::European.category(45), AqiCategory::Sensitive);
+        assert_eq!(AqiScale::European.category(120), AqiCategory::Hazardous);
+    }
+}
@@ -0,0 +1,505 @@
+//! Alarms on a weekly schedule. Set at boot from `ALARMS`, a `;`-separated
+//! list of `HH:MM` times, each optionally followed by the days it rings:
+//! `07:00 mon-fri; 09:30 sat,sun; 06:15`. Days are `mon`..`sun`, ranges of
+//! them, `weekdays`, `weekends` or `daily` (the default).
+//!
+//! Times are wall-clock times in the display zone, so a 07:00 alarm stays at
+//! 07:00 across DST changes. One that falls in the hour skipped in spring
+//! rings at the same time after it (02:30 becomes 03:30), and one in the hour
+//! repeated in autumn rings only the first time round.
+//!
+//! An alarm rings for [`RING_LIMIT`] unless it's dismissed, or snoozed for
+//! [`SNOOZE`]; whether one is ringing is worked out afresh from the clock
+//! each time by [`ringing`], so nothing needs to tick.
+
+use core::cell::RefCell;
+
+use alloc::{format, string::String, vec::Vec};
+use anyhow::{Result, anyhow};
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embedded_graphics::{
+    Drawable,
+    draw_target::DrawTarget,
+    geometry::Point,
+    mono_font::{
+        MonoTextStyle,
+        iso_8859_1::{FONT_5X8, FONT_6X10, FONT_7X13},
+    },
+    pixelcolor::{Rgb888, RgbColor},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+};
+use jiff::{
+    SignedDuration, Timestamp, ToSpan, Zoned,
+    civil::{Time, Weekday},
+};
+
+use crate::aging::checksum;
+use crate::clockformat::fit;
+
+/// An alarm nobody answers gives up after this.
+pub const RING_LIMIT: SignedDuration = SignedDuration::from_mins(30);
+pub const SNOOZE: SignedDuration = SignedDuration::from_mins(9);
+/// Length of [`Silence::to_words`].
+pub const WORDS: usize = 4;
+const MAGIC: i64 = 0x414c_524d_0000_0001;
+// flash on for half of this, off for the other half
+const FLASH_PERIOD_MS: i128 = 1000;
+const DISPLAY_W: u32 = 64;
+const DISPLAY_H: i32 = 32;
+
+const DAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Days of the week as bits, Monday the lowest.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Weekdays(pub u8);
+
+impl Weekdays {
+    pub const DAILY: Self = Self(0x7f);
+    pub const WEEKDAYS: Self = Self(0x1f);
+    pub const WEEKENDS: Self = Self(0x60);
+
+    pub fn contains(&self, day: Weekday) -> bool {
+        self.0 & 1 << day.to_monday_zero_offset() != 0
+    }
+
+    /// `mon,wed,fri`, `mon-fri`, `sat-sun`, `weekdays`, `weekends`, `daily`,
+    /// or any `,`-separated mix of them.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut mask = 0;
+        for part in spec.split(',').map(str::trim) {
+            mask |= match part {
+                "daily" => Self::DAILY.0,
+                "weekdays" => Self::WEEKDAYS.0,
+                "weekends" => Self::WEEKENDS.0,
+                _ => match part.split_once('-') {
+                    // wraps past Sunday, e.g. fri-mon
+                    Some((from, to)) => {
+                        let (from, to) = (day(from)?, day(to)?);
+                        (0..=(to + 7 - from) % 7)
+                            .map(|offset| (from + offset) % 7)
+                            .fold(0, |mask, day| mask | 1 << day)
+                    }
+                    None => 1 << day(part)?,
+                },
+            };
+        }
+        Ok(Self(mask))
+    }
+}
+
+fn day(name: &str) -> Result<u8> {
+    let name = name.trim();
+    DAY_NAMES
+        .iter()
+        .position(|day| {
+            name.get(..3)
+                .is_some_and(|name| day.eq_ignore_ascii_case(name))
+        })
+        .map(|day| day as u8)
+        .ok_or(anyhow!("unknown day {}", name))
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Alarm {
+    pub time: Time,
+    pub days: Weekdays,
+}
+
+impl Alarm {
+    /// `HH:MM`, optionally followed by [`Weekdays::parse`] days.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (time, days) = spec.split_once(' ').unwrap_or((spec, "daily"));
+        Ok(Self {
+            time: time
+                .parse()
+                .map_err(|_| anyhow!("bad alarm time {}", time))?,
+            days: Weekdays::parse(days)?,
+        })
+    }
+
+    /// When it last went off, at or before `now`, in `now`'s zone.
+    pub fn latest(&self, now: &Zoned) -> Option<Zoned> {
+        (0..=7)
+            .filter_map(|ago| self.on(now, -ago))
+            .find(|fire| fire <= now)
+    }
+
+    /// When it next goes off after `now`.
+    pub fn next(&self, now: &Zoned) -> Option<Zoned> {
+        (0..=7)
+            .filter_map(|ahead| self.on(now, ahead))
+            .find(|fire| fire > now)
+    }
+
+    // when it goes off `days` from `now`'s date, if it does that day;
+    // jiff's compatible disambiguation moves a skipped time past the gap and
+    // takes the earlier of a repeated one
+    fn on(&self, now: &Zoned, days: i64) -> Option<Zoned> {
+        let date = now.date().checked_add(days.days()).ok()?;
+        if !self.days.contains(date.weekday()) {
+            return None;
+        }
+        date.to_datetime(self.time)
+            .to_zoned(now.time_zone().clone())
+            .ok()
+    }
+}
+
+pub fn parse(spec: &str) -> Result<Vec<Alarm>> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|alarm| !alarm.is_empty())
+        .map(Alarm::parse)
+        .collect()
+}
+
+/// What's been done about the alarms: the last dismiss and snooze.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Silence {
+    pub dismissed: Option<Timestamp>,
+    pub snoozed: Option<Timestamp>,
+}
+
+impl Silence {
+    pub const fn new() -> Self {
+        Self {
+            dismissed: None,
+            snoozed: None,
+        }
+    }
+
+    pub fn to_words(&self) -> [i64; WORDS] {
+        let micros = |time: Option<Timestamp>| time.map_or(0, |time| time.as_microsecond());
+        let mut words = [MAGIC, micros(self.dismissed), micros(self.snoozed), 0];
+        words[WORDS - 1] = checksum(&words[..WORDS - 1]);
+        words
+    }
+
+    /// `None` for memory that was never written.
+    pub fn from_words(words: &[i64; WORDS]) -> Option<Self> {
+        if words[0] != MAGIC || words[WORDS - 1] != checksum(&words[..WORDS - 1]) {
+            return None;
+        }
+        let time = |micros| match micros {
+            0 => None,
+            micros => Timestamp::from_microsecond(micros).ok(),
+        };
+        Some(Self {
+            dismissed: time(words[1]),
+            snoozed: time(words[2]),
+        })
+    }
+}
+
+/// Since when an alarm has been ringing at `now`: the latest of `alarms` to
+/// go off, unless it was dismissed since or is snoozed, and for no longer
+/// than [`RING_LIMIT`]. A snooze that runs out starts it ringing again.
+pub fn ringing(alarms: &[Alarm], now: &Zoned, silence: &Silence) -> Option<Timestamp> {
+    let fired = alarms
+        .iter()
+        .filter_map(|alarm| alarm.latest(now))
+        .map(|fire| fire.timestamp())
+        .max()?;
+    if silence
+        .dismissed
+        .is_some_and(|dismissed| dismissed >= fired)
+    {
+        return None;
+    }
+    let since = match silence.snoozed {
+        Some(snoozed) if snoozed >= fired => snoozed + SNOOZE,
+        _ => fired,
+    };
+    let rung = now.timestamp().duration_since(since);
+    (!rung.is_negative() && rung < RING_LIMIT).then_some(since)
+}
+
+/// The lit half of the flash, for an alarm ringing since `since`.
+pub fn flash_on(since: Timestamp, now: Timestamp) -> bool {
+    now.duration_since(since)
+        .as_millis()
+        .rem_euclid(FLASH_PERIOD_MS)
+        < FLASH_PERIOD_MS / 2
+}
+
+/// The whole panel, lit white on the on half of the flash, with `time` in
+/// the middle.
+pub fn draw_alarm<D>(time: &str, on: bool, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    let (background, color) = if on {
+        (Rgb888::WHITE, Rgb888::BLACK)
+    } else {
+        (Rgb888::BLACK, Rgb888::WHITE)
+    };
+    target.clear(background)?;
+    let font = fit(time, &[&FONT_7X13, &FONT_6X10, &FONT_5X8], DISPLAY_W);
+    Text::with_text_style(
+        time,
+        Point::new(DISPLAY_W as i32 / 2, DISPLAY_H / 2),
+        MonoTextStyle::new(font, color),
+        TextStyleBuilder::new()
+            .alignment(Alignment::Center)
+            .baseline(Baseline::Middle)
+            .build(),
+    )
+    .draw(target)?;
+    Ok(())
+}
+
+static ALARMS: Mutex<CriticalSectionRawMutex, RefCell<Vec<Alarm>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+static SILENCE: Mutex<CriticalSectionRawMutex, RefCell<Silence>> =
+    Mutex::new(RefCell::new(Silence::new()));
+
+// Snooze and dismiss are console commands; without them a ringing alarm
+// could only be waited out.
+#[cfg(not(feature = "console"))]
+const _: () = assert!(
+    crate::config::ALARMS.is_empty(),
+    "ALARMS needs the \"console\" feature to snooze and dismiss them"
+);
+
+pub fn get() -> Vec<Alarm> {
+    ALARMS.lock(|alarms| alarms.borrow().clone())
+}
+
+pub fn set(alarms: Vec<Alarm>) {
+    ALARMS.lock(|cell| cell.replace(alarms));
+}
+
+/// Parse `spec` and ring those; none ring if it doesn't parse.
+pub fn set_from_str(spec: &str) -> Result<()> {
+    set(parse(spec)?);
+    Ok(())
+}
+
+pub fn silence() -> Silence {
+    SILENCE.lock(|silence| *silence.borrow())
+}
+
+pub fn set_silence(silence: Silence) {
+    SILENCE.lock(|cell| cell.replace(silence));
+}
+
+/// Since when the configured alarms have been ringing at `now`.
+pub fn ringing_now(now: &Zoned) -> Option<Timestamp> {
+    ALARMS.lock(|alarms| ringing(&alarms.borrow(), now, &silence()))
+}
+
+/// `alarm`, `alarm snooze` or `alarm dismiss` from the console; the reply
+/// says what's ringing or next.
+pub fn command(argument: Option<&str>, now: &Zoned) -> Result<String> {
+    let alarms = get();
+    let mut silence = silence();
+    let rang = ringing(&alarms, now, &silence);
+    match (argument, rang) {
+        (None, _) => {}
+        (Some("snooze"), Some(_)) => silence.snoozed = Some(now.timestamp()),
+        (Some("dismiss"), Some(_)) => silence.dismissed = Some(now.timestamp()),
+        (Some("snooze" | "dismiss"), None) => return Err(anyhow!("no alarm is ringing")),
+        (Some(other), _) => return Err(anyhow!("unknown alarm command {}", other)),
+    }
+    set_silence(silence);
+    if let Some(since) = ringing(&alarms, now, &silence) {
+        return Ok(format!(
+            "alarm: ringing since {}",
+            since.to_zoned(now.time_zone().clone()).strftime("%H:%M")
+        ));
+    }
+    Ok(
+        match alarms.iter().filter_map(|alarm| alarm.next(now)).min() {
+            Some(next) => format!("alarm: next {}", next.strftime("%a %H:%M")),
+            None => "alarm: none set".into(),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::tz::TimeZone;
+
+    fn pacific() -> TimeZone {
+        TimeZone::posix("PST8PDT,M3.2.0,M11.1.0").unwrap()
+    }
+
+    // `utc` seen from Pacific time
+    fn at(utc: &str) -> Zoned {
+        utc.parse::<Timestamp>().unwrap().to_zoned(pacific())
+    }
+
+    fn ts(utc: &str) -> Timestamp {
+        utc.parse().unwrap()
+    }
+
+    #[test]
+    fn weekday_masks() {
+        assert_eq!(Weekdays::parse("mon-fri").unwrap(), Weekdays::WEEKDAYS);
+        assert_eq!(Weekdays::parse("sat,sun").unwrap(), Weekdays::WEEKENDS);
+        assert_eq!(
+            Weekdays::parse("Monday, wed,FRI").unwrap(),
+            Weekdays(0b10101)
+        );
+        assert_eq!(Weekdays::parse("fri-mon").unwrap(), Weekdays(0b111_0001));
+        assert_eq!(Weekdays::parse("mon-sun").unwrap(), Weekdays::DAILY);
+        assert_eq!(
+            Weekdays::parse("weekdays,sun").unwrap(),
+            Weekdays(0b101_1111)
+        );
+        assert!(Weekdays::parse("mo").is_err());
+        assert!(Weekdays::parse("someday").is_err());
+    }
+
+    #[test]
+    fn parses_the_schedule() {
+        let alarms = parse("07:00 mon-fri; 09:30 sat,sun;06:15").unwrap();
+        assert_eq!(alarms.len(), 3);
+        assert_eq!(alarms[0].time, Time::constant(7, 0, 0, 0));
+        assert_eq!(alarms[2].days, Weekdays::DAILY);
+        assert!(parse("7am").is_err());
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn weekday_alarm_skips_the_weekend() {
+        let alarm = Alarm::parse("07:00 weekdays").unwrap();
+        // Saturday 2026-03-21 10:00 PDT
+        let saturday = at("2026-03-21T17:00:00Z");
+        assert_eq!(
+            alarm.latest(&saturday).unwrap().timestamp(),
+            ts("2026-03-20T14:00:00Z")
+        );
+        assert_eq!(
+            alarm.next(&saturday).unwrap().timestamp(),
+            ts("2026-03-23T14:00:00Z")
+        );
+    }
+
+    #[test]
+    fn wall_clock_time_across_dst() {
+        let alarm = Alarm::parse("07:00").unwrap();
+        // 07:00 PST the day before the change, 07:00 PDT the day after
+        assert_eq!(
+            alarm
+                .latest(&at("2026-03-07T16:00:00Z"))
+                .unwrap()
+                .timestamp(),
+            ts("2026-03-07T15:00:00Z")
+        );
+        assert_eq!(
+            alarm
+                .latest(&at("2026-03-09T15:00:00Z"))
+                .unwrap()
+                .timestamp(),
+            ts("2026-03-09T14:00:00Z")
+        );
+    }
+
+    #[test]
+    fn skipped_time_rings_after_the_gap() {
+        // 02:30 doesn't exist on 2026-03-08; it rings at 03:30 PDT
+        let alarm = Alarm::parse("02:30").unwrap();
+        let fire = alarm.latest(&at("2026-03-08T11:00:00Z")).unwrap();
+        assert_eq!(fire.timestamp(), ts("2026-03-08T10:30:00Z"));
+    }
+
+    #[test]
+    fn repeated_time_rings_once() {
+        // 01:30 happens twice on 2026-11-01, at 08:30Z (PDT) and 09:30Z (PST)
+        let alarms = parse("01:30").unwrap();
+        let first = at("2026-11-01T08:30:00Z");
+        assert_eq!(
+            ringing(&alarms, &first, &Silence::new()),
+            Some(ts("2026-11-01T08:30:00Z"))
+        );
+        let second = at("2026-11-01T09:35:00Z");
+        assert_eq!(ringing(&alarms, &second, &Silence::new()), None);
+    }
+
+    #[test]
+    fn rings_until_dismissed_or_given_up() {
+        let alarms = parse("07:00").unwrap();
+        let fired = ts("2026-03-18T14:00:00Z");
+        let silence = Silence::new();
+        assert_eq!(
+            ringing(&alarms, &at("2026-03-18T13:59:59Z"), &silence),
+            None
+        );
+        assert_eq!(
+            ringing(&alarms, &at("2026-03-18T14:00:00Z"), &silence),
+            Some(fired)
+        );
+        assert_eq!(
+            ringing(&alarms, &at("2026-03-18T14:29:59Z"), &silence),
+            Some(fired)
+        );
+        assert_eq!(
+            ringing(&alarms, &at("2026-03-18T14:30:00Z"), &silence),
+            None
+        );
+        let dismissed = Silence {
+            dismissed: Some(ts("2026-03-18T14:02:00Z")),
+            snoozed: None,
+        };
+        assert_eq!(
+            ringing(&alarms, &at("2026-03-18T14:03:00Z"), &dismissed),
+            None
+        );
+        // and tomorrow's isn't dismissed
+        assert_eq!(
+            ringing(&alarms, &at("2026-03-19T14:00:00Z"), &dismissed),
+            Some(ts("2026-03-19T14:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn snooze_rings_again() {
+        let alarms = parse("07:00").unwrap();
+        let silence = Silence {
+            dismissed: None,
+            snoozed: Some(ts("2026-03-18T14:01:00Z")),
+        };
+        assert_eq!(
+            ringing(&alarms, &at("2026-03-18T14:05:00Z"), &silence),
+            None
+        );
+        assert_eq!(
+            ringing(&alarms, &at("2026-03-18T14:10:00Z"), &silence),
+            Some(ts("2026-03-18T14:10:00Z"))
+        );
+        // yesterday's snooze doesn't hold today's alarm
+        let stale = Silence {
+            dismissed: None,
+            snoozed: Some(ts("2026-03-17T14:01:00Z")),
+        };
+        assert_eq!(
+            ringing(&alarms, &at("2026-03-18T14:05:00Z"), &stale),
+            Some(ts("2026-03-18T14:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn silence_round_trips() {
+        let silence = Silence {
+            dismissed: Some(ts("2026-03-18T14:02:00Z")),
+            snoozed: None,
+        };
+        assert_eq!(Silence::from_words(&silence.to_words()), Some(silence));
+        let mut torn = silence.to_words();
+        torn[1] += 1;
+        assert_eq!(Silence::from_words(&torn), None);
+        assert_eq!(Silence::from_words(&[0; WORDS]), None);
+    }
+
+    #[test]
+    fn flashes() {
+        let since = ts("2026-03-18T14:00:00Z");
+        assert!(flash_on(since, since));
+        assert!(!flash_on(since, ts("2026-03-18T14:00:00.6Z")));
+        assert!(flash_on(since, ts("2026-03-18T14:00:01.2Z")));
+    }
+}
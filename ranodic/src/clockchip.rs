@@ -0,0 +1,590 @@
+//! The I2C RTC chips we can keep time with, behind one trait, and a probe of
+//! the bus to find out which one is fitted. Chips keep UTC in 24-hour mode,
+//! 2000 to 2099.
+
+use alloc::boxed::Box;
+use anyhow::{Result, anyhow};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use ds323x::{DateTimeAccess, Ds323x, ic::DS3231, interface::I2cInterface};
+use embedded_hal::i2c::I2c;
+
+pub trait ClockChip {
+    fn model(&self) -> Model;
+    fn datetime(&mut self) -> Result<NaiveDateTime>;
+    fn set_datetime(&mut self, datetime: &NaiveDateTime) -> Result<()>;
+    /// Whether the chip's time is not to be trusted: it stopped, or lost
+    /// power, since it was last set.
+    fn lost_time(&mut self) -> Result<bool>;
+    /// Make sure the oscillator runs, and keeps running on battery.
+    fn start(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Die temperature in °C, for chips that measure it.
+    fn temperature(&mut self) -> Result<Option<f32>> {
+        Ok(None)
+    }
+    /// The oscillator trim, for chips that have one; see [`crate::aging`].
+    fn aging_offset(&mut self) -> Result<Option<i8>> {
+        Ok(None)
+    }
+    fn set_aging_offset(&mut self, _offset: i8) -> Result<()> {
+        Err(anyhow!("{} has no aging offset", self.model().name()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Model {
+    Ds3231,
+    Ds1307,
+    Pcf8563,
+    Rv3028,
+}
+
+impl Model {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::Ds3231 => "DS3231",
+            Model::Ds1307 => "DS1307",
+            Model::Pcf8563 => "PCF8563",
+            Model::Rv3028 => "RV-3028",
+        }
+    }
+
+    pub fn address(&self) -> u8 {
+        match self {
+            Model::Ds3231 | Model::Ds1307 => DS_ADDRESS,
+            Model::Pcf8563 => PCF8563_ADDRESS,
+            Model::Rv3028 => RV3028_ADDRESS,
+        }
+    }
+
+    pub fn driver<I2C: I2c + 'static>(self, i2c: I2C) -> Box<dyn ClockChip> {
+        match self {
+            Model::Ds3231 => Box::new(Ds323x::new_ds3231(i2c)),
+            Model::Ds1307 => Box::new(Ds1307 { i2c }),
+            Model::Pcf8563 => Box::new(Pcf8563 { i2c }),
+            Model::Rv3028 => Box::new(Rv3028 { i2c }),
+        }
+    }
+}
+
+const DS_ADDRESS: u8 = 0x68;
+const PCF8563_ADDRESS: u8 = 0x51;
+const RV3028_ADDRESS: u8 = 0x52;
+
+/// Find the chip on the bus, if any. Modules often carry an EEPROM somewhere
+/// in 0x50..0x57 as well, so the chips there must also look the part.
+pub fn detect<I2C: I2c>(i2c: &mut I2C) -> Option<Model> {
+    if read(i2c, DS_ADDRESS, 0x00).is_ok() {
+        return Some(if is_ds3231(i2c) {
+            Model::Ds3231
+        } else {
+            Model::Ds1307
+        });
+    }
+    // control/status 1 has five bits that always read zero
+    if read(i2c, PCF8563_ADDRESS, 0x00).is_ok_and(|control| control & 0b0101_0111 == 0) {
+        return Some(Model::Pcf8563);
+    }
+    if read(i2c, RV3028_ADDRESS, RV3028_ID).is_ok_and(|id| id >> 4 == RV3028_HID) {
+        return Some(Model::Rv3028);
+    }
+    None
+}
+
+// The DS3231 and DS1307 share an address. The DS3231's register pointer wraps
+// to the seconds after the temperature at 0x12, whose low six bits are always
+// clear; the DS1307 carries on through its RAM.
+fn is_ds3231<I2C: I2c>(i2c: &mut I2C) -> bool {
+    // the seconds may tick over between the two reads
+    (0..2).any(|_| {
+        let mut wrapped = [0; 2];
+        i2c.write_read(DS_ADDRESS, &[0x12], &mut wrapped).is_ok()
+            && read(i2c, DS_ADDRESS, 0x00)
+                .is_ok_and(|seconds| wrapped[0] & 0b0011_1111 == 0 && wrapped[1] == seconds)
+    })
+}
+
+fn read<I2C: I2c>(i2c: &mut I2C, address: u8, register: u8) -> Result<u8> {
+    let mut data = [0];
+    i2c.write_read(address, &[register], &mut data)
+        .map_err(i2c_error)?;
+    Ok(data[0])
+}
+
+fn i2c_error<E: embedded_hal::i2c::Error>(e: E) -> anyhow::Error {
+    anyhow!("I2C {:?}", e.kind())
+}
+
+fn bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
+
+fn to_bcd(value: u32) -> u8 {
+    (((value / 10) << 4) | (value % 10)) as u8
+}
+
+// seconds, minutes, hours, day, month, year, already masked
+fn from_registers(registers: [u8; 6]) -> Result<NaiveDateTime> {
+    let [second, minute, hour, day, month, year] = registers.map(bcd);
+    NaiveDate::from_ymd_opt(2000 + year as i32, month as u32, day as u32)
+        .and_then(|date| date.and_hms_opt(hour as u32, minute as u32, second as u32))
+        .ok_or(anyhow!("chip holds no valid time"))
+}
+
+fn check_year(datetime: &NaiveDateTime) -> Result<u8> {
+    match datetime.year() {
+        year @ 2000..=2099 => Ok(to_bcd((year - 2000) as u32)),
+        year => Err(anyhow!("{} is out of range", year)),
+    }
+}
+
+fn ds323x_error<E: embedded_hal::i2c::Error>(e: ds323x::Error<E>) -> anyhow::Error {
+    match e {
+        ds323x::Error::Comm(e) => i2c_error(e),
+        ds323x::Error::InvalidInputData => anyhow!("InvalidInputData"),
+        ds323x::Error::InvalidDeviceState => anyhow!("InvalidDeviceState"),
+    }
+}
+
+impl<I2C: I2c> ClockChip for Ds323x<I2cInterface<I2C>, DS3231> {
+    fn model(&self) -> Model {
+        Model::Ds3231
+    }
+
+    fn datetime(&mut self) -> Result<NaiveDateTime> {
+        DateTimeAccess::datetime(self).map_err(ds323x_error)
+    }
+
+    fn set_datetime(&mut self, datetime: &NaiveDateTime) -> Result<()> {
+        DateTimeAccess::set_datetime(self, datetime).map_err(ds323x_error)?;
+        self.clear_has_been_stopped_flag().map_err(ds323x_error)
+    }
+
+    fn lost_time(&mut self) -> Result<bool> {
+        self.has_been_stopped().map_err(ds323x_error)
+    }
+
+    fn start(&mut self) -> Result<()> {
+        self.enable().map_err(ds323x_error)
+    }
+
+    fn temperature(&mut self) -> Result<Option<f32>> {
+        Ds323x::temperature(self).map(Some).map_err(ds323x_error)
+    }
+
+    fn aging_offset(&mut self) -> Result<Option<i8>> {
+        Ds323x::aging_offset(self).map(Some).map_err(ds323x_error)
+    }
+
+    /// Takes effect at once rather than at the next 64 s conversion.
+    fn set_aging_offset(&mut self, offset: i8) -> Result<()> {
+        Ds323x::set_aging_offset(self, offset).map_err(ds323x_error)?;
+        self.convert_temperature().map_err(ds323x_error)
+    }
+}
+
+/// Maxim DS1307: no compensation, and a clock-halt bit in the seconds.
+pub struct Ds1307<I2C> {
+    i2c: I2C,
+}
+
+const DS1307_CLOCK_HALT: u8 = 0x80;
+
+impl<I2C: I2c> ClockChip for Ds1307<I2C> {
+    fn model(&self) -> Model {
+        Model::Ds1307
+    }
+
+    fn datetime(&mut self) -> Result<NaiveDateTime> {
+        let mut data = [0; 7];
+        self.i2c
+            .write_read(DS_ADDRESS, &[0x00], &mut data)
+            .map_err(i2c_error)?;
+        from_registers([
+            data[0] & 0x7f,
+            data[1] & 0x7f,
+            data[2] & 0x3f,
+            data[4] & 0x3f,
+            data[5] & 0x1f,
+            data[6],
+        ])
+    }
+
+    fn set_datetime(&mut self, datetime: &NaiveDateTime) -> Result<()> {
+        let year = check_year(datetime)?;
+        // clock halt clear, 24-hour mode
+        self.i2c
+            .write(
+                DS_ADDRESS,
+                &[
+                    0x00,
+                    to_bcd(datetime.second()),
+                    to_bcd(datetime.minute()),
+                    to_bcd(datetime.hour()),
+                    datetime.weekday().number_from_sunday() as u8,
+                    to_bcd(datetime.day()),
+                    to_bcd(datetime.month()),
+                    year,
+                ],
+            )
+            .map_err(i2c_error)
+    }
+
+    fn lost_time(&mut self) -> Result<bool> {
+        Ok(read(&mut self.i2c, DS_ADDRESS, 0x00)? & DS1307_CLOCK_HALT != 0)
+    }
+
+    fn start(&mut self) -> Result<()> {
+        let seconds = read(&mut self.i2c, DS_ADDRESS, 0x00)?;
+        if seconds & DS1307_CLOCK_HALT == 0 {
+            return Ok(());
+        }
+        self.i2c
+            .write(DS_ADDRESS, &[0x00, seconds & !DS1307_CLOCK_HALT])
+            .map_err(i2c_error)
+    }
+}
+
+/// NXP PCF8563: time from register 0x02, with a voltage-low flag in the
+/// seconds. The century bit's meaning varies between boards, so it's ignored.
+pub struct Pcf8563<I2C> {
+    i2c: I2C,
+}
+
+const PCF8563_VOLTAGE_LOW: u8 = 0x80;
+const PCF8563_STOP: u8 = 0x20;
+
+impl<I2C: I2c> ClockChip for Pcf8563<I2C> {
+    fn model(&self) -> Model {
+        Model::Pcf8563
+    }
+
+    fn datetime(&mut self) -> Result<NaiveDateTime> {
+        let mut data = [0; 7];
+        self.i2c
+            .write_read(PCF8563_ADDRESS, &[0x02], &mut data)
+            .map_err(i2c_error)?;
+        from_registers([
+            data[0] & 0x7f,
+            data[1] & 0x7f,
+            data[2] & 0x3f,
+            data[3] & 0x3f,
+            data[5] & 0x1f,
+            data[6],
+        ])
+    }
+
+    fn set_datetime(&mut self, datetime: &NaiveDateTime) -> Result<()> {
+        let year = check_year(datetime)?;
+        // writing the seconds clears the voltage-low flag
+        self.i2c
+            .write(
+                PCF8563_ADDRESS,
+                &[
+                    0x02,
+                    to_bcd(datetime.second()),
+                    to_bcd(datetime.minute()),
+                    to_bcd(datetime.hour()),
+                    to_bcd(datetime.day()),
+                    datetime.weekday().num_days_from_sunday() as u8,
+                    to_bcd(datetime.month()),
+                    year,
+                ],
+            )
+            .map_err(i2c_error)
+    }
+
+    fn lost_time(&mut self) -> Result<bool> {
+        Ok(read(&mut self.i2c, PCF8563_ADDRESS, 0x02)? & PCF8563_VOLTAGE_LOW != 0)
+    }
+
+    fn start(&mut self) -> Result<()> {
+        let control = read(&mut self.i2c, PCF8563_ADDRESS, 0x00)?;
+        if control & PCF8563_STOP == 0 {
+            return Ok(());
+        }
+        self.i2c
+            .write(PCF8563_ADDRESS, &[0x00, control & !PCF8563_STOP])
+            .map_err(i2c_error)
+    }
+}
+
+/// Micro Crystal RV-3028-C7: factory-trimmed to ±1 ppm, but shipped with the
+/// battery switchover off.
+pub struct Rv3028<I2C> {
+    i2c: I2C,
+}
+
+const RV3028_STATUS: u8 = 0x0e;
+const RV3028_CONTROL_1: u8 = 0x0f;
+const RV3028_ID: u8 = 0x28;
+const RV3028_EEPROM_BACKUP: u8 = 0x37;
+const RV3028_HID: u8 = 0x3;
+const RV3028_POWER_ON_RESET: u8 = 0x01;
+const RV3028_EEPROM_REFRESH_OFF: u8 = 0x08;
+const RV3028_BACKUP_SWITCHOVER: u8 = 0x0c;
+const RV3028_DIRECT_SWITCHING: u8 = 0x04;
+
+impl<I2C: I2c> ClockChip for Rv3028<I2C> {
+    fn model(&self) -> Model {
+        Model::Rv3028
+    }
+
+    fn datetime(&mut self) -> Result<NaiveDateTime> {
+        let mut data = [0; 7];
+        self.i2c
+            .write_read(RV3028_ADDRESS, &[0x00], &mut data)
+            .map_err(i2c_error)?;
+        from_registers([
+            data[0] & 0x7f,
+            data[1] & 0x7f,
+            data[2] & 0x3f,
+            data[4] & 0x3f,
+            data[5] & 0x1f,
+            data[6],
+        ])
+    }
+
+    fn set_datetime(&mut self, datetime: &NaiveDateTime) -> Result<()> {
+        let year = check_year(datetime)?;
+        self.i2c
+            .write(
+                RV3028_ADDRESS,
+                &[
+                    0x00,
+                    to_bcd(datetime.second()),
+                    to_bcd(datetime.minute()),
+                    to_bcd(datetime.hour()),
+                    datetime.weekday().num_days_from_sunday() as u8,
+                    to_bcd(datetime.day()),
+                    to_bcd(datetime.month()),
+                    year,
+                ],
+            )
+            .map_err(i2c_error)?;
+        let status = read(&mut self.i2c, RV3028_ADDRESS, RV3028_STATUS)?;
+        self.i2c
+            .write(
+                RV3028_ADDRESS,
+                &[RV3028_STATUS, status & !RV3028_POWER_ON_RESET],
+            )
+            .map_err(i2c_error)
+    }
+
+    fn lost_time(&mut self) -> Result<bool> {
+        Ok(read(&mut self.i2c, RV3028_ADDRESS, RV3028_STATUS)? & RV3028_POWER_ON_RESET != 0)
+    }
+
+    // Switch to the battery directly when the supply drops. Only the RAM
+    // mirror of the EEPROM is changed, to spare the EEPROM, so its daily
+    // refresh has to be turned off or it would undo this.
+    fn start(&mut self) -> Result<()> {
+        let backup = read(&mut self.i2c, RV3028_ADDRESS, RV3028_EEPROM_BACKUP)?;
+        if backup & RV3028_BACKUP_SWITCHOVER != 0 {
+            return Ok(());
+        }
+        let control = read(&mut self.i2c, RV3028_ADDRESS, RV3028_CONTROL_1)?;
+        self.i2c
+            .write(
+                RV3028_ADDRESS,
+                &[RV3028_CONTROL_1, control | RV3028_EEPROM_REFRESH_OFF],
+            )
+            .map_err(i2c_error)?;
+        self.i2c
+            .write(
+                RV3028_ADDRESS,
+                &[RV3028_EEPROM_BACKUP, backup | RV3028_DIRECT_SWITCHING],
+            )
+            .map_err(i2c_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    const NACK: ErrorKind = ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address);
+
+    // Sunday 2025-03-09 10:02:03
+    fn when() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 3, 9)
+            .unwrap()
+            .and_hms_opt(10, 2, 3)
+            .unwrap()
+    }
+
+    fn absent(address: u8, register: u8) -> Transaction {
+        Transaction::write_read(address, vec![register], vec![0]).with_error(NACK)
+    }
+
+    #[test]
+    fn detects_ds3231_by_register_wrap() {
+        let mut i2c = Mock::new(&[
+            Transaction::write_read(DS_ADDRESS, vec![0x00], vec![0x03]),
+            // temperature LSB, then seconds
+            Transaction::write_read(DS_ADDRESS, vec![0x12], vec![0x40, 0x03]),
+            Transaction::write_read(DS_ADDRESS, vec![0x00], vec![0x03]),
+        ]);
+        assert_eq!(detect(&mut i2c), Some(Model::Ds3231));
+        i2c.done();
+    }
+
+    #[test]
+    fn detects_ds1307_by_its_ram() {
+        let mut i2c = Mock::new(&[
+            Transaction::write_read(DS_ADDRESS, vec![0x00], vec![0x03]),
+            Transaction::write_read(DS_ADDRESS, vec![0x12], vec![0xa5, 0x5a]),
+            Transaction::write_read(DS_ADDRESS, vec![0x00], vec![0x03]),
+            Transaction::write_read(DS_ADDRESS, vec![0x12], vec![0xa5, 0x5a]),
+            Transaction::write_read(DS_ADDRESS, vec![0x00], vec![0x04]),
+        ]);
+        assert_eq!(detect(&mut i2c), Some(Model::Ds1307));
+        i2c.done();
+    }
+
+    #[test]
+    fn detects_pcf8563_and_rv3028_but_not_eeproms() {
+        let mut i2c = Mock::new(&[
+            absent(DS_ADDRESS, 0x00),
+            Transaction::write_read(PCF8563_ADDRESS, vec![0x00], vec![0x08]),
+        ]);
+        assert_eq!(detect(&mut i2c), Some(Model::Pcf8563));
+        i2c.done();
+
+        let mut i2c = Mock::new(&[
+            absent(DS_ADDRESS, 0x00),
+            absent(PCF8563_ADDRESS, 0x00),
+            Transaction::write_read(RV3028_ADDRESS, vec![RV3028_ID], vec![0x30]),
+        ]);
+        assert_eq!(detect(&mut i2c), Some(Model::Rv3028));
+        i2c.done();
+
+        // 24C02s answer anything
+        let mut i2c = Mock::new(&[
+            absent(DS_ADDRESS, 0x00),
+            Transaction::write_read(PCF8563_ADDRESS, vec![0x00], vec![0xff]),
+            Transaction::write_read(RV3028_ADDRESS, vec![RV3028_ID], vec![0xff]),
+        ]);
+        assert_eq!(detect(&mut i2c), None);
+        i2c.done();
+    }
+
+    #[test]
+    fn ds3231_reads_sets_and_trims() {
+        let mut i2c = Mock::new(&[
+            Transaction::write_read(
+                DS_ADDRESS,
+                vec![0x00],
+                vec![0x03, 0x02, 0x10, 0x01, 0x09, 0x03, 0x25],
+            ),
+            Transaction::write(
+                DS_ADDRESS,
+                vec![0x00, 0x03, 0x02, 0x10, 0x01, 0x09, 0x03, 0x25],
+            ),
+            // clear the oscillator-stop flag, leaving the alarm flags alone
+            Transaction::write(DS_ADDRESS, vec![0x0f, 0x0b]),
+            Transaction::write(DS_ADDRESS, vec![0x10, 0xf6]),
+            Transaction::write_read(DS_ADDRESS, vec![0x0e], vec![0x1c]),
+            Transaction::write(DS_ADDRESS, vec![0x0e, 0x3c]),
+        ]);
+        let mut chip = Model::Ds3231.driver(i2c.clone());
+        assert_eq!(chip.datetime().unwrap(), when());
+        chip.set_datetime(&when()).unwrap();
+        chip.set_aging_offset(-10).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn ds1307_reads_sets_and_restarts() {
+        let mut i2c = Mock::new(&[
+            Transaction::write_read(
+                DS_ADDRESS,
+                vec![0x00],
+                vec![0x03, 0x02, 0x10, 0x01, 0x09, 0x03, 0x25],
+            ),
+            Transaction::write(
+                DS_ADDRESS,
+                vec![0x00, 0x03, 0x02, 0x10, 0x01, 0x09, 0x03, 0x25],
+            ),
+            Transaction::write_read(DS_ADDRESS, vec![0x00], vec![0x83]),
+            Transaction::write_read(DS_ADDRESS, vec![0x00], vec![0x83]),
+            Transaction::write(DS_ADDRESS, vec![0x00, 0x03]),
+        ]);
+        let mut chip = Model::Ds1307.driver(i2c.clone());
+        assert_eq!(chip.datetime().unwrap(), when());
+        chip.set_datetime(&when()).unwrap();
+        assert!(chip.lost_time().unwrap());
+        chip.start().unwrap();
+        assert!(chip.set_aging_offset(1).is_err());
+        assert_eq!(chip.aging_offset().unwrap(), None);
+        i2c.done();
+    }
+
+    #[test]
+    fn pcf8563_reads_sets_and_flags_low_voltage() {
+        let mut i2c = Mock::new(&[
+            // voltage low, century bit set
+            Transaction::write_read(
+                PCF8563_ADDRESS,
+                vec![0x02],
+                vec![0x83, 0x02, 0x10, 0x09, 0x00, 0x83, 0x25],
+            ),
+            Transaction::write(
+                PCF8563_ADDRESS,
+                vec![0x02, 0x03, 0x02, 0x10, 0x09, 0x00, 0x03, 0x25],
+            ),
+            Transaction::write_read(PCF8563_ADDRESS, vec![0x02], vec![0x83]),
+            Transaction::write_read(PCF8563_ADDRESS, vec![0x00], vec![0x20]),
+            Transaction::write(PCF8563_ADDRESS, vec![0x00, 0x00]),
+        ]);
+        let mut chip = Model::Pcf8563.driver(i2c.clone());
+        assert_eq!(chip.datetime().unwrap(), when());
+        chip.set_datetime(&when()).unwrap();
+        assert!(chip.lost_time().unwrap());
+        chip.start().unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn rv3028_reads_sets_and_enables_switchover() {
+        let mut i2c = Mock::new(&[
+            Transaction::write_read(
+                RV3028_ADDRESS,
+                vec![0x00],
+                vec![0x03, 0x02, 0x10, 0x00, 0x09, 0x03, 0x25],
+            ),
+            Transaction::write(
+                RV3028_ADDRESS,
+                vec![0x00, 0x03, 0x02, 0x10, 0x00, 0x09, 0x03, 0x25],
+            ),
+            Transaction::write_read(RV3028_ADDRESS, vec![RV3028_STATUS], vec![0x01]),
+            Transaction::write(RV3028_ADDRESS, vec![RV3028_STATUS, 0x00]),
+            Transaction::write_read(RV3028_ADDRESS, vec![RV3028_EEPROM_BACKUP], vec![0x10]),
+            Transaction::write_read(RV3028_ADDRESS, vec![RV3028_CONTROL_1], vec![0x00]),
+            Transaction::write(RV3028_ADDRESS, vec![RV3028_CONTROL_1, 0x08]),
+            Transaction::write(RV3028_ADDRESS, vec![RV3028_EEPROM_BACKUP, 0x14]),
+        ]);
+        let mut chip = Model::Rv3028.driver(i2c.clone());
+        assert_eq!(chip.datetime().unwrap(), when());
+        chip.set_datetime(&when()).unwrap();
+        chip.start().unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn refuses_years_the_chips_cant_hold() {
+        let mut i2c = Mock::new(&[]);
+        let mut chip = Model::Pcf8563.driver(i2c.clone());
+        let y2k = NaiveDate::from_ymd_opt(1999, 12, 31)
+            .unwrap()
+            .and_hms_opt(23, 59, 59)
+            .unwrap();
+        assert!(chip.set_datetime(&y2k).is_err());
+        i2c.done();
+    }
+}
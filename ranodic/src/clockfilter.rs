@@ -0,0 +1,375 @@
+//! NTP clock filter, selection and discipline after RFC 5905, cut down to what
+//! a panel that polls every couple of minutes needs. All times are µs.
+
+/// RFC 5905 `STEPT`: offsets bigger than this are stepped, smaller ones slewed.
+pub const STEP_THRESHOLD: i64 = 128_000;
+/// RFC 5905 `MAXFREQ`: the most a slew may speed up or slow down the clock.
+pub const SLEW_MAX_PPM: i64 = 500;
+/// The RTC slow clock is far worse than a crystal, so allow more than RFC 5905.
+pub const DRIFT_MAX_PPM: f32 = 1000.0;
+/// RFC 5905 `MINDISP`, added to every sample's distance.
+pub const MIN_DISPERSION: u64 = 10_000;
+// fraction of each measured frequency error folded into the drift estimate
+const DRIFT_GAIN: f32 = 0.25;
+// intervals shorter than this say more about network jitter than about drift
+const DRIFT_MIN_INTERVAL: u64 = 60_000_000;
+
+/// One client/server exchange.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sample {
+    /// Server clock minus ours.
+    pub offset: i64,
+    /// Round-trip delay.
+    pub delay: u64,
+    pub stratum: u8,
+    /// Server precision as log2 seconds.
+    pub precision: i8,
+    /// The server's time at our boot, Unix µs: its clock when the reply
+    /// arrived less our uptime then. Sets a clock that was never set, where
+    /// `offset` would be decades.
+    pub boot_time: i64,
+    /// The server as a reference ID; see [`crate::sntp::reference_id`].
+    pub reference_id: [u8; 4],
+}
+
+impl Sample {
+    /// Root distance λ: half the round trip plus precision and dispersion. The
+    /// true offset is somewhere in `offset ± distance`.
+    pub fn distance(&self) -> u64 {
+        let precision = if self.precision >= 0 {
+            1_000_000u64 << self.precision.min(20)
+        } else {
+            1_000_000u64 >> (-(self.precision as i32)).min(63)
+        };
+        self.delay / 2 + precision + MIN_DISPERSION
+    }
+
+    fn low(&self) -> i64 {
+        self.offset.saturating_sub(self.distance() as i64)
+    }
+
+    fn high(&self) -> i64 {
+        self.offset.saturating_add(self.distance() as i64)
+    }
+}
+
+/// The clock filter: of several exchanges with one server, the one with the
+/// least delay is the least disturbed by queueing.
+pub fn filter(samples: &[Sample]) -> Option<Sample> {
+    samples.iter().copied().min_by_key(|sample| sample.delay)
+}
+
+/// What the truechimers agree on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Selection {
+    /// Distance-weighted mean offset of the survivors.
+    pub offset: i64,
+    /// Smallest survivor distance; a bound on how wrong `offset` is.
+    pub distance: u64,
+    /// Stratum of the closest survivor.
+    pub stratum: u8,
+    /// [`Sample::boot_time`] of the closest survivor.
+    pub boot_time: i64,
+    /// [`Sample::reference_id`] of the closest survivor.
+    pub reference_id: [u8; 4],
+    pub survivors: usize,
+    pub falsetickers: usize,
+}
+
+/// Selection and combine (RFC 5905 §11.2): find the smallest number of
+/// falsetickers `f` for which `n - f` correctness intervals share a point,
+/// drop the servers whose offset lies outside that intersection and average
+/// the rest. `None` when no majority agrees, in which case the clock is left
+/// alone.
+pub fn select(samples: &[Sample]) -> Option<Selection> {
+    let n = samples.len();
+    if n == 0 {
+        return None;
+    }
+    // (edge, type): -1 low edge, 0 midpoint, +1 high edge
+    let mut chime: alloc::vec::Vec<(i64, i8)> = samples
+        .iter()
+        .flat_map(|s| [(s.low(), -1), (s.offset, 0), (s.high(), 1)])
+        .collect();
+    chime.sort_unstable();
+
+    let mut f = 0;
+    let (low, high) = loop {
+        if 2 * f >= n {
+            return None;
+        }
+        let mut midpoints = 0;
+        let mut count = 0;
+        let mut low = None;
+        for &(edge, kind) in chime.iter() {
+            count -= kind as i64;
+            if count >= (n - f) as i64 {
+                low = Some(edge);
+                break;
+            }
+            if kind == 0 {
+                midpoints += 1;
+            }
+        }
+        count = 0;
+        let mut high = None;
+        for &(edge, kind) in chime.iter().rev() {
+            count += kind as i64;
+            if count >= (n - f) as i64 {
+                high = Some(edge);
+                break;
+            }
+            if kind == 0 {
+                midpoints += 1;
+            }
+        }
+        if let (Some(low), Some(high)) = (low, high)
+            && midpoints <= f
+            && low <= high
+        {
+            break (low, high);
+        }
+        f += 1;
+    };
+
+    let survivors = samples
+        .iter()
+        .filter(|s| (low..=high).contains(&s.offset))
+        .collect::<alloc::vec::Vec<_>>();
+    let closest = survivors.iter().min_by_key(|s| s.distance())?;
+    let (weighted, weights) = survivors.iter().fold((0.0, 0.0), |(sum, weights), s| {
+        let weight = 1.0 / s.distance() as f64;
+        (sum + s.offset as f64 * weight, weights + weight)
+    });
+    Some(Selection {
+        offset: (weighted / weights) as i64,
+        distance: closest.distance(),
+        stratum: closest.stratum,
+        boot_time: closest.boot_time,
+        reference_id: closest.reference_id,
+        survivors: survivors.len(),
+        falsetickers: n - survivors.len(),
+    })
+}
+
+/// What to do with a selected offset.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Correction {
+    /// Set the clock outright.
+    Step(i64),
+    /// Spread over the coming ticks by [`Discipline::tick`].
+    Slew(i64),
+}
+
+/// Frequency and phase state carried between polls.
+#[derive(Clone, Debug, Default)]
+pub struct Discipline {
+    drift_ppm: f32,
+    pending: i64,
+    carry: f32,
+    last_update: Option<u64>,
+}
+
+impl Discipline {
+    /// Feed a selected offset measured at monotonic time `now`. Whatever is
+    /// left after the slew still pending and the drift already compensated
+    /// is frequency error, which nudges the drift estimate; an offset big
+    /// enough to step says the clock was wrong, not fast or slow, and
+    /// doesn't.
+    pub fn update(&mut self, offset: i64, now: u64) -> Correction {
+        let last = self.last_update.replace(now);
+        if offset.abs() > STEP_THRESHOLD {
+            self.pending = 0;
+            self.carry = 0.0;
+            return Correction::Step(offset);
+        }
+        if let Some(last) = last {
+            let interval = now.saturating_sub(last);
+            if interval >= DRIFT_MIN_INTERVAL {
+                let residual = offset - self.pending;
+                let error_ppm = residual as f32 * 1e6 / interval as f32;
+                self.drift_ppm =
+                    (self.drift_ppm + error_ppm * DRIFT_GAIN).clamp(-DRIFT_MAX_PPM, DRIFT_MAX_PPM);
+            }
+        }
+        self.pending = offset;
+        Correction::Slew(offset)
+    }
+
+    /// The adjustment to add to the clock after `elapsed` µs: as much of the
+    /// pending slew as [`SLEW_MAX_PPM`] allows, plus drift compensation.
+    pub fn tick(&mut self, elapsed: u64) -> i64 {
+        let budget = (elapsed as i64).saturating_mul(SLEW_MAX_PPM) / 1_000_000;
+        let slew = self.pending.clamp(-budget, budget);
+        self.pending -= slew;
+        self.carry += self.drift_ppm * elapsed as f32 / 1e6;
+        let drift = self.carry as i64;
+        self.carry -= drift as f32;
+        slew + drift
+    }
+
+    /// Something else set the clock; the next offset says nothing about drift.
+    pub fn forget(&mut self) {
+        self.pending = 0;
+        self.carry = 0.0;
+        self.last_update = None;
+    }
+
+    /// Estimated frequency error of our clock; positive means it runs slow.
+    pub fn drift_ppm(&self) -> f32 {
+        self.drift_ppm
+    }
+
+    /// Slew not yet applied.
+    pub fn pending(&self) -> i64 {
+        self.pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(offset: i64, delay: u64) -> Sample {
+        Sample {
+            offset,
+            delay,
+            stratum: 2,
+            precision: -20,
+            boot_time: 1_700_000_000_000_000 + offset,
+            reference_id: (offset as i32).to_be_bytes(),
+        }
+    }
+
+    #[test]
+    fn filter_keeps_least_delayed() {
+        let samples = [
+            sample(5_000, 40_000),
+            sample(1_200, 8_000),
+            sample(-900, 12_000),
+        ];
+        assert_eq!(filter(&samples), Some(sample(1_200, 8_000)));
+        assert_eq!(filter(&[]), None);
+    }
+
+    #[test]
+    fn distance_includes_half_delay_and_dispersion() {
+        assert_eq!(sample(0, 20_000).distance(), 10_000 + MIN_DISPERSION);
+        let coarse = Sample {
+            precision: -6,
+            ..sample(0, 0)
+        };
+        assert_eq!(coarse.distance(), 15_625 + MIN_DISPERSION);
+    }
+
+    #[test]
+    fn single_server_is_its_own_majority() {
+        let selection = select(&[sample(3_000, 10_000)]).unwrap();
+        assert_eq!(selection.offset, 3_000);
+        assert_eq!(selection.survivors, 1);
+        assert_eq!(selection.falsetickers, 0);
+    }
+
+    #[test]
+    fn falseticker_is_rejected() {
+        let samples = [
+            sample(2_000, 10_000),
+            sample(3_000, 12_000),
+            sample(2_500, 8_000),
+            sample(2_000_000, 9_000),
+        ];
+        let selection = select(&samples).unwrap();
+        assert_eq!(selection.survivors, 3);
+        assert_eq!(selection.falsetickers, 1);
+        assert!((2_000..=3_000).contains(&selection.offset));
+    }
+
+    #[test]
+    fn two_disagreeing_servers_select_nothing() {
+        assert_eq!(
+            select(&[sample(0, 10_000), sample(5_000_000, 10_000)]),
+            None
+        );
+        assert_eq!(select(&[]), None);
+    }
+
+    #[test]
+    fn combine_weights_closer_servers() {
+        let selection = select(&[sample(0, 2_000), sample(10_000, 60_000)]).unwrap();
+        assert!(selection.offset < 5_000);
+        assert_eq!(selection.distance, sample(0, 2_000).distance());
+        assert_eq!(selection.boot_time, sample(0, 2_000).boot_time);
+        assert_eq!(selection.reference_id, sample(0, 2_000).reference_id);
+    }
+
+    #[test]
+    fn large_offsets_step_small_ones_slew() {
+        let mut discipline = Discipline::default();
+        assert_eq!(discipline.update(2_000_000, 0), Correction::Step(2_000_000));
+        assert_eq!(discipline.pending(), 0);
+        assert_eq!(discipline.update(-40_000, 1_000), Correction::Slew(-40_000));
+        assert_eq!(discipline.pending(), -40_000);
+    }
+
+    #[test]
+    fn steps_leave_drift_alone() {
+        let mut discipline = Discipline::default();
+        discipline.update(0, 0);
+        assert_eq!(
+            discipline.update(5_000_000, 120_000_000),
+            Correction::Step(5_000_000)
+        );
+        assert_eq!(discipline.drift_ppm(), 0.0);
+        // the next offset is measured from the step
+        discipline.update(1_200, 240_000_000);
+        assert!(discipline.drift_ppm() > 0.0 && discipline.drift_ppm() < 5.0);
+    }
+
+    #[test]
+    fn slew_is_rate_limited() {
+        let mut discipline = Discipline::default();
+        discipline.update(1_200, 0);
+        assert_eq!(discipline.tick(1_000_000), 500);
+        assert_eq!(discipline.tick(1_000_000), 500);
+        assert_eq!(discipline.tick(1_000_000), 200);
+        assert_eq!(discipline.tick(1_000_000), 0);
+
+        discipline.update(-700, 10_000_000);
+        assert_eq!(discipline.tick(1_000_000), -500);
+        assert_eq!(discipline.tick(1_000_000), -200);
+    }
+
+    #[test]
+    fn drift_converges_on_a_slow_clock() {
+        // our clock loses 50 ppm; poll every 2 minutes
+        const TRUE_PPM: f32 = 50.0;
+        const POLL: u64 = 120_000_000;
+        let mut discipline = Discipline::default();
+        let mut error = 0.0f32;
+        for poll in 0..40u64 {
+            discipline.update(error as i64, poll * POLL);
+            for _ in 0..120 {
+                error += TRUE_PPM;
+                error -= discipline.tick(1_000_000) as f32;
+            }
+        }
+        assert!((discipline.drift_ppm() - TRUE_PPM).abs() < 2.0);
+        assert!(error.abs() < 500.0);
+    }
+
+    #[test]
+    fn drift_is_bounded_and_forget_skips_an_estimate() {
+        let mut discipline = Discipline::default();
+        discipline.update(0, 0);
+        discipline.update(120_000, 60_000_000);
+        assert!(discipline.drift_ppm() <= DRIFT_MAX_PPM);
+
+        let before = discipline.drift_ppm();
+        discipline.forget();
+        discipline.update(100_000, 120_000_000);
+        assert_eq!(discipline.drift_ppm(), before);
+    }
+}
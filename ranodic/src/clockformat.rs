@@ -0,0 +1,241 @@
+//! How the clock reads: 12 or 24 hour, seconds or not, which date, or any
+//! strftime pattern. Set at boot from `CLOCK_FORMAT`, a `;`-separated list of
+//!
+//! - `12h` / `24h`
+//! - `seconds`
+//! - `noblink`: keep the colon still
+//! - `date=short` (`Wed Mar 18`, or however the [`Locale`] writes it),
+//!   `date=iso` (`2026-03-18`), `date=week` (`Wed 18 W12`, the ISO week),
+//!   `date=none`, or `date=` a strftime pattern
+//! - `time=` a strftime pattern
+//!
+//! e.g. `12h;seconds;date=iso`. Day and month names come from the [`Locale`].
+//! [`fit`] then picks the biggest font the text fits in.
+
+use core::cell::RefCell;
+
+use alloc::string::{String, ToString};
+use anyhow::{Result, anyhow};
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embedded_graphics::mono_font::MonoFont;
+use jiff::Zoned;
+
+use crate::locale::Locale;
+
+pub const ISO_DATE: &str = "%Y-%m-%d";
+pub const WEEK_DATE: &str = "%a %d W%V";
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClockFormat {
+    /// `None` leaves the top row to the time.
+    pub date: Option<DateFormat>,
+    pub time: String,
+    /// `12h` was asked for; other clocks follow it.
+    pub hour12: bool,
+    /// Blank the colons every other half second.
+    pub blink: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DateFormat {
+    /// The locale's own short date.
+    Short,
+    Pattern(String),
+}
+
+impl Default for ClockFormat {
+    fn default() -> Self {
+        Self {
+            date: Some(DateFormat::Short),
+            time: "%H:%M".to_string(),
+            hour12: false,
+            blink: true,
+        }
+    }
+}
+
+impl ClockFormat {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut format = Self::default();
+        let (mut seconds, mut time) = (false, None);
+        for option in spec.split(';').map(str::trim).filter(|o| !o.is_empty()) {
+            match option.split_once('=') {
+                None if option == "12h" => format.hour12 = true,
+                None if option == "24h" => format.hour12 = false,
+                None if option == "seconds" => seconds = true,
+                None if option == "noblink" => format.blink = false,
+                Some(("date", "short")) => format.date = Some(DateFormat::Short),
+                Some(("date", "iso")) => format.date = Some(DateFormat::Pattern(ISO_DATE.into())),
+                Some(("date", "week")) => format.date = Some(DateFormat::Pattern(WEEK_DATE.into())),
+                Some(("date", "none")) => format.date = None,
+                Some(("date", pattern)) => {
+                    format.date = Some(DateFormat::Pattern(checked(pattern)?))
+                }
+                Some(("time", pattern)) => time = Some(checked(pattern)?),
+                _ => return Err(anyhow!("unknown clock format option {}", option)),
+            }
+        }
+        format.time = time.unwrap_or_else(|| {
+            match (format.hour12, seconds) {
+                (false, false) => "%H:%M",
+                (false, true) => "%H:%M:%S",
+                (true, false) => "%-I:%M %p",
+                (true, true) => "%-I:%M:%S %p",
+            }
+            .to_string()
+        });
+        // a ticking seconds field is blink enough
+        format.blink &= !seconds;
+        Ok(format)
+    }
+
+    pub fn date_text(&self, now: &Zoned, locale: &Locale) -> Option<String> {
+        self.date.as_ref().map(|date| match date {
+            DateFormat::Short => render(locale.short_date, now, locale),
+            DateFormat::Pattern(pattern) => render(pattern, now, locale),
+        })
+    }
+
+    /// The time, colons blanked for the second half of each second.
+    pub fn time_text(&self, now: &Zoned, locale: &Locale) -> String {
+        let text = render(&self.time, now, locale);
+        if self.blink && now.millisecond() >= 500 {
+            text.replace(':', " ")
+        } else {
+            text
+        }
+    }
+}
+
+// patterns are checked when set, so this only fails on a broken clock
+fn render(pattern: &str, now: &Zoned, locale: &Locale) -> String {
+    locale.format(pattern, now).unwrap_or_default()
+}
+
+fn checked(pattern: &str) -> Result<String> {
+    jiff::fmt::strtime::format(pattern, &Zoned::default()).map_err(anyhow::Error::msg)?;
+    Ok(pattern.to_string())
+}
+
+/// The first of `fonts`, biggest first, that fits `text` in `width` pixels;
+/// the last if none do.
+pub fn fit<'a>(text: &str, fonts: &[&'a MonoFont<'a>], width: u32) -> &'a MonoFont<'a> {
+    let chars = text.chars().count() as u32;
+    fonts
+        .iter()
+        .find(|font| {
+            chars * font.character_size.width + chars.saturating_sub(1) * font.character_spacing
+                <= width
+        })
+        .or(fonts.last())
+        .copied()
+        .expect("no fonts to fit")
+}
+
+static FORMAT: Mutex<CriticalSectionRawMutex, RefCell<Option<ClockFormat>>> =
+    Mutex::new(RefCell::new(None));
+
+pub fn get() -> ClockFormat {
+    FORMAT.lock(|format| format.borrow().clone().unwrap_or_default())
+}
+
+pub fn set(format: ClockFormat) {
+    FORMAT.lock(|cell| cell.replace(Some(format)));
+}
+
+/// Parse `spec` and make it the clock format; the current one is kept if
+/// `spec` doesn't parse.
+pub fn set_from_str(spec: &str) -> Result<()> {
+    set(ClockFormat::parse(spec)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locale::{ENGLISH, GERMAN};
+    use embedded_graphics::mono_font::ascii::{FONT_4X6, FONT_5X8, FONT_6X10, FONT_7X13};
+
+    fn at(s: &str) -> Zoned {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn default_is_the_old_layout() {
+        let format = ClockFormat::parse("").unwrap();
+        assert_eq!(format, ClockFormat::default());
+        let now = at("2026-03-18T09:05:07.2-07:00[-07:00]");
+        assert_eq!(format.date_text(&now, &ENGLISH).unwrap(), "Wed Mar 18");
+        assert_eq!(format.time_text(&now, &ENGLISH), "09:05");
+        let later = at("2026-03-18T09:05:07.7-07:00[-07:00]");
+        assert_eq!(format.time_text(&later, &ENGLISH), "09 05");
+    }
+
+    #[test]
+    fn twelve_hour_with_seconds() {
+        let format = ClockFormat::parse("12h; seconds").unwrap();
+        assert!(!format.blink);
+        let now = at("2026-03-18T21:05:07.7-07:00[-07:00]");
+        assert_eq!(format.time_text(&now, &ENGLISH), "9:05:07 PM");
+        let format = ClockFormat::parse("12h").unwrap();
+        assert_eq!(
+            format.time_text(&at("2026-03-18T00:30:00+00:00[+00:00]"), &ENGLISH),
+            "12:30 AM"
+        );
+    }
+
+    #[test]
+    fn dates() {
+        let now = at("2026-03-18T09:05:07-07:00[-07:00]");
+        let date = |spec| ClockFormat::parse(spec).unwrap().date_text(&now, &ENGLISH);
+        assert_eq!(date("date=iso").unwrap(), "2026-03-18");
+        assert_eq!(date("date=week").unwrap(), "Wed 18 W12");
+        assert_eq!(date("date=%d.%m.%Y").unwrap(), "18.03.2026");
+        assert_eq!(date("date=none"), None);
+        // the ISO week of 1 January can be the year before's
+        let new_year = at("2027-01-01T12:00:00+00:00[+00:00]");
+        let week = ClockFormat::parse("date=week").unwrap();
+        assert_eq!(week.date_text(&new_year, &ENGLISH).unwrap(), "Fri 01 W53");
+    }
+
+    #[test]
+    fn dates_in_the_locale() {
+        let now = at("2026-03-18T21:05:07-07:00[-07:00]");
+        let german = |spec| {
+            let format = ClockFormat::parse(spec).unwrap();
+            (
+                format.date_text(&now, &GERMAN).unwrap(),
+                format.time_text(&now, &GERMAN),
+            )
+        };
+        assert_eq!(german(""), ("Mi 18. Mär".into(), "21:05".into()));
+        assert_eq!(german("date=week").0, "Mi 18 W12");
+        assert_eq!(german("date=%A, %-d. %B").0, "Mittwoch, 18. März");
+    }
+
+    #[test]
+    fn custom_time() {
+        let format = ClockFormat::parse("time=%H.%M;noblink").unwrap();
+        let now = at("2026-03-18T09:05:07.7-07:00[-07:00]");
+        assert_eq!(format.time_text(&now, &ENGLISH), "09.05");
+    }
+
+    #[test]
+    fn rejects_nonsense() {
+        assert!(ClockFormat::parse("13h").is_err());
+        assert!(ClockFormat::parse("date=%a %").is_err());
+        assert!(ClockFormat::parse("colour=red").is_err());
+    }
+
+    #[test]
+    fn biggest_font_that_fits() {
+        let fonts = [&FONT_7X13, &FONT_6X10, &FONT_5X8, &FONT_4X6];
+        let width = |text, room| fit(text, &fonts, room).character_size.width;
+        assert_eq!(width("09:05", 64), 7);
+        assert_eq!(width("9:05 PM", 45), 6);
+        assert_eq!(width("9:05:07 PM", 64), 6);
+        assert_eq!(width("12:05:07 PM", 64), 5);
+        assert_eq!(width("12:05:07 PM", 46), 4);
+        assert_eq!(width("far too long for any font", 64), 4);
+    }
+}
@@ -0,0 +1,22 @@
+/// Display timezone, set at build time; see [`crate::timezone::parse`] for
+/// what's accepted.
+pub const TIMEZONE: &str = env!("TIMEZONE");
+
+/// Clock layout, set at build time; see [`crate::clockformat`] for the
+/// options.
+pub const CLOCK_FORMAT: &str = env!("CLOCK_FORMAT");
+
+/// Language for day and month names; see [`crate::locale::LOCALES`].
+pub const LOCALE: &str = env!("LOCALE");
+
+/// Zones for the world clock page; see [`crate::worldclock`]. Empty leaves
+/// the page out.
+pub const WORLD_CLOCK: &str = env!("WORLD_CLOCK");
+
+/// Alarm schedule, set at build time; see [`crate::alarms`]. Empty sets
+/// none.
+pub const ALARMS: &str = env!("ALARMS");
+
+/// Calendar for the agenda page; see [`crate::agenda`]. Empty leaves the
+/// page out.
+pub const ICAL_URL: &str = env!("ICAL_URL");
@@ -0,0 +1,99 @@
+//! A line console on TCP/2323 for the [`crate::timers`] and
+//! [`crate::alarms`]: `nc panel 2323`, then e.g. `timer 5m` or `alarm
+//! snooze`. Every command is answered with what's running, and a blank line
+//! asks for the same.
+
+use alloc::string::String;
+use anyhow::Result;
+
+use crate::alarms::{self, Silence};
+use crate::log::{debug, info};
+use crate::ntp::gettimeofday;
+use embassy_net::Stack;
+use embassy_net::tcp::TcpSocket;
+use embassy_time::Duration;
+use embedded_io_async::Write;
+use esp_hal::ram;
+
+pub const CONSOLE_PORT: u16 = 2323;
+const MAX_LINE: usize = 128;
+// an idle client is dropped so the next one can connect
+const IDLE_TIMEOUT: u64 = 300;
+
+// so an alarm dismissed just before one of our resets stays dismissed
+#[ram(unstable(rtc_fast), unstable(persistent))]
+static mut SILENCE: [i64; alarms::WORDS] = [0; alarms::WORDS];
+
+/// Put back the alarm dismissals and snoozes from before a reset.
+pub fn restore_silence() {
+    #[allow(static_mut_refs)]
+    if let Some(silence) = Silence::from_words(unsafe { &SILENCE }) {
+        alarms::set_silence(silence);
+    }
+}
+
+#[embassy_executor::task]
+pub async fn console_serve(stack: Stack<'static>) {
+    let mut rx_buffer = [0; 256];
+    let mut tx_buffer = [0; 512];
+    info!("console_serve: listening on {}", CONSOLE_PORT);
+    loop {
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+        socket.set_timeout(Some(Duration::from_secs(IDLE_TIMEOUT)));
+        if let Err(e) = socket.accept(CONSOLE_PORT).await {
+            debug!("console_serve: {:?}", e);
+            continue;
+        }
+        if let Err(e) = session(&mut socket).await {
+            debug!("console_serve: {:?}", e);
+        }
+        socket.close();
+        let _ = socket.flush().await;
+    }
+}
+
+async fn run(command: &str) -> Result<String> {
+    let now = gettimeofday().await;
+    let tz = crate::timezone::get();
+    match command.split_once(' ').map_or(command, |(word, _)| word) {
+        "alarm" => {
+            let argument = command.split_whitespace().nth(1);
+            let reply = alarms::command(argument, &now.to_zoned(tz))?;
+            unsafe { SILENCE = alarms::silence().to_words() };
+            Ok(reply)
+        }
+        #[cfg(feature = "rtcchip")]
+        "rtc" => Ok(alloc::format!("rtc {}", crate::rtc::calibration_report())),
+        _ => crate::timers::command(command, now, &tz),
+    }
+}
+
+async fn session(socket: &mut TcpSocket<'_>) -> Result<(), embassy_net::tcp::Error> {
+    let mut line = [0; MAX_LINE];
+    let mut len = 0;
+    let mut byte = [0; 1];
+    loop {
+        if socket.read(&mut byte).await? == 0 {
+            return Ok(());
+        }
+        match byte[0] {
+            b'\n' => {
+                let command = core::str::from_utf8(&line[..len]).unwrap_or("").trim();
+                len = 0;
+                let reply = match run(command).await {
+                    Ok(status) => status,
+                    Err(e) => alloc::format!("error: {}", e),
+                };
+                info!("console: {} -> {}", command, reply.as_str());
+                socket.write_all(reply.as_bytes()).await?;
+                socket.write_all(b"\n").await?;
+            }
+            // overlong lines are cut short rather than dropped
+            byte if len < MAX_LINE => {
+                line[len] = byte;
+                len += 1;
+            }
+            _ => {}
+        }
+    }
+}
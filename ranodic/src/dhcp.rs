@@ -0,0 +1,196 @@
+//! Just enough DHCP to learn the NTP servers (option 42) a network offers.
+//! embassy-net runs the lease itself but only asks for the router and DNS,
+//! and keeps the ACK to itself, so we send our own DHCPINFORM and pick the
+//! reply off a raw socket.
+
+use alloc::{vec, vec::Vec};
+
+use smoltcp::wire::{
+    DHCP_CLIENT_PORT, DHCP_SERVER_PORT, DhcpMessageType, DhcpOpCode, DhcpPacket, DhcpRepr,
+    EthernetAddress, IpProtocol, Ipv4Address, Ipv4Packet, UdpPacket,
+};
+
+pub const OPT_NTP_SERVERS: u8 = 42;
+const OPT_MESSAGE_TYPE: u8 = 53;
+// BOOTP relays drop anything shorter
+const MIN_PACKET_LEN: usize = 300;
+
+/// A DHCPINFORM from `client_ip`, which already has its lease, asking only for
+/// option 42. Goes to the broadcast address, port 67.
+pub fn inform(transaction_id: u32, mac: [u8; 6], client_ip: Ipv4Address) -> Option<Vec<u8>> {
+    let repr = DhcpRepr {
+        message_type: DhcpMessageType::Inform,
+        transaction_id,
+        secs: 0,
+        client_hardware_address: EthernetAddress(mac),
+        client_ip,
+        your_ip: Ipv4Address::UNSPECIFIED,
+        server_ip: Ipv4Address::UNSPECIFIED,
+        router: None,
+        subnet_mask: None,
+        relay_agent_ip: Ipv4Address::UNSPECIFIED,
+        broadcast: false,
+        requested_ip: None,
+        client_identifier: Some(EthernetAddress(mac)),
+        server_identifier: None,
+        parameter_request_list: Some(&[OPT_NTP_SERVERS]),
+        dns_servers: None,
+        max_size: None,
+        lease_duration: None,
+        renew_duration: None,
+        rebind_duration: None,
+        additional_options: &[],
+    };
+    let mut buffer = vec![0; repr.buffer_len().max(MIN_PACKET_LEN)];
+    repr.emit(&mut DhcpPacket::new_unchecked(&mut buffer[..]))
+        .ok()?;
+    Some(buffer)
+}
+
+/// The NTP servers in a DHCPACK answering `transaction_id`, given the whole
+/// IPv4 datagram as a raw socket sees it. `None` if the datagram isn't that
+/// ACK; an empty list if it is but offers no servers.
+pub fn ntp_servers(datagram: &[u8], transaction_id: u32) -> Option<Vec<Ipv4Address>> {
+    let ip = Ipv4Packet::new_checked(datagram).ok()?;
+    if ip.next_header() != IpProtocol::Udp {
+        return None;
+    }
+    let udp = UdpPacket::new_checked(ip.payload()).ok()?;
+    if udp.src_port() != DHCP_SERVER_PORT || udp.dst_port() != DHCP_CLIENT_PORT {
+        return None;
+    }
+    let dhcp = DhcpPacket::new_checked(udp.payload()).ok()?;
+    if dhcp.opcode() != DhcpOpCode::Reply || dhcp.transaction_id() != transaction_id {
+        return None;
+    }
+    let mut options = dhcp.options();
+    let is_ack = options.any(|option| {
+        option.kind == OPT_MESSAGE_TYPE
+            && option.data.first().copied() == Some(DhcpMessageType::Ack.into())
+    });
+    if !is_ack {
+        return None;
+    }
+    Some(
+        dhcp.options()
+            .filter(|option| option.kind == OPT_NTP_SERVERS)
+            .flat_map(|option| option.data.as_chunks::<4>().0)
+            .map(|octets| Ipv4Address::from(*octets))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smoltcp::wire::DhcpOption;
+
+    const MAC: [u8; 6] = [0x02, 0, 0, 0xab, 0xcd, 0xef];
+    const XID: u32 = 0x1234_5678;
+
+    // wrap a DHCP payload in the IPv4 and UDP headers a raw socket would see
+    fn datagram(src_port: u16, dhcp: &[u8]) -> Vec<u8> {
+        let udp_len = 8 + dhcp.len();
+        let total = 20 + udp_len;
+        let mut out = vec![0x45, 0];
+        out.extend_from_slice(&(total as u16).to_be_bytes());
+        out.extend_from_slice(&[0, 0, 0, 0, 64, 17, 0, 0]);
+        out.extend_from_slice(&[192, 168, 1, 1, 192, 168, 1, 50]);
+        out.extend_from_slice(&src_port.to_be_bytes());
+        out.extend_from_slice(&DHCP_CLIENT_PORT.to_be_bytes());
+        out.extend_from_slice(&(udp_len as u16).to_be_bytes());
+        out.extend_from_slice(&[0, 0]);
+        out.extend_from_slice(dhcp);
+        out
+    }
+
+    fn reply(message_type: DhcpMessageType, xid: u32, options: &[DhcpOption]) -> Vec<u8> {
+        let repr = DhcpRepr {
+            message_type,
+            transaction_id: xid,
+            secs: 0,
+            client_hardware_address: EthernetAddress(MAC),
+            client_ip: Ipv4Address::new(192, 168, 1, 50),
+            your_ip: Ipv4Address::UNSPECIFIED,
+            server_ip: Ipv4Address::UNSPECIFIED,
+            router: Some(Ipv4Address::new(192, 168, 1, 1)),
+            subnet_mask: None,
+            relay_agent_ip: Ipv4Address::UNSPECIFIED,
+            broadcast: false,
+            requested_ip: None,
+            client_identifier: None,
+            server_identifier: Some(Ipv4Address::new(192, 168, 1, 1)),
+            parameter_request_list: None,
+            dns_servers: None,
+            max_size: None,
+            lease_duration: None,
+            renew_duration: None,
+            rebind_duration: None,
+            additional_options: options,
+        };
+        let mut buffer = vec![0; repr.buffer_len()];
+        repr.emit(&mut DhcpPacket::new_unchecked(&mut buffer[..]))
+            .unwrap();
+        buffer
+    }
+
+    #[test]
+    fn inform_asks_for_option_42() {
+        let packet = inform(XID, MAC, Ipv4Address::new(192, 168, 1, 50)).unwrap();
+        assert!(packet.len() >= MIN_PACKET_LEN);
+        let dhcp = DhcpPacket::new_checked(&packet[..]).unwrap();
+        assert_eq!(dhcp.opcode(), DhcpOpCode::Request);
+        assert_eq!(dhcp.transaction_id(), XID);
+        assert_eq!(dhcp.client_ip(), Ipv4Address::new(192, 168, 1, 50));
+        let repr = DhcpRepr::parse(&dhcp).unwrap();
+        assert_eq!(repr.message_type, DhcpMessageType::Inform);
+        assert_eq!(repr.parameter_request_list, Some(&[OPT_NTP_SERVERS][..]));
+    }
+
+    #[test]
+    fn ack_yields_ntp_servers() {
+        let servers = [192, 168, 1, 1, 10, 0, 0, 123];
+        let ack = reply(
+            DhcpMessageType::Ack,
+            XID,
+            &[DhcpOption {
+                kind: OPT_NTP_SERVERS,
+                data: &servers,
+            }],
+        );
+        assert_eq!(
+            ntp_servers(&datagram(DHCP_SERVER_PORT, &ack), XID),
+            Some(vec![
+                Ipv4Address::new(192, 168, 1, 1),
+                Ipv4Address::new(10, 0, 0, 123)
+            ])
+        );
+    }
+
+    #[test]
+    fn ack_without_option_42_is_empty() {
+        let ack = reply(DhcpMessageType::Ack, XID, &[]);
+        assert_eq!(
+            ntp_servers(&datagram(DHCP_SERVER_PORT, &ack), XID),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn ignores_other_traffic() {
+        let servers = [192, 168, 1, 1];
+        let option = [DhcpOption {
+            kind: OPT_NTP_SERVERS,
+            data: &servers,
+        }];
+        let ack = reply(DhcpMessageType::Ack, XID, &option);
+        assert_eq!(
+            ntp_servers(&datagram(DHCP_SERVER_PORT, &ack), XID + 1),
+            None
+        );
+        assert_eq!(ntp_servers(&datagram(5353, &ack), XID), None);
+        let nak = reply(DhcpMessageType::Nak, XID, &option);
+        assert_eq!(ntp_servers(&datagram(DHCP_SERVER_PORT, &nak), XID), None);
+        assert_eq!(ntp_servers(&[0x45, 0, 0], XID), None);
+    }
+}
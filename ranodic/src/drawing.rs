@@ -1,184 +1,45 @@
-use core::sync::atomic::Ordering;
-
-use alloc::string::ToString;
-
-use crate::log::{error, info};
-use embassy_sync::{
-    blocking_mutex::raw::CriticalSectionRawMutex, lazy_lock::LazyLock, signal::Signal,
-    watch::Receiver,
-};
-use embassy_time::Timer;
+use embassy_sync::lazy_lock::LazyLock;
 use embedded_graphics::{
     Drawable,
-    geometry::{Point, Size},
+    draw_target::DrawTarget,
+    geometry::Point,
     image::ImageDrawable,
     mono_font::{
         MonoTextStyle,
         MonoTextStyleBuilder,
-        ascii::{FONT_4X6, FONT_5X7, FONT_5X8, FONT_6X10, FONT_7X13},
-        // iso_8859_1::FONT_5X8,
+        // Latin-1 so that localized day and month names draw
+        iso_8859_1::{FONT_4X6, FONT_5X7, FONT_5X8, FONT_6X10, FONT_7X13},
     },
     pixelcolor::Rgb888,
-    prelude::{Primitive, RgbColor},
-    primitives::{PrimitiveStyle, PrimitiveStyleBuilder, Rectangle},
+    prelude::RgbColor,
     text::{Alignment, Text},
 };
-use esp_hub75::Color;
-use static_cell::StaticCell;
-use tinygif::Gif;
-
-use crate::{
-    hub75::FBType,
-    nightscout::{BGDATA, BgReading, get_style},
-    ntp::{TIME_SYNCED, zgettimeofday},
-    weather::{FORECASTS, FORECASTS_PRESENT},
-};
-use jiff::ToSpan;
+use tinygif::{Frame, Gif};
 
-pub type FrameBufferExchange = Signal<CriticalSectionRawMutex, &'static mut FBType>;
 // const CLOCKPOINT: Point = Point::new(0, 7);
 
 // static SHOWME: LazyLock<Gif> =
 //     LazyLock::new(|| Gif::<Rgb888>::from_slice(include_bytes!("../../assets/showme.gif")).unwrap());
 
-static LOGO: LazyLock<Gif> = LazyLock::new(|| {
+pub static LOGO: LazyLock<Gif> = LazyLock::new(|| {
     Gif::<Rgb888>::from_slice(include_bytes!("../../assets/ranodiclogo.gif")).unwrap()
 });
 
-// the painter sends the last painted frame down this signal
-pub static FB_XMIT: FrameBufferExchange = FrameBufferExchange::new();
-// the xmitter sends the obsolete fb back
-pub static FB_PAINT: FrameBufferExchange = FrameBufferExchange::new();
-
-pub static FB0: StaticCell<FBType> = StaticCell::new();
-pub static FB1: StaticCell<FBType> = StaticCell::new();
-
-// everything: "%a %b %d %y\n%H:%M:%S"
-const DATE_FMT: &str = "%a %b %d";
-const TIME_FMT: &str = "%H:%M";
-const TIME_BLINK_FMT: &str = "%H %M";
-
-async fn past_logo(bgrecvr: &Receiver<'_, CriticalSectionRawMutex, BgReading, 2>) -> bool {
-    // soft internet invariant
-    TIME_SYNCED.load(Ordering::Relaxed)
-        || bgrecvr.contains_value()
-        || FORECASTS_PRESENT.load(Ordering::Relaxed)
-}
-
-#[embassy_executor::task]
-pub async fn display_painter(fb_inc: &'static mut FBType) {
-    info!("display painter started");
-    let mut bgrecvr = BGDATA.receiver().expect("couldn't get BGDATA recvr");
-    let mut fb = fb_inc;
-    let mac_address = crate::MAC_ADDRESS.get().await;
-    let mac_str = alloc::format!(
-        "{:02x}{:02x}{:02x}",
-        mac_address[3],
-        mac_address[4],
-        mac_address[5]
-    );
-    fb.erase();
-    'logo: loop {
-        for frame in LOGO.get().frames() {
-            frame.draw(fb).unwrap();
-            Text::with_alignment(&mac_str, MACPOINT, SMOLFONT, Alignment::Left)
-                .draw(fb)
-                .expect("failed to draw text");
-            Text::with_alignment(
-                env!("CARGO_PKG_VERSION"),
-                VERSPOINT,
-                SMOLFONT,
-                Alignment::Right,
-            )
-            .draw(fb)
-            .expect("failed to draw text");
-            FB_XMIT.signal(fb);
-            fb = FB_PAINT.wait().await;
-            fb.erase();
-            if past_logo(&bgrecvr).await {
-                break 'logo;
-            }
-            Timer::after_millis(frame.delay_centis as u64 * 10).await;
-        }
-    }
-    // bug: sometimes it forgets
-    let mut was_time_ever_synced = false;
-    loop {
-        was_time_ever_synced = was_time_ever_synced || TIME_SYNCED.load(Ordering::Relaxed);
-        if crate::GRACEFUL_SHUTDOWN.load(Ordering::Relaxed) {
-            info!("blanking buffers");
-            fb.erase();
-            FB_XMIT.signal(fb);
-            fb = FB_PAINT.wait().await;
-            fb.erase();
-            break;
-        }
-        let now = zgettimeofday().await;
-        if was_time_ever_synced {
-            Text::with_alignment(
-                now.strftime(DATE_FMT).to_string().as_str(),
-                DATEPOINT,
-                DATEFONTB,
-                Alignment::Center,
-            )
-            .draw(fb)
-            .expect("failed to draw text");
-
-            Text::with_alignment(
-                now.strftime(if now.millisecond() < 500 {
-                    TIME_FMT
-                } else {
-                    TIME_BLINK_FMT
-                })
-                .to_string()
-                .as_str(),
-                TIMEPOINT,
-                TIMEFONT,
-                Alignment::Left,
-            )
-            .draw(fb)
-            .expect("failed to draw text");
-        }
-        if bgrecvr.contains_value() {
-            let bgreading = bgrecvr.get().await;
-            let character_style = get_style(&bgreading).await;
-            Text::with_alignment(
-                bgreading.bg.to_string().as_str(),
-                BGPOINT,
-                character_style,
-                Alignment::Right,
-            )
-            .draw(fb)
-            .expect("failed to draw text");
-
-            // 30,8 -> 30,17
-            // 46,8 -> 46,17
-            // let style = PrimitiveStyleBuilder::new()
-            //     .stroke_color(Rgb888::RED)
-            //     .stroke_width(1)
-            //     .fill_color(Rgb888::GREEN)
-            //     .build();
-            // Rectangle::new(Point::new(30, 8), Size::new(16, 9))
-            //     .into_styled(style)
-            //     .draw(fb)
-            //     .expect("couldn't draw graphrect");
-        }
-        if was_time_ever_synced && FORECASTS_PRESENT.load(Ordering::Relaxed) {
-            // debug!("drawing, forecasts present");
-            let forecasts = FORECASTS.lock().await;
-            if let Some(forecast) = forecasts.get_forecast(&now) {
-                crate::weather::draw_forecast(now, &forecasts, forecast, fb);
-            } else {
-                error!("no relevant forecast in cache");
-            }
-        }
-
-        FB_XMIT.signal(fb);
-        fb = FB_PAINT.wait().await;
-        fb.erase();
-        Timer::after_millis(50).await;
-    }
-    info!("display_painter: terminating");
+/// One frame of the boot logo, with the end of the MAC address and the
+/// firmware version along the bottom.
+pub fn draw_logo<D>(
+    frame: &Frame<'_, Rgb888>,
+    mac: &str,
+    version: &str,
+    target: &mut D,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    frame.draw(target)?;
+    Text::with_alignment(mac, MACPOINT, SMOLFONT, Alignment::Left).draw(target)?;
+    Text::with_alignment(version, VERSPOINT, SMOLFONT, Alignment::Right).draw(target)?;
+    Ok(())
 }
 
 pub enum DrawEvent {
@@ -187,10 +48,10 @@ pub enum DrawEvent {
 
 pub type ColorMonoTextStyle<'a> = MonoTextStyle<'a, Rgb888>;
 
-const DATEPOINT: Point = Point::new(32, 6);
+pub const DATEPOINT: Point = Point::new(32, 6);
 
-const TIMEPOINT: Point = Point::new(0, 15);
-const BGPOINT: Point = Point::new(64, 15);
+pub const TIMEPOINT: Point = Point::new(0, 15);
+pub const BGPOINT: Point = Point::new(64, 15);
 
 const VERSPOINT: Point = Point::new(64, 31);
 const MACPOINT: Point = Point::new(0, 31);
@@ -205,40 +66,40 @@ const NEXT_TEMP_HIGH_POINT: Point = Point::new(54, 30);
 
 const DATEFONT: ColorMonoTextStyle<'static> = MonoTextStyleBuilder::new()
     .font(&FONT_5X7)
-    .text_color(Color::WHITE)
-    .background_color(Color::BLACK)
+    .text_color(Rgb888::WHITE)
+    .background_color(Rgb888::BLACK)
     .build();
 
-const DATEFONTB: ColorMonoTextStyle<'static> = MonoTextStyleBuilder::new()
+pub const DATEFONTB: ColorMonoTextStyle<'static> = MonoTextStyleBuilder::new()
     .font(&FONT_5X8)
-    .text_color(Color::WHITE)
-    .background_color(Color::BLACK)
+    .text_color(Rgb888::WHITE)
+    .background_color(Rgb888::BLACK)
     .build();
 
 const DATEFONTC: ColorMonoTextStyle<'static> = MonoTextStyleBuilder::new()
     .font(&FONT_4X6)
-    .text_color(Color::WHITE)
-    .background_color(Color::BLACK)
+    .text_color(Rgb888::WHITE)
+    .background_color(Rgb888::BLACK)
     .build();
 
-const TIMEFONT: ColorMonoTextStyle<'static> = MonoTextStyleBuilder::new()
+pub const TIMEFONT: ColorMonoTextStyle<'static> = MonoTextStyleBuilder::new()
     .font(&FONT_6X10)
-    .text_color(Color::WHITE)
-    .background_color(Color::BLACK)
+    .text_color(Rgb888::WHITE)
+    .background_color(Rgb888::BLACK)
     .build();
 
 pub const fn bgfontbase() -> MonoTextStyleBuilder<'static, Rgb888> {
     MonoTextStyleBuilder::new().font(&FONT_6X10)
 }
 
-const SMOLFONT: ColorMonoTextStyle<'static> = MonoTextStyleBuilder::new()
+pub const SMOLFONT: ColorMonoTextStyle<'static> = MonoTextStyleBuilder::new()
     .font(&FONT_4X6)
-    .text_color(Color::WHITE)
-    .background_color(Color::BLACK)
+    .text_color(Rgb888::WHITE)
+    .background_color(Rgb888::BLACK)
     .build();
 
-const BIGFONT: ColorMonoTextStyle<'static> = MonoTextStyleBuilder::new()
+pub const BIGFONT: ColorMonoTextStyle<'static> = MonoTextStyleBuilder::new()
     .font(&FONT_7X13)
-    .text_color(Color::WHITE)
-    .background_color(Color::BLACK)
+    .text_color(Rgb888::WHITE)
+    .background_color(Rgb888::BLACK)
     .build();
@@ -1,6 +1,7 @@
 extern crate alloc;
 
 use crate::log::{error, info, println};
+use alloc::string::ToString;
 use embassy_executor::SendSpawner;
 use embassy_net::{DhcpConfig, StackResources};
 use embassy_time::{Duration, Timer};
@@ -14,7 +15,7 @@ use esp_radio::Controller;
 use esp_rtos::embassy::InterruptExecutor;
 use static_cell::StaticCell;
 
-const NUM_SOCKS: usize = 8;
+const NUM_SOCKS: usize = 10;
 static STACK_RESOURCES: StaticCell<StackResources<NUM_SOCKS>> = StaticCell::new();
 static WIFI_CONTROLLER: StaticCell<Controller> = StaticCell::new();
 
@@ -61,6 +62,35 @@ pub async fn main(spawner: embassy_executor::Spawner) {
     #[cfg(feature = "esp32")]
     esp_alloc::heap_allocator!(size: 16 * 1024);
 
+    // POSIX rules are parsed onto the heap
+    if let Err(e) = crate::timezone::set_from_str(crate::config::TIMEZONE) {
+        error!(
+            "bad TIMEZONE {}: {}",
+            crate::config::TIMEZONE,
+            e.to_string()
+        );
+    }
+    if let Err(e) = crate::clockformat::set_from_str(crate::config::CLOCK_FORMAT) {
+        error!(
+            "bad CLOCK_FORMAT {}: {}",
+            crate::config::CLOCK_FORMAT,
+            e.to_string()
+        );
+    }
+    if let Err(e) = crate::locale::set_from_str(crate::config::LOCALE) {
+        error!("bad LOCALE {}: {}", crate::config::LOCALE, e.to_string());
+    }
+    if let Err(e) = crate::worldclock::set_from_str(crate::config::WORLD_CLOCK) {
+        error!(
+            "bad WORLD_CLOCK {}: {}",
+            crate::config::WORLD_CLOCK,
+            e.to_string()
+        );
+    }
+    if let Err(e) = crate::alarms::set_from_str(crate::config::ALARMS) {
+        error!("bad ALARMS {}: {}", crate::config::ALARMS, e.to_string());
+    }
+
     let rtc = crate::RTC
         .init_with(|| core::cell::UnsafeCell::new(esp_hal::rtc_cntl::Rtc::new(peripherals.LPWR)));
     rtc.get_mut()
@@ -71,8 +101,19 @@ pub async fn main(spawner: embassy_executor::Spawner) {
     info!("starting RTC watchdog");
     spawner.must_spawn(crate::watchdog_controller(rwdt));
 
+    crate::ntp::restore_tick();
+    #[cfg(feature = "console")]
+    crate::console::restore_silence();
+
+    // a chip we haven't written this boot may still beat a restored time
     #[cfg(feature = "rtcchip")]
-    let _ = crate::rtc::ic_to_sys().await;
+    {
+        let now = crate::RTCREF.get().await.time_since_boot().as_secs();
+        let error = crate::timesource::chip_error(now);
+        if crate::timesource::should_apply(crate::timesource::Source::RtcChip, error, now) {
+            let _ = crate::rtc::ic_to_sys().await;
+        }
+    }
 
     let hp_executor = {
         let software_interrupt = SoftwareInterruptControl::new(peripherals.SW_INTERRUPT);
@@ -91,17 +132,19 @@ pub async fn main(spawner: embassy_executor::Spawner) {
     let hp_spawner =
         HIPRI_SPAWNER.init_with(|| hp_executor.start(esp_hal::interrupt::Priority::Priority3));
 
-    // hp_spawner.must_spawn(crate::ntp::tick_writer());
+    hp_spawner.must_spawn(crate::ntp::tick_writer());
+    #[cfg(feature = "gps-pps")]
+    hp_spawner.must_spawn(crate::gps::pps_edges());
 
     // 4 brightness bits slays the stack here
-    let fb0 = crate::drawing::FB0.init_with(|| crate::hub75::FBType::new());
-    let fb1 = crate::drawing::FB1.init_with(|| crate::hub75::FBType::new());
+    let fb0 = crate::hub75::FB0.init_with(|| crate::hub75::FBType::new());
+    let fb1 = crate::hub75::FB1.init_with(|| crate::hub75::FBType::new());
     hp_spawner.must_spawn(crate::hub75::hub75_task(
         // tried to pick least-annoying pinout for both the DevKit-C and DevKit-M
         Default::default(),
         fb1,
     ));
-    spawner.must_spawn(crate::drawing::display_painter(fb0));
+    spawner.must_spawn(crate::hub75::display_painter(fb0));
 
     if crate::net::SSID.is_empty() || crate::net::SSID == crate::net::DEFAULT_SSID {
         error!("no WIFI configured");
@@ -160,11 +203,23 @@ pub async fn main(spawner: embassy_executor::Spawner) {
         };
         // net tasks have their own net up guards
         spawner.must_spawn(crate::ntp::ntp_sync(stack));
+        #[cfg(feature = "ntpserver")]
+        spawner.must_spawn(crate::ntp::ntp_serve(stack));
+        #[cfg(feature = "console")]
+        spawner.must_spawn(crate::console::console_serve(stack));
         spawner.must_spawn(crate::nightscout::nightscout_query(stack));
         spawner.must_spawn(crate::weather::weather_query(stack));
+        spawner.must_spawn(crate::airquality::airquality_query(stack));
+        if !crate::config::ICAL_URL.is_empty() {
+            spawner.must_spawn(crate::agenda::agenda_query(stack));
+        }
     }
+    #[cfg(feature = "rtcchip")]
     spawner.must_spawn(crate::rtc::desync_failsafe());
 
+    #[cfg(feature = "gps")]
+    spawner.must_spawn(crate::gps::gps_sync());
+
     #[cfg(feature = "heapstats")]
     spawner.spawn(crate::heap_stats_printer()).ok();
 
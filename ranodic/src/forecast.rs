@@ -2,10 +2,12 @@ use core::sync::atomic::Ordering;
 
 use crate::log::debug;
 use alloc::string::ToString;
-use jiff::{ToSpan, Zoned, civil::DateTime};
+use jiff::{ToSpan, Zoned, civil::Date};
 use num_enum::{TryFromPrimitive, TryFromPrimitiveError};
 
-use crate::{ntp::zgettimeofday, weather::FORECASTS_PRESENT};
+#[cfg(target_os = "none")]
+use crate::ntp::zgettimeofday;
+use crate::weather::FORECASTS_PRESENT;
 
 use embedded_graphics::pixelcolor::Rgb888;
 
@@ -96,11 +98,17 @@ pub struct WeatherForecast {
     pub timespan: Zoned,
     pub temperature: f32,
     pub relative_humidity: u8,
+    pub apparent_temperature: f32,
     pub precipitation: f32,
     pub precipitation_probability: u8,
     pub weather_code: WMOCode,
     pub is_day: bool,
     pub sunshine_duration: f32,
+    pub wind_speed: f32,
+    pub wind_gusts: f32,
+    /// Meteorological convention: the direction the wind blows *from*, in degrees.
+    pub wind_direction: u16,
+    pub uv_index: f32,
 }
 
 impl Default for WeatherForecast {
@@ -109,11 +117,16 @@ impl Default for WeatherForecast {
             timespan: Default::default(),
             temperature: 32.0,
             relative_humidity: 100,
+            apparent_temperature: 32.0,
             precipitation: 6.5,
             precipitation_probability: 100,
             weather_code: WMOCode::ClearSky,
             is_day: false,
             sunshine_duration: 0.0,
+            wind_speed: 0.0,
+            wind_gusts: 0.0,
+            wind_direction: 0,
+            uv_index: 0.0,
         }
     }
 }
@@ -125,26 +138,32 @@ impl WeatherForecast {
         _timezone: &str,
         temperature: f32,
         relative_humidity: u8,
+        apparent_temperature: f32,
         precipitation: f32,
         precipitation_probability: u8,
         weather_code: WMOCode,
         is_day: bool,
         sunshine_duration: f32,
+        wind_speed: f32,
+        wind_gusts: f32,
+        wind_direction: u16,
+        uv_index: f32,
     ) -> anyhow::Result<Self> {
-        let timespan: Zoned = time
-            .parse::<DateTime>()
-            .map_err(anyhow::Error::msg)?
-            .to_zoned(crate::ntp::TIMEZONE)
-            .map_err(anyhow::Error::msg)?;
+        let timespan = crate::timezone::from_utc_civil(time)?;
         Ok(Self {
             timespan,
             temperature,
             relative_humidity,
+            apparent_temperature,
             precipitation,
             precipitation_probability,
             weather_code,
             is_day,
             sunshine_duration,
+            wind_speed,
+            wind_gusts,
+            wind_direction,
+            uv_index,
         })
     }
     pub fn is_during(&self, timestamp: &Zoned) -> bool {
@@ -160,15 +179,20 @@ impl defmt::Format for WeatherForecast {
     fn format(&self, fmt: defmt::Formatter) {
         defmt::write!(
             fmt,
-            "WeatherForecast('{}', {}, {}, {}, {}, {}, {}, {})",
+            "WeatherForecast('{}', {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {})",
             self.timespan.to_string(),
             self.temperature,
             self.relative_humidity,
+            self.apparent_temperature,
             self.precipitation,
             self.precipitation_probability,
             self.weather_code,
             self.is_day,
-            self.sunshine_duration
+            self.sunshine_duration,
+            self.wind_speed,
+            self.wind_gusts,
+            self.wind_direction,
+            self.uv_index
         );
     }
 }
@@ -196,8 +220,68 @@ impl Ord for WeatherForecast {
     }
 }
 
+/// Precipitation (inches) in a 15-minute slot that counts as rain.
+pub const NOWCAST_WET: f32 = 0.004;
+/// How far ahead the nowcast looks for rain starting or stopping.
+pub const NOWCAST_HORIZON_MINUTES: i64 = 120;
+/// Length of an Open-Meteo `minutely_15` slot.
+pub const NOWCAST_SLOT_MINUTES: i64 = 15;
+
+/// What the next two hours of `minutely_15` precipitation look like.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Nowcast {
+    /// No slots cover the horizon.
+    Unknown,
+    /// Dry for the whole horizon.
+    Dry,
+    /// Wet for the whole horizon.
+    Raining,
+    /// Dry now, rain starts in this many minutes.
+    StartsIn(u16),
+    /// Wet now, rain stops in this many minutes.
+    StopsIn(u16),
+}
+
+/// Works out when rain starts or stops after `now`.
+///
+/// Each sample is `(end of slot, precipitation in the slot)`; Open-Meteo sums
+/// the *preceding* 15 minutes.  Samples need not be sorted.
+pub fn nowcast(now: &Zoned, samples: &[(Zoned, f32)]) -> Nowcast {
+    let horizon = now + NOWCAST_HORIZON_MINUTES.minutes();
+    let mut slots: alloc::vec::Vec<&(Zoned, f32)> = samples
+        .iter()
+        .filter(|(end, _)| end > now && (end - NOWCAST_SLOT_MINUTES.minutes()) < horizon)
+        .collect();
+    slots.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let Some((_, first)) = slots.first() else {
+        return Nowcast::Unknown;
+    };
+    let wet_now = *first >= NOWCAST_WET;
+    let change = slots
+        .iter()
+        .find(|(_, precipitation)| (*precipitation >= NOWCAST_WET) != wet_now);
+    match (change, wet_now) {
+        (None, false) => Nowcast::Dry,
+        (None, true) => Nowcast::Raining,
+        (Some((end, _)), wet_now) => {
+            let start = end - NOWCAST_SLOT_MINUTES.minutes();
+            let minutes = (&start - now)
+                .total(jiff::Unit::Minute)
+                .map(|minutes| minutes.max(0.0) as u16)
+                .unwrap_or(0);
+            if wet_now {
+                Nowcast::StopsIn(minutes)
+            } else {
+                Nowcast::StartsIn(minutes)
+            }
+        }
+    }
+}
+
 pub struct WeatherForecastCache {
     forecasts: alloc::vec::Vec<WeatherForecast>,
+    nowcast: alloc::vec::Vec<(Zoned, f32)>,
 }
 
 impl Default for WeatherForecastCache {
@@ -210,9 +294,23 @@ impl WeatherForecastCache {
     pub const fn new() -> Self {
         Self {
             forecasts: alloc::vec::Vec::new(),
+            nowcast: alloc::vec::Vec::new(),
         }
     }
 
+    /// Replaces the `minutely_15` precipitation samples.
+    pub fn set_nowcast(&mut self, samples: alloc::vec::Vec<(Zoned, f32)>) {
+        self.nowcast = samples;
+    }
+
+    pub fn nowcast_samples(&self) -> &[(Zoned, f32)] {
+        &self.nowcast
+    }
+
+    pub fn nowcast(&self, now: &Zoned) -> Nowcast {
+        nowcast(now, &self.nowcast)
+    }
+
     pub fn last_forecast(&self) -> Option<&WeatherForecast> {
         self.forecasts.iter().max()
     }
@@ -243,6 +341,7 @@ impl WeatherForecastCache {
             .find(|forecast| forecast.is_during(timestamp))
     }
 
+    #[cfg(target_os = "none")]
     pub async fn get_current_forecast(&self) -> Option<&WeatherForecast> {
         self.get_forecast(&zgettimeofday().await)
     }
@@ -278,6 +377,7 @@ impl WeatherForecastCache {
         self.add(forecast);
     }
 
+    #[cfg(target_os = "none")]
     pub async fn expire(&mut self) {
         let timestamp = zgettimeofday().await;
         self.remove_before(&timestamp);
@@ -368,10 +468,212 @@ impl WeatherForecastCache {
     //         .collect()
     // }
 
-    // pub fn tomorrow(&self, timestamp: &Zoned) -> alloc::vec::Vec<&WeatherForecast> {
-    //     self.between(
-    //         &timestamp.tomorrow().unwrap().start_of_day().unwrap(),
-    //         &timestamp.tomorrow().unwrap().end_of_day().unwrap(),
-    //     )
-    // }
+    pub fn tomorrow(&self, timestamp: &Zoned) -> alloc::vec::Vec<&WeatherForecast> {
+        match timestamp.tomorrow() {
+            Ok(tomorrow) => self.today(&tomorrow),
+            Err(_) => alloc::vec::Vec::new(),
+        }
+    }
+
+    /// Aggregates the hourly forecasts falling on `date`.
+    pub fn day(&self, date: Date) -> Option<DailySummary> {
+        DailySummary::from_hours(
+            date,
+            self.forecasts
+                .iter()
+                .filter(|forecast| forecast.timespan.date() == date),
+        )
+    }
+
+    /// Up to `count` consecutive days starting with the day of `timestamp`;
+    /// stops at the first day without any hourly forecasts.
+    pub fn days(&self, timestamp: &Zoned, count: usize) -> alloc::vec::Vec<DailySummary> {
+        timestamp
+            .date()
+            .series(1.day())
+            .take(count)
+            .map_while(|date| self.day(date))
+            .collect()
+    }
+}
+
+/// One day's worth of hourly forecasts boiled down for the multi-day page.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DailySummary {
+    pub date: Date,
+    pub high: f32,
+    pub low: f32,
+    pub weather_code: WMOCode,
+    pub precipitation_probability: u8,
+}
+
+impl DailySummary {
+    pub fn from_hours<'a>(
+        date: Date,
+        hours: impl Iterator<Item = &'a WeatherForecast> + Clone,
+    ) -> Option<Self> {
+        let high = hours.clone().map(|x| x.temperature).reduce(f32::max)?;
+        let low = hours.clone().map(|x| x.temperature).reduce(f32::min)?;
+        let precipitation_probability = hours.clone().map(|x| x.precipitation_probability).max()?;
+        // the daytime sky is what people mean by "the weather" for a day
+        let weather_code =
+            dominant_weather_code(hours.clone().filter(|x| x.is_day).map(|x| x.weather_code))
+                .or_else(|| dominant_weather_code(hours.map(|x| x.weather_code)))?;
+        Some(Self {
+            date,
+            high,
+            low,
+            weather_code,
+            precipitation_probability,
+        })
+    }
+}
+
+/// The most frequent code; ties go to the more severe (higher) code.
+pub fn dominant_weather_code(codes: impl Iterator<Item = WMOCode>) -> Option<WMOCode> {
+    let mut counts: alloc::vec::Vec<(WMOCode, usize)> = alloc::vec::Vec::new();
+    for code in codes {
+        match counts.iter_mut().find(|(seen, _)| *seen == code) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((code, 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(code, count)| (*count, *code as u8))
+        .map(|(code, _)| code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::{civil::date, tz::TimeZone};
+
+    fn at(hour: i8, minute: i8) -> Zoned {
+        date(2025, 3, 14)
+            .at(hour, minute, 0, 0)
+            .to_zoned(TimeZone::UTC)
+            .unwrap()
+    }
+
+    fn series(start_hour: i8, precipitation: &[f32]) -> alloc::vec::Vec<(Zoned, f32)> {
+        precipitation
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (at(start_hour, 0) + ((i as i64 + 1) * 15).minutes(), *p))
+            .collect()
+    }
+
+    #[test]
+    fn nowcast_without_samples_is_unknown() {
+        assert_eq!(nowcast(&at(12, 0), &[]), Nowcast::Unknown);
+        assert_eq!(
+            nowcast(&at(18, 0), &series(12, &[0.1; 8])),
+            Nowcast::Unknown
+        );
+    }
+
+    #[test]
+    fn nowcast_dry_and_raining() {
+        assert_eq!(nowcast(&at(12, 0), &series(12, &[0.0; 12])), Nowcast::Dry);
+        assert_eq!(
+            nowcast(&at(12, 0), &series(12, &[0.02; 12])),
+            Nowcast::Raining
+        );
+    }
+
+    #[test]
+    fn nowcast_rain_starts() {
+        let samples = series(12, &[0.0, 0.0, 0.0, 0.01, 0.02, 0.0, 0.0, 0.0]);
+        assert_eq!(nowcast(&at(12, 0), &samples), Nowcast::StartsIn(45));
+        assert_eq!(nowcast(&at(12, 10), &samples), Nowcast::StartsIn(35));
+        assert_eq!(nowcast(&at(12, 50), &samples), Nowcast::StopsIn(25));
+    }
+
+    #[test]
+    fn nowcast_ignores_changes_past_horizon() {
+        let mut precipitation = [0.0; 12];
+        precipitation[9] = 0.05;
+        assert_eq!(
+            nowcast(&at(12, 0), &series(12, &precipitation)),
+            Nowcast::Dry
+        );
+        assert_eq!(
+            nowcast(&at(12, 20), &series(12, &precipitation)),
+            Nowcast::StartsIn(115)
+        );
+    }
+
+    #[test]
+    fn nowcast_unsorted_samples() {
+        let mut samples = series(12, &[0.0, 0.0, 0.03, 0.03]);
+        samples.reverse();
+        assert_eq!(nowcast(&at(12, 0), &samples), Nowcast::StartsIn(30));
+    }
+
+    fn hour(hour: i8, temperature: f32, code: WMOCode, is_day: bool, pp: u8) -> WeatherForecast {
+        WeatherForecast {
+            timespan: at(hour, 0),
+            temperature,
+            weather_code: code,
+            is_day,
+            precipitation_probability: pp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dominant_code_prefers_frequency_then_severity() {
+        use WMOCode::*;
+        assert_eq!(dominant_weather_code([].into_iter()), None);
+        assert_eq!(
+            dominant_weather_code([ClearSky, Overcast, ClearSky].into_iter()),
+            Some(ClearSky)
+        );
+        assert_eq!(
+            dominant_weather_code([ClearSky, SlightRain, Overcast, SlightRain].into_iter()),
+            Some(SlightRain)
+        );
+        assert_eq!(
+            dominant_weather_code([ClearSky, LightThunderstorm].into_iter()),
+            Some(LightThunderstorm)
+        );
+    }
+
+    #[test]
+    fn daily_summary_aggregates_hours() {
+        let hours = [
+            hour(3, 48.0, WMOCode::LightSnow, false, 5),
+            hour(9, 55.0, WMOCode::Overcast, true, 20),
+            hour(13, 63.5, WMOCode::Overcast, true, 40),
+            hour(16, 61.0, WMOCode::SlightRain, true, 70),
+            hour(22, 50.0, WMOCode::LightSnow, false, 10),
+        ];
+        let date = date(2025, 3, 14);
+        let summary = DailySummary::from_hours(date, hours.iter()).unwrap();
+        assert_eq!(summary.date, date);
+        assert_eq!(summary.high, 63.5);
+        assert_eq!(summary.low, 48.0);
+        assert_eq!(summary.weather_code, WMOCode::Overcast);
+        assert_eq!(summary.precipitation_probability, 70);
+    }
+
+    #[test]
+    fn daily_summary_night_only_falls_back_to_all_hours() {
+        let hours = [
+            hour(1, 40.0, WMOCode::Fog, false, 0),
+            hour(2, 39.0, WMOCode::Fog, false, 0),
+        ];
+        let summary = DailySummary::from_hours(date(2025, 3, 14), hours.iter()).unwrap();
+        assert_eq!(summary.weather_code, WMOCode::Fog);
+        assert!(DailySummary::from_hours(date(2025, 3, 14), [].iter()).is_none());
+    }
+
+    #[test]
+    fn nowcast_below_threshold_is_dry() {
+        assert_eq!(
+            nowcast(&at(12, 0), &series(12, &[0.001, 0.002, 0.0, 0.003])),
+            Nowcast::Dry
+        );
+    }
 }
@@ -0,0 +1,159 @@
+//! GPS as a time source, for panels out of Wi-Fi range: NMEA from a receiver
+//! on UART1 and, with the "gps-pps" feature, its PPS output on a GPIO. See
+//! [`crate::nmea`] for what's read and how far it's trusted.
+
+use crate::RTCREF;
+use crate::clockfilter::{Correction, Discipline};
+use crate::log::{debug, info, warn};
+use crate::nmea::{self, Lines};
+use crate::ntp::{STEPPED_ELSEWHERE, TIME_SYNCED};
+use crate::timesource::{self, Source};
+#[cfg(feature = "rtcchip")]
+use alloc::string::ToString;
+use core::sync::atomic::Ordering;
+#[cfg(feature = "gps-pps")]
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::Instant;
+#[cfg(feature = "gps-pps")]
+use esp_hal::gpio::{Input, InputConfig, Pull};
+use esp_hal::peripherals::*;
+use esp_hal::uart::{Config, UartRx};
+
+#[cfg(feature = "tidbyt")]
+compile_error!("the Tidbyt has no free pins for a GPS receiver");
+#[cfg(all(feature = "esp32", not(feature = "tidbyt")))]
+compile_error!("no GPS receiver pins are set for the ESP32; add them to uart() and pps()");
+
+const BAUDRATE: u32 = 9600;
+/// Fixes between samples; the discipline slews and corrects drift in between.
+const GPS_INTERVAL: u32 = 16;
+
+/// Our clock at the latest PPS edge.
+#[cfg(feature = "gps-pps")]
+static PULSE: Signal<CriticalSectionRawMutex, i64> = Signal::new();
+
+fn uart() -> UartRx<'static, esp_hal::Async> {
+    let config = Config::default().with_baudrate(BAUDRATE);
+    #[cfg(feature = "esp32c6")]
+    return UartRx::new(unsafe { UART1::steal() }, config)
+        .expect("couldn't init UART")
+        .with_rx(unsafe { GPIO4::steal() })
+        .into_async();
+
+    #[cfg(feature = "esp32s3")]
+    return UartRx::new(unsafe { UART1::steal() }, config)
+        .expect("couldn't init UART")
+        .with_rx(unsafe { GPIO10::steal() })
+        .into_async();
+}
+
+#[cfg(feature = "gps-pps")]
+fn pps() -> Input<'static> {
+    let config = InputConfig::default().with_pull(Pull::Down);
+    #[cfg(feature = "esp32c6")]
+    return Input::new(unsafe { GPIO5::steal() }, config);
+
+    #[cfg(feature = "esp32s3")]
+    return Input::new(unsafe { GPIO11::steal() }, config);
+}
+
+/// Timestamp PPS edges; spawn on the high-priority executor so little gets
+/// between the edge and reading the clock.
+#[cfg(feature = "gps-pps")]
+#[embassy_executor::task]
+pub async fn pps_edges() {
+    let rtc = &**RTCREF.get().await;
+    let mut pps = pps();
+    loop {
+        pps.wait_for_rising_edge().await;
+        PULSE.signal(rtc.current_time_us() as i64);
+    }
+}
+
+#[embassy_executor::task]
+pub async fn gps_sync() {
+    debug!("gps_sync started");
+    let rtc = &**RTCREF.get().await;
+    let mut uart = uart();
+    let mut lines = Lines::new();
+    let mut discipline = Discipline::default();
+    let mut buf = [0; 32];
+    let mut fixes: u32 = 0;
+    let mut last = Instant::now();
+    loop {
+        let read = match uart.read_async(&mut buf).await {
+            Ok(read) => read,
+            Err(e) => {
+                warn!("gps_sync: UART: {:?}", e);
+                continue;
+            }
+        };
+        let received = rtc.current_time_us() as i64;
+        for byte in &buf[..read] {
+            let Some(line) = lines.push(*byte) else {
+                continue;
+            };
+            let fix = match nmea::parse(line) {
+                Ok(fix) if fix.valid => fix,
+                Ok(_) => {
+                    debug!("gps_sync: no fix yet");
+                    continue;
+                }
+                Err(nmea::Error::Unsupported) => continue,
+                Err(e) => {
+                    debug!("gps_sync: {:?}", e);
+                    continue;
+                }
+            };
+            #[cfg(feature = "gps-pps")]
+            let pulse = PULSE.try_take();
+            #[cfg(not(feature = "gps-pps"))]
+            let pulse = None;
+
+            let now = Instant::now();
+            let (offset, error) = nmea::offset(&fix, received, pulse);
+            // every sentence in a second carries the time, but only the first
+            // after the edge gets the pulse; with a PPS line, the rest would
+            // feed the filter offsets 250 times as rough
+            #[cfg(feature = "gps-pps")]
+            if error != timesource::GPS_PPS_ERROR {
+                debug!("gps_sync: no PPS edge for this sentence");
+                continue;
+            }
+            let uptime = rtc.time_since_boot().as_secs();
+            if !timesource::should_apply(Source::Gps, error, uptime) {
+                // leave the clock to whichever source is better
+                discipline.forget();
+                (fixes, last) = (0, now);
+                continue;
+            }
+
+            // a fix a second keeps the slew and drift correction going
+            let adjust = discipline.tick((now - last).as_micros());
+            last = now;
+            if adjust != 0 {
+                rtc.set_current_time_us(rtc.current_time_us().saturating_add_signed(adjust));
+            }
+            fixes = fixes.wrapping_add(1);
+            if fixes % GPS_INTERVAL != 1 {
+                continue;
+            }
+            // the offset was measured before that adjustment
+            match discipline.update(offset - adjust, now.as_micros()) {
+                Correction::Step(offset) => {
+                    info!("gps_sync: stepping {}us", offset);
+                    rtc.set_current_time_us(rtc.current_time_us().saturating_add_signed(offset));
+                    STEPPED_ELSEWHERE.store(true, Ordering::Relaxed);
+                }
+                Correction::Slew(offset) => debug!("gps_sync: slewing {}us", offset),
+            }
+            // the receiver is the reference clock
+            timesource::record(Source::Gps, uptime, error, Some(0), None);
+            #[cfg(feature = "rtcchip")]
+            if let Err(e) = crate::rtc::ntp_synced(discipline.pending()).await {
+                warn!("gps_sync: RTC chip: {}", e.to_string());
+            }
+            TIME_SYNCED.store(true, Ordering::Relaxed);
+        }
+    }
+}
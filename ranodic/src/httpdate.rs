@@ -0,0 +1,110 @@
+//! The `Date:` header of HTTP responses as a last-resort time source, for
+//! networks that firewall UDP/123.
+//!
+//! It only has one-second resolution and says nothing about how long the
+//! response took, so it ranks below NTP and below an RTC chip that NTP set
+//! (see [`crate::timesource`]): while a better source is fresh it's ignored,
+//! and otherwise it only steps a clock that is off by more than
+//! [`TOLERANCE`]. A DS3231 that's merely a second out keeps its time; one
+//! that lost power and woke up in 2000 gets corrected.
+
+use jiff::{SignedDuration, Timestamp, fmt::rfc2822::DateTimeParser};
+
+/// How far the clock may disagree with a `Date:` header before it's stepped.
+pub const TOLERANCE: SignedDuration = SignedDuration::from_secs(2);
+
+static PARSER: DateTimeParser = DateTimeParser::new();
+
+/// Parse an RFC 9110 IMF-fixdate (`Sun, 06 Nov 1994 08:49:37 GMT`).
+pub fn parse(header: &str) -> Option<Timestamp> {
+    PARSER.parse_timestamp(header.trim()).ok()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Verdict {
+    /// A better source is fresh; the header has nothing to add.
+    Ignore,
+    /// The clock agrees to within [`TOLERANCE`].
+    Confirm,
+    /// Step the clock by this many µs.
+    Step(i64),
+}
+
+/// Compare a `Date:` header with our clock when the response arrived.
+pub fn judge(date: Timestamp, clock: Timestamp, outranked: bool) -> Verdict {
+    if outranked {
+        return Verdict::Ignore;
+    }
+    // the header is truncated to the second, so its middle is half a second on
+    let date = date + SignedDuration::from_millis(500);
+    let offset = date.duration_since(clock);
+    if offset.abs() <= TOLERANCE {
+        Verdict::Confirm
+    } else {
+        Verdict::Step(offset.as_micros() as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_imf_fixdate() {
+        assert_eq!(
+            parse("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(ts("1994-11-06T08:49:37Z"))
+        );
+        assert_eq!(
+            parse(" Fri, 14 Mar 2025 17:03:09 GMT\r"),
+            Some(ts("2025-03-14T17:03:09Z"))
+        );
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("yesterday"), None);
+        assert_eq!(parse("Fri, 32 Mar 2025 17:03:09 GMT"), None);
+    }
+
+    #[test]
+    fn better_source_wins() {
+        let date = ts("2025-03-14T17:03:09Z");
+        assert_eq!(
+            judge(date, ts("2000-01-01T00:00:00Z"), true),
+            Verdict::Ignore
+        );
+    }
+
+    #[test]
+    fn close_enough_is_confirmed() {
+        let date = ts("2025-03-14T17:03:09Z");
+        assert_eq!(
+            judge(date, ts("2025-03-14T17:03:10.900Z"), false),
+            Verdict::Confirm
+        );
+        assert_eq!(
+            judge(date, ts("2025-03-14T17:03:08Z"), false),
+            Verdict::Confirm
+        );
+    }
+
+    #[test]
+    fn far_off_clock_is_stepped_to_mid_second() {
+        let date = ts("2025-03-14T17:03:09Z");
+        assert_eq!(
+            judge(date, ts("2025-03-14T17:03:00Z"), false),
+            Verdict::Step(9_500_000)
+        );
+        assert_eq!(
+            judge(date, ts("2025-03-14T17:03:19.5Z"), false),
+            Verdict::Step(-10_000_000)
+        );
+    }
+}
@@ -1,5 +1,8 @@
-use crate::log::info;
+use core::sync::atomic::Ordering;
 
+use crate::log::{error, info};
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal, watch::Receiver};
 use embassy_time::Timer;
 #[cfg(feature = "esp32")]
 use esp_hal::i2s::AnyI2s;
@@ -12,7 +15,17 @@ use esp_hub75::{
     framebuffer::{compute_frame_count, compute_rows, plain::DmaFrameBuffer},
 };
 
-use crate::drawing::{FB_PAINT, FB_XMIT};
+use static_cell::StaticCell;
+
+use crate::{
+    agenda::AGENDA,
+    airquality::AIR_QUALITY,
+    drawing::{LOGO, draw_logo},
+    nightscout::{BGDATA, BgReading},
+    ntp::{TIME_SYNCED, zgettimeofday},
+    weather::{FORECASTS, FORECASTS_PRESENT},
+    widget::Context,
+};
 
 const ROWS: usize = 32;
 const COLS: usize = 64;
@@ -26,6 +39,108 @@ const FRAME_COUNT: usize = compute_frame_count(BRIGHTNESS_BITS);
 pub type FBType = DmaFrameBuffer<ROWS, COLS, NROWS, BRIGHTNESS_BITS, FRAME_COUNT>;
 pub type Hub75Type<'d> = Hub75<'d, esp_hal::Async>;
 
+pub type FrameBufferExchange = Signal<CriticalSectionRawMutex, &'static mut FBType>;
+
+// the painter sends the last painted frame down this signal
+pub static FB_XMIT: FrameBufferExchange = FrameBufferExchange::new();
+// the xmitter sends the obsolete fb back
+pub static FB_PAINT: FrameBufferExchange = FrameBufferExchange::new();
+
+pub static FB0: StaticCell<FBType> = StaticCell::new();
+pub static FB1: StaticCell<FBType> = StaticCell::new();
+
+async fn past_logo(bgrecvr: &Receiver<'_, CriticalSectionRawMutex, BgReading, 2>) -> bool {
+    // soft internet invariant
+    TIME_SYNCED.load(Ordering::Relaxed)
+        || bgrecvr.contains_value()
+        || FORECASTS_PRESENT.load(Ordering::Relaxed)
+}
+
+#[embassy_executor::task]
+pub async fn display_painter(fb_inc: &'static mut FBType) {
+    info!("display painter started");
+    let mut bgrecvr = BGDATA.receiver().expect("couldn't get BGDATA recvr");
+    let mut fb = fb_inc;
+    let world_zones = crate::worldclock::get();
+    let pages = crate::widget::pages::<FBType>();
+    let mac_address = crate::MAC_ADDRESS.get().await;
+    let mac_str = alloc::format!(
+        "{:02x}{:02x}{:02x}",
+        mac_address[3],
+        mac_address[4],
+        mac_address[5]
+    );
+    fb.erase();
+    'logo: loop {
+        for frame in LOGO.get().frames() {
+            draw_logo(&frame, &mac_str, env!("CARGO_PKG_VERSION"), fb)
+                .expect("failed to draw the logo");
+            FB_XMIT.signal(fb);
+            fb = FB_PAINT.wait().await;
+            fb.erase();
+            if past_logo(&bgrecvr).await {
+                break 'logo;
+            }
+            Timer::after_millis(frame.delay_centis as u64 * 10).await;
+        }
+    }
+    // bug: sometimes it forgets
+    let mut was_time_ever_synced = false;
+    loop {
+        was_time_ever_synced = was_time_ever_synced || TIME_SYNCED.load(Ordering::Relaxed);
+        if crate::GRACEFUL_SHUTDOWN.load(Ordering::Relaxed) {
+            info!("blanking buffers");
+            fb.erase();
+            FB_XMIT.signal(fb);
+            fb = FB_PAINT.wait().await;
+            fb.erase();
+            break;
+        }
+        let now = zgettimeofday().await;
+        let bgreading = if bgrecvr.contains_value() {
+            Some(bgrecvr.get().await)
+        } else {
+            None
+        };
+        let uptime = crate::RTCREF.get().await.time_since_boot().as_secs();
+        {
+            let forecasts = FORECASTS.lock().await;
+            let air_quality = AIR_QUALITY.lock().await;
+            let agenda = AGENDA.lock().await;
+            let format = crate::clockformat::get();
+            let timers = crate::timers::get(now.timestamp());
+            let ctx = Context {
+                now: &now,
+                synced: was_time_ever_synced,
+                quality: crate::timesource::quality(uptime),
+                format: &format,
+                locale: crate::locale::get(),
+                bg: bgreading.as_ref(),
+                forecasts: &forecasts,
+                air_quality: &air_quality,
+                agenda: &agenda,
+                world: &world_zones,
+                timers: &timers,
+                alarm: crate::alarms::ringing_now(&now),
+            };
+            match crate::widget::current(&pages, &ctx) {
+                Some(page) => {
+                    if page.draw(&ctx, fb).is_err() {
+                        error!("failed to draw the {} page", page.name);
+                    }
+                }
+                None => error!("no page to show"),
+            }
+        }
+
+        FB_XMIT.signal(fb);
+        fb = FB_PAINT.wait().await;
+        fb.erase();
+        Timer::after_millis(50).await;
+    }
+    info!("display_painter: terminating");
+}
+
 pub struct DisplayPeripherals<'d> {
     #[cfg(feature = "esp32")]
     i2s: AnyI2s<'d>,
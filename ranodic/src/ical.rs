@@ -0,0 +1,913 @@
+//! Just enough iCalendar (RFC 5545) to know what's next: the VEVENTs of a
+//! calendar export, with their `DTSTART`/`DTEND`/`DURATION`, `TZID`s from
+//! [`crate::timezone::IANA_ZONES`], all-day dates, `EXDATE`s, moved or
+//! cancelled instances (`RECURRENCE-ID`), and simple `RRULE`s:
+//!
+//! - `FREQ=DAILY`, `WEEKLY` (with `BYDAY=MO,WE,...`), `MONTHLY` (on a day of
+//!   the month, or with `BYDAY=2TU` / `-1FR`) or `YEARLY`
+//! - `INTERVAL`, `COUNT` and `UNTIL`
+//!
+//! A rule with anything else in it is shown only on its first date.
+//!
+//! The [`Parser`] is fed one line at a time and never holds more than one
+//! event, [`MAX_KEPT`] occurrences and [`MAX_OVERRIDES`] moved instances, so
+//! memory doesn't grow with the size of the calendar. Only occurrences
+//! between `now` and the horizon are kept.
+
+use alloc::{string::String, vec::Vec};
+use anyhow::{Result, anyhow};
+use jiff::{
+    SignedDuration, Timestamp, ToSpan,
+    civil::{Date, DateTime, Time, Weekday},
+    tz::TimeZone,
+};
+
+/// Longest content line, unfolded; the rest is dropped.
+pub const MAX_LINE: usize = 512;
+/// Longest summary kept, in characters.
+pub const MAX_SUMMARY: usize = 48;
+/// Most `EXDATE`s kept per event.
+pub const MAX_EXDATES: usize = 32;
+/// Most moved or cancelled instances remembered.
+pub const MAX_OVERRIDES: usize = 64;
+/// Most occurrences returned.
+pub const MAX_EVENTS: usize = 16;
+/// Occurrences held while parsing; more than [`MAX_EVENTS`] so that ones
+/// moved by a later `RECURRENCE-ID` don't crowd out the rest.
+pub const MAX_KEPT: usize = 2 * MAX_EVENTS;
+// rule periods walked per event, so a rule can't keep us busy forever
+const MAX_PERIODS: i64 = 4096;
+
+/// One occurrence of an event.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Event {
+    pub summary: String,
+    pub start: Timestamp,
+    pub end: Timestamp,
+    pub all_day: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Freq {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Rule {
+    freq: Freq,
+    interval: i64,
+    count: Option<i64>,
+    until: Option<Timestamp>,
+    /// `BYDAY` without ordinals, Monday the lowest bit.
+    days: u8,
+    /// `BYDAY` with one, e.g. `-1FR`.
+    nth: Option<(i8, Weekday)>,
+    month_day: Option<i8>,
+    month: Option<i8>,
+}
+
+impl Rule {
+    /// `None` for a rule we can't expand.
+    fn parse(value: &str, tz: &TimeZone) -> Option<Self> {
+        let mut rule = Self {
+            freq: Freq::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            days: 0,
+            nth: None,
+            month_day: None,
+            month: None,
+        };
+        let mut freq = None;
+        for part in value.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part.split_once('=')?;
+            match key {
+                "FREQ" => {
+                    freq = Some(match value {
+                        "DAILY" => Freq::Daily,
+                        "WEEKLY" => Freq::Weekly,
+                        "MONTHLY" => Freq::Monthly,
+                        "YEARLY" => Freq::Yearly,
+                        _ => return None,
+                    })
+                }
+                "INTERVAL" => rule.interval = value.parse().ok().filter(|i| *i > 0)?,
+                "COUNT" => rule.count = Some(value.parse().ok()?),
+                "UNTIL" => rule.until = Some(when(value, true, tz)?.until()?),
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        let (nth, name) = day.split_at(day.len().checked_sub(2)?);
+                        let weekday = weekday(name)?;
+                        if nth.is_empty() {
+                            rule.days |= 1 << weekday.to_monday_zero_offset();
+                        } else if rule.nth.is_none() {
+                            rule.nth = Some((nth.parse().ok()?, weekday));
+                        } else {
+                            return None;
+                        }
+                    }
+                }
+                "BYMONTHDAY" => rule.month_day = Some(value.parse().ok()?),
+                "BYMONTH" => rule.month = Some(value.parse().ok()?),
+                // only matters for WEEKLY with INTERVAL and BYDAY; Monday it is
+                "WKST" => {}
+                _ => return None,
+            }
+        }
+        rule.freq = freq?;
+        let supported = match rule.freq {
+            Freq::Daily => rule.days == 0 && rule.nth.is_none() && rule.month_day.is_none(),
+            Freq::Weekly => rule.nth.is_none() && rule.month_day.is_none(),
+            Freq::Monthly | Freq::Yearly => {
+                rule.days == 0 && !(rule.nth.is_some() && rule.month_day.is_some())
+            }
+        };
+        (supported && (rule.month.is_none() || rule.freq == Freq::Yearly)).then_some(rule)
+    }
+
+    /// The dates of period `k` counting from `first`, in order; up to seven.
+    fn dates(&self, first: Date, k: i64) -> Vec<Date> {
+        let step = k * self.interval;
+        let mut dates = Vec::new();
+        match self.freq {
+            Freq::Daily => dates.extend(first.checked_add(step.days()).ok()),
+            Freq::Weekly => {
+                let monday = first
+                    .checked_sub((first.weekday().to_monday_zero_offset() as i64).days())
+                    .and_then(|monday| monday.checked_add((7 * step).days()));
+                let days = match self.days {
+                    0 => 1 << first.weekday().to_monday_zero_offset(),
+                    days => days,
+                };
+                if let Ok(monday) = monday {
+                    dates.extend(
+                        (0..7)
+                            .filter(|day| days & 1 << day != 0)
+                            .filter_map(|day| monday.checked_add((day as i64).days()).ok()),
+                    );
+                }
+            }
+            Freq::Monthly => {
+                let months = first.year() as i64 * 12 + first.month() as i64 - 1 + step;
+                dates.extend(self.in_month(first, months));
+            }
+            Freq::Yearly => {
+                let month = self.month.unwrap_or(first.month()) as i64;
+                let months = (first.year() as i64 + step) * 12 + month - 1;
+                dates.extend(self.in_month(first, months));
+            }
+        }
+        dates
+    }
+
+    // the date in month number `months` (years * 12 + month - 1); none if it
+    // doesn't have one, like the 31st of June
+    fn in_month(&self, first: Date, months: i64) -> Option<Date> {
+        let year = i16::try_from(months.div_euclid(12)).ok()?;
+        let month = (months.rem_euclid(12) + 1) as i8;
+        let start = Date::new(year, month, 1).ok()?;
+        if let Some((nth, weekday)) = self.nth {
+            return start.nth_weekday_of_month(nth, weekday).ok();
+        }
+        let day = self.month_day.unwrap_or(first.day());
+        let day = if day < 0 {
+            start.days_in_month() + 1 + day
+        } else {
+            day
+        };
+        Date::new(year, month, day).ok()
+    }
+
+    // periods to skip straight past when nothing is being counted: the one
+    // before the one `target` is in
+    fn periods_before(&self, first: Date, target: Date) -> i64 {
+        if self.count.is_some() || target <= first {
+            return 0;
+        }
+        let days = first.until(target).map_or(0, |span| span.get_days() as i64);
+        let months = (target.year() as i64 - first.year() as i64) * 12 + target.month() as i64
+            - first.month() as i64;
+        let periods = match self.freq {
+            Freq::Daily => days,
+            Freq::Weekly => days / 7,
+            Freq::Monthly => months,
+            Freq::Yearly => months / 12,
+        } / self.interval;
+        (periods - 1).max(0)
+    }
+}
+
+fn weekday(name: &str) -> Option<Weekday> {
+    Some(match name {
+        "MO" => Weekday::Monday,
+        "TU" => Weekday::Tuesday,
+        "WE" => Weekday::Wednesday,
+        "TH" => Weekday::Thursday,
+        "FR" => Weekday::Friday,
+        "SA" => Weekday::Saturday,
+        "SU" => Weekday::Sunday,
+        _ => return None,
+    })
+}
+
+/// A `DTSTART`-style value: civil time in a zone, or a date.
+#[derive(Clone, Debug)]
+struct When {
+    civil: DateTime,
+    tz: TimeZone,
+    all_day: bool,
+}
+
+impl When {
+    fn timestamp(&self) -> Option<Timestamp> {
+        Some(self.civil.to_zoned(self.tz.clone()).ok()?.timestamp())
+    }
+
+    // an UNTIL date includes the whole day
+    fn until(&self) -> Option<Timestamp> {
+        match self.all_day {
+            true => Some(self.timestamp()? + SignedDuration::from_hours(24)),
+            false => self.timestamp(),
+        }
+    }
+}
+
+// `20260318`, `20260318T090000` or `20260318T090000Z`; `tz` for the ones
+// without a Z
+fn when(value: &str, date_only: bool, tz: &TimeZone) -> Option<When> {
+    let value = value.trim();
+    let number = |range: core::ops::Range<usize>| value.get(range)?.parse::<i16>().ok();
+    let date = Date::new(number(0..4)?, number(4..6)? as i8, number(6..8)? as i8).ok()?;
+    if value.len() == 8 {
+        return Some(When {
+            civil: date.to_datetime(Time::midnight()),
+            tz: tz.clone(),
+            all_day: true,
+        });
+    }
+    if date_only || value.get(8..9)? != "T" {
+        return None;
+    }
+    let time = Time::new(
+        number(9..11)? as i8,
+        number(11..13)? as i8,
+        number(13..15)? as i8,
+        0,
+    )
+    .ok()?;
+    let tz = match &value[15..] {
+        "" => tz.clone(),
+        "Z" => TimeZone::UTC,
+        _ => return None,
+    };
+    Some(When {
+        civil: date.to_datetime(time),
+        tz,
+        all_day: false,
+    })
+}
+
+// `P1D`, `PT1H30M`, `P1W`: days and seconds
+fn duration(value: &str) -> Option<(i64, i64)> {
+    let value = value.trim();
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut value = value.strip_prefix('P')?;
+    let (mut days, mut seconds, mut in_time) = (0i64, 0i64, false);
+    while !value.is_empty() {
+        if let Some(rest) = value.strip_prefix('T') {
+            in_time = true;
+            value = rest;
+            continue;
+        }
+        let digits = value.find(|c: char| !c.is_ascii_digit())?;
+        let number: i64 = value[..digits].parse().ok()?;
+        match (value.as_bytes()[digits], in_time) {
+            (b'W', false) => days += 7 * number,
+            (b'D', false) => days += number,
+            (b'H', true) => seconds += 3600 * number,
+            (b'M', true) => seconds += 60 * number,
+            (b'S', true) => seconds += number,
+            _ => return None,
+        }
+        value = &value[digits + 1..];
+    }
+    Some(if negative {
+        (-days, -seconds)
+    } else {
+        (days, seconds)
+    })
+}
+
+/// A content line split up: `NAME;PARAMS:VALUE`.
+struct Property<'a> {
+    name: &'a str,
+    params: &'a str,
+    value: &'a str,
+}
+
+impl<'a> Property<'a> {
+    fn split(line: &'a str) -> Option<Self> {
+        let name_end = line.find([';', ':'])?;
+        // the value starts at the first colon that isn't in a quoted parameter
+        let mut quoted = false;
+        let colon = line[name_end..]
+            .char_indices()
+            .find_map(|(idx, c)| match c {
+                '"' => {
+                    quoted = !quoted;
+                    None
+                }
+                ':' if !quoted => Some(name_end + idx),
+                _ => None,
+            })?;
+        Some(Self {
+            name: &line[..name_end],
+            params: &line[name_end..colon],
+            value: &line[colon + 1..],
+        })
+    }
+
+    fn param(&self, key: &str) -> Option<&'a str> {
+        self.params.split(';').find_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            name.eq_ignore_ascii_case(key)
+                .then(|| value.trim_matches('"'))
+        })
+    }
+
+    fn is(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+
+    /// The zone of a date-time property: its `TZID` if we know it, or `local`.
+    fn tz(&self, local: &TimeZone) -> TimeZone {
+        self.param("TZID")
+            .and_then(tzid)
+            .unwrap_or_else(|| local.clone())
+    }
+
+    fn when(&self, local: &TimeZone) -> Option<When> {
+        let date_only = self
+            .param("VALUE")
+            .is_some_and(|value| value.eq_ignore_ascii_case("DATE"));
+        when(self.value, date_only, &self.tz(local))
+    }
+}
+
+// IANA names, some with a vendor prefix like `/mozilla.org/20070129_1/`
+fn tzid(name: &str) -> Option<TimeZone> {
+    let mut name = name;
+    loop {
+        if let Ok(tz) = crate::timezone::parse(name) {
+            return Some(tz);
+        }
+        name = &name[name.find('/')? + 1..];
+    }
+}
+
+// TEXT values on one line, in what the Latin-1 fonts can draw
+fn text(value: &str, into: &mut String) {
+    let mut chars = value.chars();
+    let mut len = 0;
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => ' ',
+                Some(escaped) => escaped,
+                None => break,
+            },
+            c if (c as u32) < 0x100 => c,
+            _ => '?',
+        };
+        if len == MAX_SUMMARY {
+            break;
+        }
+        into.push(c);
+        len += 1;
+    }
+}
+
+fn uid_hash(uid: &str) -> u64 {
+    uid.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The event being read.
+#[derive(Default)]
+struct Pending {
+    summary: String,
+    start: Option<When>,
+    end: Option<When>,
+    duration: Option<(i64, i64)>,
+    /// `Some(None)` for a rule we can't expand.
+    rule: Option<Option<Rule>>,
+    exdates: Vec<Timestamp>,
+    recurrence_id: Option<Timestamp>,
+    uid: u64,
+    cancelled: bool,
+}
+
+/// Reads a calendar a line at a time; see the module docs.
+pub struct Parser {
+    now: Timestamp,
+    horizon: Timestamp,
+    local: TimeZone,
+    line: String,
+    /// The start of a physical line from [`Parser::feed_bytes`], waiting
+    /// for the rest of it.
+    partial: Vec<u8>,
+    calendar: bool,
+    /// Inside a VEVENT, and how many components deep inside that (VALARM).
+    event: Option<Pending>,
+    nested: u32,
+    /// Occurrences so far, soonest first, with the UID they came from.
+    kept: Vec<(u64, Event)>,
+    /// `(UID, original start)` of moved or cancelled instances.
+    overrides: Vec<(u64, Timestamp)>,
+}
+
+impl Parser {
+    /// Keep what's on between `now` and `now + horizon`; floating times and
+    /// dates are in `local`.
+    pub fn new(now: Timestamp, horizon: SignedDuration, local: TimeZone) -> Self {
+        Self {
+            now,
+            horizon: now.checked_add(horizon).unwrap_or(Timestamp::MAX),
+            local,
+            line: String::with_capacity(MAX_LINE),
+            partial: Vec::new(),
+            calendar: false,
+            event: None,
+            nested: 0,
+            kept: Vec::new(),
+            overrides: Vec::new(),
+        }
+    }
+
+    /// One physical line, with or without its line ending.
+    pub fn feed(&mut self, line: &str) {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if let Some(folded) = line.strip_prefix([' ', '\t']) {
+            self.extend(folded);
+            return;
+        }
+        self.flush();
+        self.extend(line);
+    }
+
+    /// Whatever came off the wire next; lines may be split anywhere between
+    /// calls. Bytes past [`MAX_LINE`] on a line are dropped, as in
+    /// [`Parser::feed`].
+    pub fn feed_bytes(&mut self, mut bytes: &[u8]) {
+        while let Some(eol) = bytes.iter().position(|b| *b == b'\n') {
+            self.take_partial(&bytes[..eol]);
+            let line = core::mem::take(&mut self.partial);
+            self.feed_utf8(&line);
+            self.partial = line;
+            self.partial.clear();
+            bytes = &bytes[eol + 1..];
+        }
+        self.take_partial(bytes);
+    }
+
+    fn take_partial(&mut self, bytes: &[u8]) {
+        let room = MAX_LINE.saturating_sub(self.partial.len());
+        self.partial
+            .extend_from_slice(&bytes[..bytes.len().min(room)]);
+    }
+
+    /// A line cut short may end part way into a character; that much of it
+    /// is dropped.
+    fn feed_utf8(&mut self, line: &[u8]) {
+        let line = match core::str::from_utf8(line) {
+            Ok(line) => line,
+            Err(e) => core::str::from_utf8(&line[..e.valid_up_to()]).unwrap_or_default(),
+        };
+        self.feed(line);
+    }
+
+    /// The occurrences found, soonest first; an error if this wasn't a
+    /// calendar at all.
+    pub fn finish(mut self) -> Result<Vec<Event>> {
+        let partial = core::mem::take(&mut self.partial);
+        if !partial.is_empty() {
+            self.feed_utf8(&partial);
+        }
+        self.flush();
+        if !self.calendar {
+            return Err(anyhow!("not an iCalendar file"));
+        }
+        let overrides = self.overrides;
+        Ok(self
+            .kept
+            .into_iter()
+            .filter(|(uid, event)| !overrides.contains(&(*uid, event.start)))
+            .map(|(_, event)| event)
+            .take(MAX_EVENTS)
+            .collect())
+    }
+
+    fn extend(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.line.len() + c.len_utf8() > MAX_LINE {
+                break;
+            }
+            self.line.push(c);
+        }
+    }
+
+    fn flush(&mut self) {
+        let line = core::mem::take(&mut self.line);
+        if !line.is_empty() {
+            self.property(&line);
+        }
+        self.line = line;
+        self.line.clear();
+    }
+
+    fn property(&mut self, line: &str) {
+        let Some(property) = Property::split(line) else {
+            return;
+        };
+        if property.is("BEGIN") {
+            if property.value.eq_ignore_ascii_case("VCALENDAR") {
+                self.calendar = true;
+            } else if self.event.is_some() {
+                self.nested += 1;
+            } else if property.value.eq_ignore_ascii_case("VEVENT") {
+                self.event = Some(Pending::default());
+            }
+            return;
+        }
+        if property.is("END") && self.event.is_some() {
+            if self.nested > 0 {
+                self.nested -= 1;
+            } else if let Some(event) = self.event.take() {
+                self.finish_event(event);
+            }
+            return;
+        }
+        let local = self.local.clone();
+        let (Some(event), 0) = (&mut self.event, self.nested) else {
+            return;
+        };
+        match property.name {
+            name if name.eq_ignore_ascii_case("SUMMARY") => {
+                event.summary.clear();
+                text(property.value, &mut event.summary);
+            }
+            name if name.eq_ignore_ascii_case("DTSTART") => event.start = property.when(&local),
+            name if name.eq_ignore_ascii_case("DTEND") => event.end = property.when(&local),
+            name if name.eq_ignore_ascii_case("DURATION") => {
+                event.duration = duration(property.value)
+            }
+            name if name.eq_ignore_ascii_case("UID") => event.uid = uid_hash(property.value),
+            name if name.eq_ignore_ascii_case("STATUS") => {
+                event.cancelled = property.value.eq_ignore_ascii_case("CANCELLED")
+            }
+            name if name.eq_ignore_ascii_case("RRULE") => {
+                // DTSTART's zone is what UNTIL dates are in; it's usually
+                // seen first
+                let tz = event.start.as_ref().map_or(local, |start| start.tz.clone());
+                event.rule = Some(Rule::parse(property.value, &tz));
+            }
+            name if name.eq_ignore_ascii_case("EXDATE") => {
+                let tz = property.tz(&local);
+                let date_only = property.param("VALUE") == Some("DATE");
+                for value in property.value.split(',') {
+                    if event.exdates.len() == MAX_EXDATES {
+                        break;
+                    }
+                    if let Some(date) = when(value, date_only, &tz).and_then(|w| w.timestamp()) {
+                        event.exdates.push(date);
+                    }
+                }
+            }
+            name if name.eq_ignore_ascii_case("RECURRENCE-ID") => {
+                event.recurrence_id = property.when(&local).and_then(|w| w.timestamp())
+            }
+            _ => {}
+        }
+    }
+
+    fn finish_event(&mut self, event: Pending) {
+        if let Some(recurrence_id) = event.recurrence_id
+            && self.overrides.len() < MAX_OVERRIDES
+        {
+            self.overrides.push((event.uid, recurrence_id));
+        }
+        let Some(start) = &event.start else {
+            return;
+        };
+        if event.cancelled {
+            return;
+        }
+        // how long each occurrence lasts, as days then seconds, so all-day
+        // ones stay whole days across DST
+        let length = match (&event.end, event.duration) {
+            (Some(end), _) if start.all_day => (
+                start
+                    .civil
+                    .date()
+                    .until(end.civil.date())
+                    .map_or(1, |span| span.get_days() as i64),
+                0,
+            ),
+            (Some(end), _) => (
+                0,
+                end.timestamp()
+                    .zip(start.timestamp())
+                    .map_or(0, |(end, start)| end.duration_since(start).as_secs()),
+            ),
+            (None, Some(duration)) => duration,
+            (None, None) if start.all_day => (1, 0),
+            (None, None) => (0, 0),
+        };
+        // an instance moved elsewhere is its own event
+        let uid = match event.recurrence_id {
+            Some(_) => 0,
+            None => event.uid,
+        };
+        let rule = match event.rule {
+            Some(Some(rule)) if event.recurrence_id.is_none() => rule,
+            _ => {
+                self.occurrence(uid, &event, start.civil, length);
+                return;
+            }
+        };
+        let first = start.civil.date();
+        let earliest = self
+            .now
+            .checked_sub(SignedDuration::from_hours(24 * length.0.max(0) + 24))
+            .unwrap_or(self.now)
+            .checked_sub(SignedDuration::from_secs(length.1.max(0)))
+            .unwrap_or(self.now)
+            .to_zoned(start.tz.clone())
+            .date();
+        let skip = rule.periods_before(first, earliest);
+        let mut counted = 0;
+        for k in skip..skip + MAX_PERIODS {
+            for date in rule.dates(first, k) {
+                if date < first {
+                    continue;
+                }
+                let civil = date.to_datetime(start.civil.time());
+                let Ok(at) = civil.to_zoned(start.tz.clone()) else {
+                    continue;
+                };
+                let at = at.timestamp();
+                counted += 1;
+                if rule.count.is_some_and(|count| counted > count)
+                    || rule.until.is_some_and(|until| at > until)
+                    || at > self.horizon
+                {
+                    return;
+                }
+                if !event.exdates.contains(&at) {
+                    self.occurrence(uid, &event, civil, length);
+                }
+            }
+        }
+    }
+
+    fn occurrence(&mut self, uid: u64, event: &Pending, civil: DateTime, length: (i64, i64)) {
+        let Some(start) = &event.start else {
+            return;
+        };
+        let tz = start.tz.clone();
+        let Ok(begins) = civil.to_zoned(tz.clone()) else {
+            return;
+        };
+        let ends = civil
+            .date()
+            .checked_add(length.0.days())
+            .ok()
+            .and_then(|date| date.to_datetime(civil.time()).to_zoned(tz).ok())
+            .and_then(|ends| {
+                ends.timestamp()
+                    .checked_add(SignedDuration::from_secs(length.1))
+                    .ok()
+            });
+        let (start, end) = (begins.timestamp(), ends.unwrap_or(begins.timestamp()));
+        // a zero-length event is on at its start
+        if end.max(start + SignedDuration::from_secs(1)) <= self.now || start > self.horizon {
+            return;
+        }
+        let at = self.kept.partition_point(|(_, kept)| kept.start <= start);
+        if at == MAX_KEPT {
+            return;
+        }
+        self.kept.insert(
+            at,
+            (
+                uid,
+                Event {
+                    summary: event.summary.clone(),
+                    start,
+                    end,
+                    all_day: event.start.as_ref().is_some_and(|start| start.all_day),
+                },
+            ),
+        );
+        self.kept.truncate(MAX_KEPT);
+    }
+}
+
+/// [`Parser`] over a whole file.
+pub fn parse(
+    ics: &str,
+    now: Timestamp,
+    horizon: SignedDuration,
+    local: &TimeZone,
+) -> Result<Vec<Event>> {
+    let mut parser = Parser::new(now, horizon, local.clone());
+    for line in ics.lines() {
+        parser.feed(line);
+    }
+    parser.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOGLE: &str = include_str!("../fixtures/ics/google.ics");
+    const NEXTCLOUD: &str = include_str!("../fixtures/ics/nextcloud.ics");
+    const WEEK: SignedDuration = SignedDuration::from_hours(7 * 24);
+
+    fn ts(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    fn pacific() -> TimeZone {
+        TimeZone::posix("PST8PDT,M3.2.0,M11.1.0").unwrap()
+    }
+
+    fn berlin() -> TimeZone {
+        TimeZone::posix("CET-1CEST,M3.5.0,M10.5.0/3").unwrap()
+    }
+
+    fn summaries(events: &[Event]) -> Vec<&str> {
+        events.iter().map(|event| event.summary.as_str()).collect()
+    }
+
+    #[test]
+    fn google_export() {
+        // Monday 2026-03-16 08:00 PDT
+        let events = parse(GOOGLE, ts("2026-03-16T15:00:00Z"), 2 * WEEK, &pacific()).unwrap();
+        assert_eq!(
+            summaries(&events),
+            [
+                "Spring break",
+                "Standup",
+                "Dentist, Dr. Müller",
+                "Standup (moved)",
+                "Team lunch",
+                "Standup",
+                "Standup",
+                "Standup",
+            ]
+        );
+        // all day from the date, through the DTEND date
+        assert!(events[0].all_day);
+        assert_eq!(events[0].start, ts("2026-03-14T07:00:00Z"));
+        assert_eq!(events[0].end, ts("2026-03-21T07:00:00Z"));
+        // TZID'd weekly rule, kept at 09:30 local after the DST change
+        assert_eq!(events[1].start, ts("2026-03-16T16:30:00Z"));
+        assert_eq!(events[1].end, ts("2026-03-16T16:45:00Z"));
+        // UTC, with a folded summary
+        assert_eq!(events[2].start, ts("2026-03-16T21:00:00Z"));
+        // Wednesday's standup was moved to the afternoon and Friday's is an
+        // EXDATE
+        assert_eq!(events[3].start, ts("2026-03-18T21:00:00Z"));
+        assert_eq!(events[4].start, ts("2026-03-19T19:00:00Z"));
+        assert_eq!(events[5].start, ts("2026-03-23T16:30:00Z"));
+    }
+
+    #[test]
+    fn fed_in_pieces() {
+        // split mid-line, mid-CRLF and inside the ü of Müller
+        let now = ts("2026-03-16T15:00:00Z");
+        for size in [1, 7, 64, 1000] {
+            let mut parser = Parser::new(now, 2 * WEEK, pacific());
+            for piece in GOOGLE.as_bytes().chunks(size) {
+                parser.feed_bytes(piece);
+            }
+            assert_eq!(
+                parser.finish().unwrap(),
+                parse(GOOGLE, now, 2 * WEEK, &pacific()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn nextcloud_export() {
+        // Friday 2026-03-27 12:00 CET
+        let events = parse(NEXTCLOUD, ts("2026-03-27T11:00:00Z"), WEEK, &berlin()).unwrap();
+        assert_eq!(
+            summaries(&events),
+            [
+                "Yoga",
+                "Müll rausbringen",
+                "Elternabend",
+                "Yoga",
+                "Geburtstag Oma",
+            ]
+        );
+        // across the change to CEST on the 29th the class stays at 18:00
+        assert_eq!(events[0].start, ts("2026-03-27T17:00:00Z"));
+        assert_eq!(events[3].start, ts("2026-03-31T16:00:00Z"));
+        // the last Monday of the month, all day, from DURATION
+        assert!(events[1].all_day);
+        assert_eq!(events[1].start, ts("2026-03-29T22:00:00Z"));
+        assert_eq!(events[1].end, ts("2026-03-30T22:00:00Z"));
+        // yearly, all day
+        assert_eq!(events[4].start, ts("2026-04-01T22:00:00Z"));
+    }
+
+    #[test]
+    fn ongoing_events_are_kept() {
+        let events = parse(GOOGLE, ts("2026-03-16T16:40:00Z"), WEEK, &pacific()).unwrap();
+        assert_eq!(events[1].summary, "Standup");
+        assert_eq!(events[1].start, ts("2026-03-16T16:30:00Z"));
+    }
+
+    #[test]
+    fn counted_and_until_rules_end() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\nUID:a\r\nSUMMARY:Course\r\n\
+            DTSTART:20260302T180000Z\r\nDURATION:PT1H\r\n\
+            RRULE:FREQ=WEEKLY;COUNT=3\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:b\r\nSUMMARY:Pills\r\n\
+            DTSTART;VALUE=DATE:20260301\r\n\
+            RRULE:FREQ=DAILY;INTERVAL=2;UNTIL=20260319\r\nEND:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let events = parse(ics, ts("2026-03-14T00:00:00Z"), WEEK, &TimeZone::UTC).unwrap();
+        let starts: Vec<_> = events
+            .iter()
+            .map(|event| (event.summary.as_str(), event.start))
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                ("Pills", ts("2026-03-15T00:00:00Z")),
+                ("Course", ts("2026-03-16T18:00:00Z")),
+                ("Pills", ts("2026-03-17T00:00:00Z")),
+                ("Pills", ts("2026-03-19T00:00:00Z")),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_running_rules_skip_ahead() {
+        let ics = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:Daily\n\
+            DTSTART:19900101T120000Z\nRRULE:FREQ=DAILY\nEND:VEVENT\nEND:VCALENDAR\n";
+        let events = parse(ics, ts("2026-03-14T13:00:00Z"), WEEK, &TimeZone::UTC).unwrap();
+        assert_eq!(events.len(), 7);
+        assert_eq!(events[0].start, ts("2026-03-15T12:00:00Z"));
+    }
+
+    #[test]
+    fn unknown_rules_show_once() {
+        let ics = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:Odd\n\
+            DTSTART:20260315T120000Z\nRRULE:FREQ=MINUTELY\nEND:VEVENT\nEND:VCALENDAR\n";
+        let events = parse(ics, ts("2026-03-14T13:00:00Z"), WEEK, &TimeZone::UTC).unwrap();
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn memory_is_bounded() {
+        let mut ics = String::from("BEGIN:VCALENDAR\n");
+        for _ in 0..100 {
+            ics.push_str("BEGIN:VEVENT\nSUMMARY:");
+            ics.extend(core::iter::repeat_n('x', 2 * MAX_LINE));
+            ics.push_str("\nDTSTART:20260315T120000Z\nEND:VEVENT\n");
+        }
+        ics.push_str("END:VCALENDAR\n");
+        let events = parse(&ics, ts("2026-03-14T13:00:00Z"), WEEK, &TimeZone::UTC).unwrap();
+        assert_eq!(events.len(), MAX_EVENTS);
+        assert_eq!(events[0].summary.chars().count(), MAX_SUMMARY);
+    }
+
+    #[test]
+    fn rejects_what_isnt_a_calendar() {
+        assert!(parse("<html>", ts("2026-03-14T13:00:00Z"), WEEK, &TimeZone::UTC).is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(duration("PT1H30M"), Some((0, 5400)));
+        assert_eq!(duration("P1D"), Some((1, 0)));
+        assert_eq!(duration("P1W"), Some((7, 0)));
+        assert_eq!(duration("-PT15M"), Some((0, -900)));
+        assert_eq!(duration("P1H"), None);
+    }
+}
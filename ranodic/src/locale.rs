@@ -0,0 +1,402 @@
+//! Day and month names for the date line and the multi-day forecast. jiff's
+//! strftime only speaks English, so [`Locale::format`] puts the names in
+//! itself. Everything here is Latin-1, which is what the `iso_8859_1` fonts
+//! can draw; weekday abbreviations are at most three letters so the five-day
+//! forecast columns can hold them.
+
+use core::cell::RefCell;
+
+use alloc::string::String;
+use anyhow::{Result, anyhow};
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use jiff::Zoned;
+use jiff::civil::Weekday;
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Locale {
+    pub code: &'static str,
+    /// Monday first.
+    pub weekdays: [&'static str; 7],
+    pub weekdays_full: [&'static str; 7],
+    pub months: [&'static str; 12],
+    pub months_full: [&'static str; 12],
+    /// What `date=short` means here.
+    pub short_date: &'static str,
+}
+
+pub const ENGLISH: Locale = Locale {
+    code: "en",
+    weekdays: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    weekdays_full: [
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+        "Sunday",
+    ],
+    months: [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ],
+    months_full: [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+    short_date: "%a %b %d",
+};
+
+pub const GERMAN: Locale = Locale {
+    code: "de",
+    weekdays: ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+    weekdays_full: [
+        "Montag",
+        "Dienstag",
+        "Mittwoch",
+        "Donnerstag",
+        "Freitag",
+        "Samstag",
+        "Sonntag",
+    ],
+    months: [
+        "Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez",
+    ],
+    months_full: [
+        "Januar",
+        "Februar",
+        "März",
+        "April",
+        "Mai",
+        "Juni",
+        "Juli",
+        "August",
+        "September",
+        "Oktober",
+        "November",
+        "Dezember",
+    ],
+    short_date: "%a %-d. %b",
+};
+
+pub const SPANISH: Locale = Locale {
+    code: "es",
+    weekdays: ["Lun", "Mar", "Mié", "Jue", "Vie", "Sáb", "Dom"],
+    weekdays_full: [
+        "lunes",
+        "martes",
+        "miércoles",
+        "jueves",
+        "viernes",
+        "sábado",
+        "domingo",
+    ],
+    months: [
+        "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sep", "oct", "nov", "dic",
+    ],
+    months_full: [
+        "enero",
+        "febrero",
+        "marzo",
+        "abril",
+        "mayo",
+        "junio",
+        "julio",
+        "agosto",
+        "septiembre",
+        "octubre",
+        "noviembre",
+        "diciembre",
+    ],
+    short_date: "%a %-d %b",
+};
+
+pub const FRENCH: Locale = Locale {
+    code: "fr",
+    weekdays: ["Lun", "Mar", "Mer", "Jeu", "Ven", "Sam", "Dim"],
+    weekdays_full: [
+        "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+    ],
+    months: [
+        "jan", "fév", "mar", "avr", "mai", "jun", "jul", "aoû", "sep", "oct", "nov", "déc",
+    ],
+    months_full: [
+        "janvier",
+        "février",
+        "mars",
+        "avril",
+        "mai",
+        "juin",
+        "juillet",
+        "août",
+        "septembre",
+        "octobre",
+        "novembre",
+        "décembre",
+    ],
+    short_date: "%a %-d %b",
+};
+
+pub const ITALIAN: Locale = Locale {
+    code: "it",
+    weekdays: ["Lun", "Mar", "Mer", "Gio", "Ven", "Sab", "Dom"],
+    weekdays_full: [
+        "lunedì",
+        "martedì",
+        "mercoledì",
+        "giovedì",
+        "venerdì",
+        "sabato",
+        "domenica",
+    ],
+    months: [
+        "gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic",
+    ],
+    months_full: [
+        "gennaio",
+        "febbraio",
+        "marzo",
+        "aprile",
+        "maggio",
+        "giugno",
+        "luglio",
+        "agosto",
+        "settembre",
+        "ottobre",
+        "novembre",
+        "dicembre",
+    ],
+    short_date: "%a %-d %b",
+};
+
+pub const DUTCH: Locale = Locale {
+    code: "nl",
+    weekdays: ["Ma", "Di", "Wo", "Do", "Vr", "Za", "Zo"],
+    weekdays_full: [
+        "maandag",
+        "dinsdag",
+        "woensdag",
+        "donderdag",
+        "vrijdag",
+        "zaterdag",
+        "zondag",
+    ],
+    months: [
+        "jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec",
+    ],
+    months_full: [
+        "januari",
+        "februari",
+        "maart",
+        "april",
+        "mei",
+        "juni",
+        "juli",
+        "augustus",
+        "september",
+        "oktober",
+        "november",
+        "december",
+    ],
+    short_date: "%a %-d %b",
+};
+
+pub const PORTUGUESE: Locale = Locale {
+    code: "pt",
+    weekdays: ["Seg", "Ter", "Qua", "Qui", "Sex", "Sáb", "Dom"],
+    weekdays_full: [
+        "segunda-feira",
+        "terça-feira",
+        "quarta-feira",
+        "quinta-feira",
+        "sexta-feira",
+        "sábado",
+        "domingo",
+    ],
+    months: [
+        "jan", "fev", "mar", "abr", "mai", "jun", "jul", "ago", "set", "out", "nov", "dez",
+    ],
+    months_full: [
+        "janeiro",
+        "fevereiro",
+        "março",
+        "abril",
+        "maio",
+        "junho",
+        "julho",
+        "agosto",
+        "setembro",
+        "outubro",
+        "novembro",
+        "dezembro",
+    ],
+    short_date: "%a %-d %b",
+};
+
+pub const SWEDISH: Locale = Locale {
+    code: "sv",
+    weekdays: ["Mån", "Tis", "Ons", "Tor", "Fre", "Lör", "Sön"],
+    weekdays_full: [
+        "måndag", "tisdag", "onsdag", "torsdag", "fredag", "lördag", "söndag",
+    ],
+    months: [
+        "jan", "feb", "mar", "apr", "maj", "jun", "jul", "aug", "sep", "okt", "nov", "dec",
+    ],
+    months_full: [
+        "januari",
+        "februari",
+        "mars",
+        "april",
+        "maj",
+        "juni",
+        "juli",
+        "augusti",
+        "september",
+        "oktober",
+        "november",
+        "december",
+    ],
+    short_date: "%a %-d %b",
+};
+
+pub const LOCALES: &[&Locale] = &[
+    &ENGLISH,
+    &GERMAN,
+    &SPANISH,
+    &FRENCH,
+    &ITALIAN,
+    &DUTCH,
+    &PORTUGUESE,
+    &SWEDISH,
+];
+
+impl Locale {
+    pub fn weekday(&self, weekday: Weekday) -> &'static str {
+        self.weekdays[weekday.to_monday_zero_offset() as usize]
+    }
+
+    /// strftime, with `%a`, `%A`, `%b`, `%h` and `%B` in this language.
+    pub fn format(&self, pattern: &str, now: &Zoned) -> Result<String, jiff::Error> {
+        let month = now.month() as usize - 1;
+        let weekday = now.weekday().to_monday_zero_offset() as usize;
+        let mut localized = String::with_capacity(pattern.len());
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                localized.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('a') => localized.push_str(self.weekdays[weekday]),
+                Some('A') => localized.push_str(self.weekdays_full[weekday]),
+                Some('b' | 'h') => localized.push_str(self.months[month]),
+                Some('B') => localized.push_str(self.months_full[month]),
+                Some(other) => {
+                    localized.push('%');
+                    localized.push(other);
+                }
+                None => localized.push('%'),
+            }
+        }
+        jiff::fmt::strtime::format(&localized, now)
+    }
+}
+
+/// `code` is a two-letter language (`de`), optionally with a region or
+/// encoding after it (`de_AT.UTF-8`).
+pub fn find(code: &str) -> Option<&'static Locale> {
+    let language = code.trim().get(..2)?;
+    LOCALES
+        .iter()
+        .find(|locale| locale.code.eq_ignore_ascii_case(language))
+        .copied()
+}
+
+static LOCALE: Mutex<CriticalSectionRawMutex, RefCell<&'static Locale>> =
+    Mutex::new(RefCell::new(&ENGLISH));
+
+pub fn get() -> &'static Locale {
+    LOCALE.lock(|locale| *locale.borrow())
+}
+
+pub fn set(locale: &'static Locale) {
+    LOCALE.lock(|cell| cell.replace(locale));
+}
+
+/// Look `code` up and make it the display language; English stays if it's
+/// not one we have. Empty means English.
+pub fn set_from_str(code: &str) -> Result<()> {
+    if code.trim().is_empty() {
+        set(&ENGLISH);
+        return Ok(());
+    }
+    set(find(code).ok_or(anyhow!("no names for language {}", code))?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> Zoned {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn names_are_latin1_and_fit_the_forecast() {
+        for locale in LOCALES {
+            let names = locale
+                .weekdays
+                .iter()
+                .chain(&locale.weekdays_full)
+                .chain(&locale.months)
+                .chain(&locale.months_full);
+            for name in names {
+                assert!(name.chars().all(|c| (c as u32) < 0x100), "{name}");
+            }
+            for day in locale.weekdays {
+                assert!(day.chars().count() <= 3, "{day}");
+            }
+        }
+    }
+
+    #[test]
+    fn short_dates() {
+        let now = at("2026-03-18T09:05:07+01:00[+01:00]");
+        let short = |locale: &Locale| locale.format(locale.short_date, &now).unwrap();
+        assert_eq!(short(&ENGLISH), "Wed Mar 18");
+        assert_eq!(short(&GERMAN), "Mi 18. Mär");
+        assert_eq!(short(&SPANISH), "Mié 18 mar");
+        assert_eq!(short(&SWEDISH), "Ons 18 mar");
+    }
+
+    #[test]
+    fn full_names_and_other_fields() {
+        let now = at("2026-08-01T21:05:00+02:00[+02:00]");
+        assert_eq!(
+            FRENCH.format("%A %-d %B %Y, %H:%M", &now).unwrap(),
+            "samedi 1 août 2026, 21:05"
+        );
+        assert_eq!(PORTUGUESE.format("%a %h", &now).unwrap(), "Sáb ago");
+        assert_eq!(GERMAN.format("100%% %a", &now).unwrap(), "100% Sa");
+        assert!(GERMAN.format("%a %", &now).is_err());
+    }
+
+    #[test]
+    fn languages_are_found_by_prefix() {
+        assert_eq!(find("de"), Some(&GERMAN));
+        assert_eq!(find("es_MX.UTF-8"), Some(&SPANISH));
+        assert_eq!(find("NL"), Some(&DUTCH));
+        assert_eq!(find("ja"), None);
+        assert_eq!(find("x"), None);
+    }
+}
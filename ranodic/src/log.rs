@@ -3,3 +3,15 @@ pub(crate) use defmt::{debug, error, info, println, warn};
 
 #[cfg(feature = "log")]
 pub(crate) use log::{debug, error, info, println, warn};
+
+// neither, as on the host: the arguments are still evaluated and type-checked,
+// and the message dropped
+#[cfg(not(any(feature = "defmt", feature = "log")))]
+macro_rules! quiet {
+    ($format:literal $(, $argument:expr)* $(,)?) => {{
+        $(let _ = &$argument;)*
+    }};
+}
+
+#[cfg(not(any(feature = "defmt", feature = "log")))]
+pub(crate) use {quiet as debug, quiet as error, quiet as info, quiet as println, quiet as warn};
@@ -8,55 +8,52 @@ use alloc::{
 use crate::log::{debug, error, info};
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, watch::Watch};
 use embassy_time::Timer;
-use embedded_graphics::pixelcolor::RgbColor;
-use esp_hub75::Color;
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
 use serde_json::Value;
 
 use nanofish::{HttpHeader, ResponseBody, mime_types};
 
-use crate::{
-    drawing::{ColorMonoTextStyle, bgfontbase},
-    ntp::zgettimeofday,
-};
+use crate::drawing::{ColorMonoTextStyle, bgfontbase};
 
+#[cfg(target_os = "none")]
 const NIGHTSCOUT_TOKEN: &str = env!("NIGHTSCOUT_TOKEN");
+#[cfg(target_os = "none")]
 const NIGHTSCOUT_URL: &str = env!("NIGHTSCOUT_URL");
 
 pub static QUICKTRIES: AtomicU8 = AtomicU8::new(3);
 
 pub const OKAY_FONT: ColorMonoTextStyle<'static> = bgfontbase()
-    .text_color(Color::GREEN)
-    .background_color(Color::BLACK)
+    .text_color(Rgb888::GREEN)
+    .background_color(Rgb888::BLACK)
     .build();
 
 pub const HIGH_FONT: ColorMonoTextStyle<'static> = bgfontbase()
-    .text_color(Color::YELLOW)
-    .background_color(Color::BLACK)
+    .text_color(Rgb888::YELLOW)
+    .background_color(Rgb888::BLACK)
     .build();
 
 pub const SUPERHIGH_FONT: ColorMonoTextStyle<'static> = bgfontbase()
-    .text_color(Color::BLACK)
-    .background_color(Color::YELLOW)
+    .text_color(Rgb888::BLACK)
+    .background_color(Rgb888::YELLOW)
     .build();
 
 pub const LOW_FONT: ColorMonoTextStyle<'static> = bgfontbase()
-    .text_color(Color::RED)
-    .background_color(Color::BLACK)
+    .text_color(Rgb888::RED)
+    .background_color(Rgb888::BLACK)
     .build();
 
 pub const SUPERLOW_FONT: ColorMonoTextStyle<'static> = bgfontbase()
-    .text_color(Color::WHITE)
-    .background_color(Color::RED)
+    .text_color(Rgb888::WHITE)
+    .background_color(Rgb888::RED)
     .build();
 
 pub const STALE_FONT: ColorMonoTextStyle<'static> = bgfontbase()
-    .text_color(Color::WHITE)
-    .background_color(Color::BLUE)
+    .text_color(Rgb888::WHITE)
+    .background_color(Rgb888::BLUE)
     .build();
 
-pub async fn get_style(bgreading: &BgReading) -> ColorMonoTextStyle<'static> {
-    let now = zgettimeofday().await;
-    let diffsecs = (&now - &bgreading.timestamp)
+pub fn get_style(bgreading: &BgReading, now: &jiff::Zoned) -> ColorMonoTextStyle<'static> {
+    let diffsecs = (now - &bgreading.timestamp)
         .total(jiff::Unit::Second)
         .unwrap() as u64;
     if diffsecs > crate::nightscout::STALE_SECS {
@@ -68,12 +65,12 @@ pub async fn get_style(bgreading: &BgReading) -> ColorMonoTextStyle<'static> {
     } else if bgreading.bg <= 75 {
         // trace!("datapoint is low");
         LOW_FONT
-    } else if bgreading.bg >= 150 {
-        // trace!("datapoint is high");
-        HIGH_FONT
     } else if bgreading.bg >= 250 {
         // trace!("datapoint is superhigh");
         SUPERHIGH_FONT
+    } else if bgreading.bg >= 150 {
+        // trace!("datapoint is high");
+        HIGH_FONT
     } else {
         // trace!("datapoint is okay");
         OKAY_FONT
@@ -106,6 +103,7 @@ pub static BGDATA: Watch<CriticalSectionRawMutex, BgReading, 2> = Watch::new();
 const BG_SUCCESS_INTERVAL: u64 = 150;
 const BG_FAILURE_INTERVAL: u64 = 10;
 
+#[cfg(target_os = "none")]
 #[embassy_executor::task]
 pub async fn nightscout_query(stack: embassy_net::Stack<'static>) {
     debug!("nightscout_query alive");
@@ -153,24 +151,22 @@ pub async fn nightscout_query(stack: embassy_net::Stack<'static>) {
             Timer::after_secs(BG_FAILURE_INTERVAL).await;
             continue;
         }
+        crate::ntp::http_date(response.get_header("Date")).await;
 
         if let ResponseBody::Text(jason) = response.body {
             let entries: Value = serde_json::from_str(jason).expect("valued");
-            if let (Some(bgda), Some(bgu), Some(bgdt), Some(bgzo)) = (
+            // utcOffset is the uploader's zone, not ours; display in the configured one
+            if let (Some(bgda), Some(bgu), Some(bgdt)) = (
                 entries[0]["sgv"].as_u64(),
                 entries[0]["units"].as_str(),
                 entries[0]["date"].as_i64(),
-                entries[0]["utcOffset"].as_i64(),
             ) {
                 let reading = BgReading {
                     bg: bgda,
                     units: bgu.into(),
                     timestamp: jiff::Timestamp::from_millisecond(bgdt)
                         .expect("nightscout_query: couldn't timestamp")
-                        .to_zoned(jiff::tz::TimeZone::fixed(
-                            jiff::tz::Offset::from_seconds((bgzo * 60).try_into().unwrap())
-                                .expect("nightscout_query: couldn't offset zone"),
-                        )),
+                        .to_zoned(crate::timezone::get()),
                 };
                 info!("nightscout_query: sent reading: {:?}", reading);
                 sender.send(reading);
@@ -185,3 +181,34 @@ pub async fn nightscout_query(stack: embassy_net::Stack<'static>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A style's colors; the fonts hold a `dyn` glyph
+    /// mapping, so whole styles don't compare.
+    fn colors(style: ColorMonoTextStyle<'static>) -> (Option<Rgb888>, Option<Rgb888>) {
+        (style.text_color, style.background_color)
+    }
+
+    fn style(bg: u64) -> (Option<Rgb888>, Option<Rgb888>) {
+        let now: jiff::Zoned = "2026-03-18T09:41:00-07:00[-07:00]".parse().unwrap();
+        let reading = BgReading {
+            bg,
+            units: String::from("mg/dl"),
+            timestamp: now.clone(),
+        };
+        colors(get_style(&reading, &now))
+    }
+
+    #[test]
+    fn each_range_has_its_style() {
+        assert_eq!(style(52), colors(SUPERLOW_FONT));
+        assert_eq!(style(71), colors(LOW_FONT));
+        assert_eq!(style(112), colors(OKAY_FONT));
+        assert_eq!(style(183), colors(HIGH_FONT));
+        assert_eq!(style(250), colors(SUPERHIGH_FONT));
+        assert_eq!(style(287), colors(SUPERHIGH_FONT));
+    }
+}
@@ -0,0 +1,303 @@
+//! Time from a GPS receiver's NMEA 0183 output: `$--RMC` and `$--ZDA` from
+//! any talker (GP, GN, GL...). Everything else the receiver sends is skipped.
+//!
+//! A sentence goes out some way into the second it describes, so on its own
+//! it's good to a few hundred ms. With the receiver's PPS output wired up, the
+//! pulse marks the start of that second to within a microsecond and the
+//! sentence only has to say which second it was.
+
+use crate::timesource::{GPS_ERROR, GPS_PPS_ERROR};
+use jiff::Timestamp;
+use jiff::civil::{Date, DateTime, Time};
+use jiff::tz::Offset;
+
+/// The longest sentence the standard allows, with the `\r\n`.
+pub const MAX_SENTENCE: usize = 82;
+/// How far into the second the receiver typically finishes sending about it,
+/// µs.
+pub const SENTENCE_LATENCY: i64 = 250_000;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Not `$...*hh`.
+    Framing,
+    Checksum,
+    /// A sentence without the time.
+    Unsupported,
+    /// A missing or nonsense field; receivers send empty ones before a fix.
+    Field,
+}
+
+/// What a sentence says the time is.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Fix {
+    pub time: Timestamp,
+    /// RMC status `A`; a receiver without a fix may still send the time from
+    /// its own RTC with status `V`.
+    pub valid: bool,
+}
+
+pub fn parse(line: &str) -> Result<Fix, Error> {
+    let (body, checksum) = line
+        .trim_end()
+        .strip_prefix('$')
+        .and_then(|line| line.rsplit_once('*'))
+        .ok_or(Error::Framing)?;
+    if checksum.len() != 2 {
+        return Err(Error::Framing);
+    }
+    let checksum = u8::from_str_radix(checksum, 16).map_err(|_| Error::Framing)?;
+    if body.bytes().fold(0, |sum, byte| sum ^ byte) != checksum {
+        return Err(Error::Checksum);
+    }
+    let mut fields = body.split(',');
+    let address = fields.next().ok_or(Error::Framing)?;
+    match address.get(2..) {
+        Some("RMC") if address.len() == 5 => rmc(fields),
+        Some("ZDA") if address.len() == 5 => zda(fields),
+        _ => Err(Error::Unsupported),
+    }
+}
+
+// hhmmss.ss,A,llll.ll,a,yyyyy.yy,a,x.x,x.x,ddmmyy,...
+fn rmc<'a>(mut fields: impl Iterator<Item = &'a str>) -> Result<Fix, Error> {
+    let time = time(fields.next())?;
+    let valid = fields.next() == Some("A");
+    let date = fields.nth(6).ok_or(Error::Field)?;
+    if date.len() != 6 {
+        return Err(Error::Field);
+    }
+    // two digit years: the receivers this could meet are all from after 1980
+    let year = number(&date[4..6])?;
+    let year = if year < 80 { 2000 + year } else { 1900 + year };
+    let date = Date::new(
+        year as i16,
+        number(&date[2..4])? as i8,
+        number(&date[0..2])? as i8,
+    )
+    .map_err(|_| Error::Field)?;
+    fix(date, time, valid)
+}
+
+// hhmmss.ss,dd,mm,yyyy,zh,zm; the local zone fields are always zero in
+// practice and ignored
+fn zda<'a>(mut fields: impl Iterator<Item = &'a str>) -> Result<Fix, Error> {
+    let time = time(fields.next())?;
+    let mut next = || number(fields.next().ok_or(Error::Field)?);
+    let (day, month, year) = (next()?, next()?, next()?);
+    if year > 9999 || month > 12 || day > 31 {
+        return Err(Error::Field);
+    }
+    let date = Date::new(year as i16, month as i8, day as i8).map_err(|_| Error::Field)?;
+    fix(date, time, true)
+}
+
+fn fix(date: Date, time: Time, valid: bool) -> Result<Fix, Error> {
+    Ok(Fix {
+        time: Offset::UTC
+            .to_timestamp(DateTime::from_parts(date, time))
+            .map_err(|_| Error::Field)?,
+        valid,
+    })
+}
+
+// hhmmss with any number of decimals
+fn time(field: Option<&str>) -> Result<Time, Error> {
+    let field = field.ok_or(Error::Field)?;
+    let (whole, fraction) = field.split_once('.').unwrap_or((field, ""));
+    if whole.len() != 6 || fraction.len() > 9 {
+        return Err(Error::Field);
+    }
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        number(fraction)? * 10i32.pow(9 - fraction.len() as u32)
+    };
+    Time::new(
+        number(&whole[0..2])? as i8,
+        number(&whole[2..4])? as i8,
+        number(&whole[4..6])? as i8,
+        nanos,
+    )
+    .map_err(|_| Error::Field)
+}
+
+fn number(field: &str) -> Result<i32, Error> {
+    if field.is_empty() || field.len() > 9 || !field.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Error::Field);
+    }
+    field.parse().map_err(|_| Error::Field)
+}
+
+/// Our clock's offset from `fix`, µs, and how wrong that could be. `received`
+/// is our clock when the sentence's last byte arrived and `pulse` our clock at
+/// the latest PPS edge, if there is a PPS line.
+pub fn offset(fix: &Fix, received: i64, pulse: Option<i64>) -> (i64, u64) {
+    let time = fix.time.as_microsecond();
+    match pulse {
+        // the sentence names the second the pulse before it started
+        Some(pulse)
+            if (0..1_000_000).contains(&(received - pulse))
+                && fix.time.subsec_nanosecond() == 0 =>
+        {
+            (time - pulse, GPS_PPS_ERROR)
+        }
+        _ => (time + SENTENCE_LATENCY - received, GPS_ERROR),
+    }
+}
+
+/// Splits what comes off the UART into lines. Ones too long to be NMEA, like
+/// the noise before the receiver settles, are dropped whole.
+pub struct Lines {
+    buf: [u8; MAX_SENTENCE],
+    len: usize,
+    overflowed: bool,
+}
+
+impl Default for Lines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lines {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_SENTENCE],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// The line `byte` completes, if it's a newline.
+    pub fn push(&mut self, byte: u8) -> Option<&str> {
+        if byte == b'\n' {
+            let (len, overflowed) = (self.len, self.overflowed);
+            self.len = 0;
+            self.overflowed = false;
+            if overflowed {
+                return None;
+            }
+            return core::str::from_utf8(&self.buf[..len]).ok();
+        }
+        if self.len == MAX_SENTENCE {
+            self.overflowed = true;
+        } else {
+            self.buf[self.len] = byte;
+            self.len += 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    // as a u-blox NEO-M8N (NMEA 4.1, GN talker) and an older GP-only module
+    // send them
+    const RMC_UBLOX: &str =
+        "$GNRMC,201530.00,A,3746.49424,N,12225.09861,W,0.012,,180326,,,A,V*07\r\n";
+    const ZDA_UBLOX: &str = "$GNZDA,201530.00,18,03,2026,00,00*71\r\n";
+    const RMC_MTK: &str = "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+    const RMC_NO_FIX: &str = "$GNRMC,,V,,,,,,,,,,N*4D";
+    const ZDA_NO_FIX: &str = "$GNZDA,,,,,00,00*56";
+    const GGA: &str = "$GPGGA,092750.000,5321.6802,N,00630.3372,W,1,8,1.03,61.7,M,55.2,M,,*76";
+
+    #[test]
+    fn parses_rmc_and_zda() {
+        let expected = Fix {
+            time: ts("2026-03-18T20:15:30Z"),
+            valid: true,
+        };
+        assert_eq!(parse(RMC_UBLOX), Ok(expected));
+        assert_eq!(parse(ZDA_UBLOX), Ok(expected));
+        assert_eq!(
+            parse(RMC_MTK),
+            Ok(Fix {
+                time: ts("1994-03-23T12:35:19Z"),
+                valid: true,
+            })
+        );
+        assert_eq!(
+            parse("$GPZDA,082710.25,16,09,2002,00,00*63").map(|fix| fix.time),
+            Ok(ts("2002-09-16T08:27:10.25Z"))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_checksums_and_framing() {
+        assert_eq!(
+            parse(&RMC_UBLOX.replace("201530", "201531")),
+            Err(Error::Checksum)
+        );
+        assert_eq!(parse(&RMC_MTK.replace("*6A", "*6B")), Err(Error::Checksum));
+        assert_eq!(parse(&RMC_MTK.replace("*6A", "")), Err(Error::Framing));
+        assert_eq!(parse(&RMC_MTK.replace("*6A", "*6")), Err(Error::Framing));
+        assert_eq!(parse(&RMC_MTK[1..]), Err(Error::Framing));
+        assert_eq!(parse(""), Err(Error::Framing));
+    }
+
+    #[test]
+    fn no_fix_is_not_time() {
+        assert_eq!(parse(RMC_NO_FIX), Err(Error::Field));
+        assert_eq!(parse(ZDA_NO_FIX), Err(Error::Field));
+        assert_eq!(parse(GGA), Err(Error::Unsupported));
+        // time from the receiver's RTC before a fix
+        assert_eq!(
+            parse("$GPRMC,123519,V,,,,,,,230394,,,N*51").map(|fix| fix.valid),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn without_pps_the_sentence_is_assumed_late() {
+        let fix = parse(RMC_UBLOX).unwrap();
+        let clock = fix.time.as_microsecond();
+        // our clock read 0.4 s past the second when the sentence came in
+        assert_eq!(
+            offset(&fix, clock + 400_000, None),
+            (SENTENCE_LATENCY - 400_000, GPS_ERROR)
+        );
+    }
+
+    #[test]
+    fn pps_marks_the_second() {
+        let fix = parse(RMC_UBLOX).unwrap();
+        let clock = fix.time.as_microsecond();
+        // the pulse came when our clock read 3 ms short of the second
+        let pulse = clock - 3_000;
+        assert_eq!(
+            offset(&fix, pulse + 180_000, Some(pulse)),
+            (3_000, GPS_PPS_ERROR)
+        );
+        // a pulse from a second before doesn't belong to this sentence
+        assert_eq!(offset(&fix, pulse + 1_200_000, Some(pulse)).1, GPS_ERROR);
+    }
+
+    #[test]
+    fn lines_are_split_and_bounded() {
+        let mut lines = Lines::new();
+        let mut seen = 0;
+        let noise = [0xffu8; 200];
+        let stream = [
+            &noise[..],
+            b"\n",
+            RMC_UBLOX.as_bytes(),
+            ZDA_UBLOX.as_bytes(),
+        ]
+        .concat();
+        for byte in stream {
+            if let Some(line) = lines.push(byte) {
+                assert!(parse(line).is_ok(), "{line}");
+                seen += 1;
+            }
+        }
+        assert_eq!(seen, 2);
+    }
+}
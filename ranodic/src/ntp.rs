@@ -1,35 +1,35 @@
 use crate::RTCREF;
-#[cfg(feature = "rtcchip")]
-use crate::rtc::micros_to_ic;
+use crate::clockfilter::{self, Correction, Discipline, Sample};
+use crate::dhcp;
+use crate::httpdate::{self, Verdict};
+#[cfg(feature = "ntpserver")]
+use crate::sntp::{self, ServerState};
+use crate::timesource::{self, HTTP_DATE_ERROR, Source};
+use crate::warmboot::{self, Tick};
 use alloc::string::ToString;
+use alloc::vec::Vec;
 use anyhow::anyhow;
 use core::net::SocketAddr;
-use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
 
-use crate::log::{debug, error, info};
-use embassy_net::IpAddress;
+use crate::log::{debug, error, info, warn};
+use embassy_net::raw::{IpProtocol, IpVersion, PacketMetadata as RawPacketMetadata, RawSocket};
 use embassy_net::udp::{PacketMetadata, UdpSocket};
-use embassy_time::Timer;
+use embassy_net::{IpAddress, Ipv4Address, Stack};
+use embassy_time::{Duration, Instant, Ticker, Timer};
 use esp_hal::ram;
 use esp_hal::rtc_cntl::Rtc;
+use esp_hal::system::reset_reason;
 use jiff::Zoned;
-use smoltcp::wire::DnsQueryType;
+use smoltcp::wire::{DHCP_CLIENT_PORT, DHCP_SERVER_PORT, DnsQueryType};
 use sntpc::{
-    NtpContext, NtpResult, NtpTimestampGenerator, NtpUdpSocket, sntp_process_response,
-    sntp_send_request,
+    NtpContext, NtpResult, NtpTimestampGenerator, NtpUdpSocket, fraction_to_microseconds,
+    sntp_process_response, sntp_send_request,
 };
 use sntpc_net_embassy::UdpSocketWrapper;
 
-// const TZ: &str = env!("TZ");
-
-// and this is how I learned the "fun" of nested macros
-// https://github.com/rust-lang/rust/issues/90765
-
-// fuckin' software, amirite
-// const TIMEZONE: jiff::tz::TimeZone = jiff::tz::get!("PST8PDT,M3.2.0,M11.1.0");
-
 pub async fn zgettimeofday() -> Zoned {
-    gettimeofday().await.to_zoned(TIMEZONE)
+    gettimeofday().await.to_zoned(crate::timezone::get())
 }
 
 pub async fn gettimeofday() -> jiff::Timestamp {
@@ -37,28 +37,84 @@ pub async fn gettimeofday() -> jiff::Timestamp {
         .unwrap_or(jiff::Timestamp::MIN)
 }
 
-pub const TIMEZONE: jiff::tz::TimeZone = jiff::tz::get!("PST8PDT");
 const NTP_SERVER: &str = env!("NTP_SERVER");
-const USEC_IN_SEC: u64 = 1_000_000;
 const NTP_INTERVAL: u64 = 120;
+/// Most servers sampled from each of DHCP and `NTP_SERVER`.
+const MAX_SERVERS: usize = 4;
+const DHCP_TIMEOUT: u64 = 2;
+/// Seconds before asking again a DHCP server that offered no NTP servers,
+/// in case the reply was lost.
+const OFFER_RETRY: u64 = 3600;
 
 pub static NTP_SYNCED: AtomicU32 = AtomicU32::new(0);
 
 pub static TIME_SYNCED: AtomicBool = AtomicBool::new(false);
 
+/// Estimated RTC frequency error in parts per billion; positive runs slow.
+pub static DRIFT_PPB: AtomicI32 = AtomicI32::new(0);
+
+/// Seconds since boot when an HTTP `Date:` header last set or confirmed the
+/// clock.
+pub static HTTP_SYNCED: AtomicU32 = AtomicU32::new(0);
+
+/// Set by anything other than `ntp_sync` that steps the clock, so the next
+/// offset isn't mistaken for drift.
+pub static STEPPED_ELSEWHERE: AtomicBool = AtomicBool::new(false);
+
 #[ram(unstable(rtc_fast), unstable(persistent))]
-static mut TICK: u64 = 0;
+static mut TICK: [i64; warmboot::WORDS] = [0; warmboot::WORDS];
 #[embassy_executor::task]
 pub async fn tick_writer() {
     let rtc = &**RTCREF.get().await;
     loop {
-        if TIME_SYNCED.load(Ordering::Relaxed) {
-            unsafe { TICK = rtc.current_time_us() };
+        let uptime = rtc.time_since_boot();
+        if TIME_SYNCED.load(Ordering::Relaxed)
+            && let Some((_, sync)) = timesource::best(uptime.as_secs())
+        {
+            let tick = Tick {
+                time: rtc.current_time_us() as i64,
+                uptime: uptime.as_micros(),
+                error: sync.error_at(uptime.as_secs()),
+            };
+            unsafe { TICK = tick.to_words() };
         }
         Timer::after_secs(1).await;
     }
 }
 
+/// After a software or watchdog reset, put back the time `tick_writer` last
+/// saved, so the panel doesn't sit on the logo until NTP answers.
+pub fn restore_tick() {
+    let Some(reason) = reset_reason() else {
+        return;
+    };
+    if !warmboot::is_soft_reset(reason as u32) {
+        return;
+    }
+    #[allow(static_mut_refs)]
+    let Some(tick) = Tick::from_words(unsafe { &TICK }) else {
+        info!("restore_tick: nothing saved");
+        return;
+    };
+    let Some(rtc) = RTCREF.try_get() else {
+        return;
+    };
+    let uptime = rtc.time_since_boot();
+    let (time, error) = tick.restore(uptime.as_micros());
+    if error > warmboot::MAX_ERROR {
+        info!(
+            "restore_tick: saved time is {}ms out, ignored",
+            error / 1000
+        );
+        return;
+    }
+    rtc.set_current_time_us(time as u64);
+    timesource::record(Source::Restored, uptime.as_secs(), error, None, None);
+    TIME_SYNCED.store(true, Ordering::Relaxed);
+    STEPPED_ELSEWHERE.store(true, Ordering::Relaxed);
+    info!("restore_tick: restored to within {}ms", error / 1000);
+}
+
 #[derive(Clone, Copy)]
 struct RtcTimestampGen<'a> {
     rtc: &'a Rtc<'a>,
@@ -92,120 +148,428 @@ impl NtpTimestampGenerator for RtcTimestampGen<'_> {
 #[embassy_executor::task]
 pub async fn ntp_sync(stack: embassy_net::Stack<'static>) {
     debug!("ntp_sync started");
+    let mut discipline = Discipline::default();
+    // option 42 as offered to our current address, and when we asked
+    let mut offered: Option<(Ipv4Address, u64, Vec<IpAddress>)> = None;
     loop {
         stack.wait_config_up().await;
-        let ntpctx = NtpContext::new(RtcTimestampGen::new().await);
-        let mut rx_meta = [PacketMetadata::EMPTY; 16];
-        let mut rx_buffer = [0; 1024];
-        let mut tx_meta = [PacketMetadata::EMPTY; 16];
-        let mut tx_buffer = [0; 1024];
-        let mut socket = UdpSocket::new(
-            stack,
-            &mut rx_meta,
-            &mut rx_buffer,
-            &mut tx_meta,
-            &mut tx_buffer,
-        );
-        socket.bind(123).unwrap();
-        let ntpsocket = UdpSocketWrapper::new(socket);
-        debug!("ntp_sync network stack up");
-        info!("ntp_sync: DNS");
-        let addrs = match stack.dns_query(NTP_SERVER, DnsQueryType::A).await {
-            Ok(e) => {
-                if e.is_empty() {
-                    error!("ntp_sync: empty addresses for {}", NTP_SERVER);
-                    #[cfg(feature = "rtcchip")]
-                    let _ = crate::rtc::ic_to_sys().await;
-                    Timer::after_secs(NTP_INTERVAL / 10).await;
+        if STEPPED_ELSEWHERE.swap(false, Ordering::Relaxed) {
+            discipline.forget();
+        }
+        if let Some(config) = stack.config_v4() {
+            let address = config.address.address();
+            let now = Instant::now().as_secs();
+            let ask = offered.as_ref().is_none_or(|(seen, asked, servers)| {
+                *seen != address || (servers.is_empty() && now - asked >= OFFER_RETRY)
+            });
+            if ask {
+                offered = Some((address, now, dhcp_ntp_servers(stack, address).await));
+            }
+        }
+        let servers = offered
+            .as_ref()
+            .map(|(_, _, servers)| servers.clone())
+            .unwrap_or_default();
+        let mut selection = None;
+        if !servers.is_empty() {
+            let samples = sample(stack, &servers).await;
+            selection = clockfilter::select(&samples);
+            if selection.is_none() {
+                warn!(
+                    "ntp_sync: no majority among {} responding offered servers, trying {}",
+                    samples.len(),
+                    NTP_SERVER
+                );
+                // ask the DHCP server again next poll
+                offered = None;
+            }
+        }
+        let selection = match selection {
+            Some(selection) => selection,
+            None => {
+                let addrs = resolve(stack, NTP_SERVER).await;
+                if addrs.is_empty() {
+                    error!("ntp_sync: no addresses for {}", NTP_SERVER);
+                    fallback(&mut discipline).await;
                     continue;
                 }
-                e
-            }
-            Err(e) => {
-                error!("ntp_sync: DNS error: {}", e);
-                #[cfg(feature = "rtcchip")]
-                let _ = crate::rtc::ic_to_sys().await;
-                Timer::after_secs(NTP_INTERVAL / 10).await;
-                continue;
+                let samples = sample(stack, &addrs).await;
+                let Some(selection) = clockfilter::select(&samples) else {
+                    error!(
+                        "ntp_sync: no majority among {} responding servers",
+                        samples.len()
+                    );
+                    fallback(&mut discipline).await;
+                    continue;
+                };
+                selection
             }
         };
-        info!("ntp_sync: get_time");
-        match get_time(addrs, &ntpsocket, ntpctx).await {
-            Ok(ntptime) => {
-                info!("ntp_sync: ok response");
-
-                #[cfg(feature = "rtcchip")]
-                let _rtcchipres = micros_to_ic(
-                    ntptime.sec() as u64 * USEC_IN_SEC
-                        + sntpc::fraction_to_microseconds(ntptime.sec_fraction()) as u64,
-                );
-                RTCREF.get().await.set_current_time_us(
-                    ntptime.sec() as u64 * USEC_IN_SEC
-                        + sntpc::fraction_to_microseconds(ntptime.sec_fraction()) as u64,
-                );
-                TIME_SYNCED.store(true, Ordering::Relaxed);
-                NTP_SYNCED.store(
-                    RTCREF
-                        .get()
-                        .await
-                        .time_since_boot()
-                        .as_secs()
-                        .try_into()
-                        .unwrap(),
-                    Ordering::Relaxed,
-                );
+        info!(
+            "ntp_sync: offset {}us within {}us, stratum {}, {} falsetickers",
+            selection.offset, selection.distance, selection.stratum, selection.falsetickers
+        );
+
+        let rtc = &**RTCREF.get().await;
+        if !timesource::should_apply(
+            Source::Ntp,
+            selection.distance,
+            rtc.time_since_boot().as_secs(),
+        ) {
+            // GPS with PPS has the clock; keep out of its way
+            debug!("ntp_sync: outranked");
+            discipline.forget();
+            Timer::after_secs(NTP_INTERVAL).await;
+            continue;
+        }
+        match discipline.update(selection.offset, Instant::now().as_micros()) {
+            // the clock still counts from 1970; set it to the server's time
+            // rather than add decades of offset to it
+            Correction::Step(_) if !TIME_SYNCED.load(Ordering::Relaxed) => {
+                let now = selection
+                    .boot_time
+                    .saturating_add_unsigned(Instant::now().as_micros());
+                info!("ntp_sync: setting the clock to {}us", now);
+                rtc.set_current_time_us(now.max(0) as u64);
+            }
+            Correction::Step(offset) => {
+                info!("ntp_sync: stepping {}us", offset);
+                rtc.set_current_time_us(rtc.current_time_us().saturating_add_signed(offset));
             }
+            Correction::Slew(offset) => debug!("ntp_sync: slewing {}us", offset),
+        }
+        DRIFT_PPB.store((discipline.drift_ppm() * 1000.0) as i32, Ordering::Relaxed);
+        debug!("ntp_sync: drift {}ppm", discipline.drift_ppm());
+
+        let uptime = rtc.time_since_boot().as_secs();
+        timesource::record(
+            Source::Ntp,
+            uptime,
+            selection.distance,
+            Some(selection.stratum),
+            Some(selection.reference_id),
+        );
+        #[cfg(feature = "rtcchip")]
+        if let Err(e) = crate::rtc::ntp_synced(discipline.pending()).await {
+            warn!("ntp_sync: RTC chip: {}", e.to_string());
+        }
+        TIME_SYNCED.store(true, Ordering::Relaxed);
+        NTP_SYNCED.store(uptime.try_into().unwrap(), Ordering::Relaxed);
+        discipline_for(&mut discipline, NTP_INTERVAL).await;
+    }
+}
+
+/// Sample up to [`MAX_SERVERS`] of `addrs` through a fresh socket.
+async fn sample(stack: Stack<'static>, addrs: &[IpAddress]) -> Vec<Sample> {
+    let addrs = &addrs[..addrs.len().min(MAX_SERVERS)];
+    let ntpctx = NtpContext::new(RtcTimestampGen::new().await);
+    let mut rx_meta = [PacketMetadata::EMPTY; 16];
+    let mut rx_buffer = [0; 1024];
+    let mut tx_meta = [PacketMetadata::EMPTY; 16];
+    let mut tx_buffer = [0; 1024];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    // with the server on 123, ask from anywhere else
+    #[cfg(feature = "ntpserver")]
+    socket.bind(0).unwrap();
+    #[cfg(not(feature = "ntpserver"))]
+    socket.bind(123).unwrap();
+    let ntpsocket = UdpSocketWrapper::new(socket);
+    debug!("ntp_sync network stack up");
+    info!("ntp_sync: sampling {} servers", addrs.len());
+    sample_servers(addrs, &ntpsocket, ntpctx).await
+}
+
+// NTP is unreachable: fall back to the RTC chip if there is one and it's
+// better than what we have, and keep the clock disciplined while waiting to
+// retry
+async fn fallback(discipline: &mut Discipline) {
+    #[cfg(feature = "rtcchip")]
+    {
+        let uptime = RTCREF.get().await.time_since_boot().as_secs();
+        if timesource::should_apply(Source::RtcChip, timesource::chip_error(uptime), uptime)
+            && crate::rtc::ic_to_sys().await.is_ok()
+        {
+            discipline.forget();
+        }
+    }
+    discipline_for(discipline, NTP_INTERVAL / 10).await;
+}
+
+/// Wait `secs`, applying slew and drift correction to the RTC every second.
+async fn discipline_for(discipline: &mut Discipline, secs: u64) {
+    let rtc = &**RTCREF.get().await;
+    let mut ticker = Ticker::every(Duration::from_secs(1));
+    let mut last = Instant::now();
+    for _ in 0..secs {
+        ticker.next().await;
+        let now = Instant::now();
+        let adjust = discipline.tick((now - last).as_micros());
+        last = now;
+        if adjust != 0 {
+            rtc.set_current_time_us(rtc.current_time_us().saturating_add_signed(adjust));
+        }
+    }
+}
+
+/// Feed the `Date:` header of a successful HTTP response in; see
+/// [`httpdate`] for how it ranks against NTP and the RTC chip.
+pub async fn http_date(header: Option<&str>) {
+    let Some(date) = header.and_then(httpdate::parse) else {
+        return;
+    };
+    let rtc = &**RTCREF.get().await;
+    let uptime = rtc.time_since_boot().as_secs();
+    let outranked = !timesource::should_apply(Source::HttpDate, HTTP_DATE_ERROR, uptime);
+    match httpdate::judge(date, gettimeofday().await, outranked) {
+        Verdict::Ignore => return,
+        Verdict::Confirm => debug!("http_date: clock agrees with {}", date.as_second()),
+        Verdict::Step(offset) => {
+            info!("http_date: stepping {}us to {}", offset, date.as_second());
+            rtc.set_current_time_us(rtc.current_time_us().saturating_add_signed(offset));
+            STEPPED_ELSEWHERE.store(true, Ordering::Relaxed);
+            #[cfg(feature = "rtcchip")]
+            if let Err(e) = crate::rtc::sys_to_ic().await {
+                warn!("http_date: RTC chip: {}", e.to_string());
+            }
+        }
+    }
+    timesource::record(Source::HttpDate, uptime, HTTP_DATE_ERROR, None, None);
+    HTTP_SYNCED.store(uptime.try_into().unwrap_or(u32::MAX), Ordering::Relaxed);
+    TIME_SYNCED.store(true, Ordering::Relaxed);
+}
+
+/// Answer SNTP requests from the LAN on UDP/123; see [`sntp`].
+#[cfg(feature = "ntpserver")]
+#[embassy_executor::task]
+pub async fn ntp_serve(stack: Stack<'static>) {
+    let rtc = &**RTCREF.get().await;
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buffer = [0; 512];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_buffer = [0; 512];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    socket.bind(123).unwrap();
+    info!("ntp_serve: listening");
+    let mut request = [0; 128];
+    loop {
+        let (len, meta) = match socket.recv_from(&mut request).await {
+            Ok(received) => received,
             Err(e) => {
-                error!("ntp_sync: Error getting time: {:?}", e.to_string());
-                #[cfg(feature = "rtcchip")]
-                let _ = crate::rtc::ic_to_sys().await;
-                Timer::after_secs(NTP_INTERVAL / 10).await;
+                debug!("ntp_serve: {:?}", e);
                 continue;
             }
+        };
+        let received = rtc.current_time_us();
+        let state = server_state(rtc);
+        let Some(reply) = sntp::respond(&request[..len], &state, received, rtc.current_time_us())
+        else {
+            continue;
+        };
+        if let Err(e) = socket.send_to(&reply, meta).await {
+            debug!("ntp_serve: {:?}", e);
+        }
+    }
+}
+
+/// What `ntp_serve` tells clients about our clock.
+#[cfg(feature = "ntpserver")]
+fn server_state(rtc: &Rtc) -> ServerState {
+    let uptime = rtc.time_since_boot().as_secs();
+    let best = timesource::best(uptime);
+    let (Some((source, sync)), true) = (best, TIME_SYNCED.load(Ordering::Relaxed)) else {
+        return ServerState::UNSYNCED;
+    };
+    // the upstream server when there is one, else what set the clock
+    let reference_id = sync.reference_id.unwrap_or_else(|| {
+        let mut label = [0; 4];
+        for (id, byte) in label.iter_mut().zip(source.label().bytes()) {
+            *id = byte;
+        }
+        label
+    });
+    ServerState {
+        quality: timesource::quality(uptime),
+        source_stratum: sync.stratum,
+        reference_id,
+        reference: rtc
+            .current_time_us()
+            .saturating_sub(uptime.saturating_sub(sync.at) * 1_000_000),
+        error: sync.error_at(uptime),
+    }
+}
+
+/// `name`'s A records, and AAAA too when built with IPv6.
+async fn resolve(stack: Stack<'static>, name: &str) -> Vec<IpAddress> {
+    info!("ntp_sync: DNS");
+    let mut addrs = Vec::new();
+    match stack.dns_query(name, DnsQueryType::A).await {
+        Ok(found) => addrs.extend(found),
+        Err(e) => error!("ntp_sync: DNS error: {}", e),
+    }
+    #[cfg(feature = "ipv6")]
+    match stack.dns_query(name, DnsQueryType::Aaaa).await {
+        Ok(found) => addrs.extend(found),
+        Err(e) => error!("ntp_sync: DNS AAAA error: {}", e),
+    }
+    addrs
+}
+
+/// Ask the DHCP server for NTP servers with a DHCPINFORM; the reply is
+/// addressed to the DHCP client port, which embassy-net's own DHCP socket
+/// swallows, so it's read off a raw socket instead. Empty if nothing answers
+/// or the network doesn't offer any.
+async fn dhcp_ntp_servers(stack: Stack<'static>, client_ip: Ipv4Address) -> Vec<IpAddress> {
+    let mac = *crate::MAC_ADDRESS.get().await;
+    let xid = RTCREF.get().await.current_time_us() as u32;
+    let Some(inform) = dhcp::inform(xid, mac, client_ip) else {
+        return Vec::new();
+    };
+
+    let mut raw_rx_meta = [RawPacketMetadata::EMPTY; 4];
+    let mut raw_rx_buffer = [0; 1536];
+    let mut raw_tx_meta = [RawPacketMetadata::EMPTY; 1];
+    let mut raw_tx_buffer = [0; 0];
+    let raw = RawSocket::new(
+        stack,
+        Some(IpVersion::Ipv4),
+        Some(IpProtocol::Udp),
+        &mut raw_rx_meta,
+        &mut raw_rx_buffer,
+        &mut raw_tx_meta,
+        &mut raw_tx_buffer,
+    );
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut rx_buffer = [0; 0];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0; 576];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if let Err(e) = socket.bind(DHCP_CLIENT_PORT) {
+        error!("dhcp_ntp_servers: bind: {:?}", e);
+        return Vec::new();
+    }
+    let server = (IpAddress::Ipv4(Ipv4Address::BROADCAST), DHCP_SERVER_PORT);
+    if let Err(e) = socket.send_to(&inform, server).await {
+        error!("dhcp_ntp_servers: send: {:?}", e);
+        return Vec::new();
+    }
+
+    let mut datagram = [0; 1536];
+    let reply = embassy_time::with_timeout(Duration::from_secs(DHCP_TIMEOUT), async {
+        loop {
+            if let Ok(len) = raw.recv(&mut datagram).await
+                && let Some(servers) = dhcp::ntp_servers(&datagram[..len], xid)
+            {
+                return servers;
+            }
+        }
+    })
+    .await;
+    match reply {
+        Ok(servers) => {
+            info!("dhcp_ntp_servers: offered {} servers", servers.len());
+            servers.into_iter().map(IpAddress::Ipv4).collect()
+        }
+        Err(_) => {
+            info!("dhcp_ntp_servers: no DHCPINFORM reply");
+            Vec::new()
         }
-        Timer::after_secs(NTP_INTERVAL).await;
     }
 }
 
 const NTP_RETRIES: u64 = 5;
-pub async fn get_time<U, T>(
-    // addr: net::SocketAddr,
-    addrs: heapless::Vec<IpAddress, { smoltcp::config::DNS_MAX_RESULT_COUNT }>,
+/// Exchanges per server per poll; the clock filter keeps the least delayed.
+const FILTER_EXCHANGES: usize = 4;
+
+/// Run the clock filter against every server in `addrs`, returning one
+/// sample per server that answered.
+pub async fn sample_servers<U, T>(
+    addrs: &[IpAddress],
     socket: &U,
     context: NtpContext<T>,
-) -> Result<NtpResult, anyhow::Error>
+) -> Vec<Sample>
 where
     U: NtpUdpSocket,
     T: NtpTimestampGenerator + Copy,
 {
-    for retry in 0..NTP_RETRIES {
-        for addr in addrs.iter() {
-            let saddr = SocketAddr::from((*addr, 123));
-            let req_result = sntp_send_request(saddr, socket, context).await;
-            if req_result.is_err() {
-                error!("sntp_send_request: {}", req_result.unwrap_err());
-                Timer::after_secs(retry).await;
-                continue;
+    let mut samples = Vec::new();
+    for addr in addrs.iter() {
+        let mut exchanges = Vec::new();
+        for retry in 0..NTP_RETRIES {
+            if exchanges.len() >= FILTER_EXCHANGES {
+                break;
             }
-            info!("sntp_send_request: request sent");
-
-            let resp = embassy_time::with_timeout(
-                embassy_time::Duration::from_secs(2),
-                sntp_process_response(saddr, socket, context, req_result.unwrap()),
-            )
-            .await;
-            match resp {
-                Ok(Ok(ntpr)) => {
-                    return Ok(ntpr);
+            match get_time(SocketAddr::from((*addr, 123)), socket, context).await {
+                Ok(ntpr) => {
+                    // its transmit time, and half the trip back
+                    let server = ntpr.sec() as i64 * 1_000_000
+                        + fraction_to_microseconds(ntpr.sec_fraction()) as i64
+                        + ntpr.roundtrip() as i64 / 2;
+                    exchanges.push(Sample {
+                        offset: ntpr.offset(),
+                        delay: ntpr.roundtrip(),
+                        stratum: ntpr.stratum(),
+                        precision: ntpr.precision(),
+                        boot_time: server - Instant::now().as_micros() as i64,
+                        reference_id: crate::sntp::reference_id((*addr).into()),
+                    })
                 }
-                Err(toerr) => {
-                    error!("get_time: attempt timeout {}", toerr);
-                }
-                Ok(Err(oerr)) => {
-                    error!("get_time: other error {}", oerr);
+                Err(e) => {
+                    error!("sample_servers: {}", e.to_string());
+                    Timer::after_secs(retry).await;
                 }
             }
         }
+        if let Some(sample) = clockfilter::filter(&exchanges) {
+            debug!(
+                "sample_servers: offset {}us delay {}us",
+                sample.offset, sample.delay
+            );
+            samples.push(sample);
+        }
+    }
+    samples
+}
+
+pub async fn get_time<U, T>(
+    saddr: SocketAddr,
+    socket: &U,
+    context: NtpContext<T>,
+) -> Result<NtpResult, anyhow::Error>
+where
+    U: NtpUdpSocket,
+    T: NtpTimestampGenerator + Copy,
+{
+    let req_result = sntp_send_request(saddr, socket, context)
+        .await
+        .map_err(|e| anyhow!("sntp_send_request: {:?}", e))?;
+    info!("sntp_send_request: request sent");
+
+    match embassy_time::with_timeout(
+        embassy_time::Duration::from_secs(2),
+        sntp_process_response(saddr, socket, context, req_result),
+    )
+    .await
+    {
+        Ok(Ok(ntpr)) => Ok(ntpr),
+        Err(_) => Err(anyhow!("get_time: attempt timeout")),
+        Ok(Err(oerr)) => Err(anyhow!("get_time: other error {:?}", oerr)),
     }
-    Err(anyhow!("get_time: exhausted retries"))
 }
@@ -1,132 +1,291 @@
 // ds323x::
 
-use crate::log::{error, info};
+use crate::aging::{self, Calibration, Report};
+use crate::clockchip::{self, ClockChip, Model};
+use crate::log::{debug, error, info, warn};
+use crate::timesource::{self, Reconcile, Source};
 use crate::{RTCREF, ntp::TIME_SYNCED};
+use alloc::boxed::Box;
+use alloc::string::ToString;
 use anyhow::{Result, anyhow};
-use chrono::{DateTime, NaiveDateTime, TimeDelta};
-use core::sync::atomic::Ordering;
-use ds323x::{DateTimeAccess, Ds323x, ic::DS3231, interface::I2cInterface};
-use embassy_time::{Duration, Timer};
+use chrono::{DateTime, NaiveDateTime};
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embassy_sync::once_lock::OnceLock;
+use embassy_time::{Duration, Instant, Timer};
 use esp_hal::{
     i2c::master::{Config, I2c},
     peripherals::*,
+    ram,
 };
 
+type RtcIc = Box<dyn ClockChip>;
+
+// whichever chip the first look at the bus found
+static MODEL: OnceLock<Option<Model>> = OnceLock::new();
+
+// how far off the chip may wander before an NTP sync rewrites it, µs
+const CHIP_REWRITE_THRESHOLD: i64 = 200_000;
+// further off than this the chip stopped or was set by hand, and its drift
+// history is no use
+const CHIP_RESTART_THRESHOLD: i64 = 10_000_000;
+// how often to read the chip while waiting for its seconds to tick over, ms
+const ROLLOVER_POLL: u64 = 5;
+
+// survives software and watchdog resets, which come every half hour
+#[ram(unstable(rtc_fast), unstable(persistent))]
+static mut CALIBRATION_WORDS: [i64; aging::WORDS] = [0; aging::WORDS];
+static CALIBRATION: Mutex<CriticalSectionRawMutex, RefCell<Option<Calibration>>> =
+    Mutex::new(RefCell::new(None));
+static CHIP_CHECKED: AtomicBool = AtomicBool::new(false);
+
+fn with_calibration<R>(f: impl FnOnce(&mut Calibration) -> R) -> R {
+    CALIBRATION.lock(|calibration| {
+        let mut calibration = calibration.borrow_mut();
+        #[allow(static_mut_refs)]
+        let words = unsafe { &mut CALIBRATION_WORDS };
+        let calibration =
+            calibration.get_or_insert_with(|| Calibration::from_words(words).unwrap_or_default());
+        let result = f(calibration);
+        *words = calibration.to_words();
+        result
+    })
+}
+
+/// What the aging calibration has learned so far.
+pub fn calibration_report() -> Report {
+    with_calibration(|calibration| calibration.report())
+}
+
 pub fn rtcread() -> Result<NaiveDateTime> {
     let mut rtcic = get_rtcic()?;
-    let temp = rtcic.temperature().expect("couldn't read rtc temperature");
-    info!("rtc says the temperature is {}", temp);
+    if let Ok(Some(temp)) = rtcic.temperature() {
+        info!("rtc says the temperature is {}", temp);
+    }
 
-    match rtcic.enable() {
+    match rtcic.start() {
         Ok(_) => {
             info!("rtcread: RTC enabled");
         }
         Err(e) => {
-            error!("rtcread: RTC enable error {}", e);
+            error!("rtcread: RTC enable error {}", e.to_string());
         }
     };
-    match rtcic.datetime() {
-        Ok(ndt) => Ok(ndt),
-        Err(ds323x::Error::InvalidInputData) => Err(anyhow!("InvalidInputData")),
-        Err(ds323x::Error::InvalidDeviceState) => Err(anyhow!("InvalidDeviceState")),
-        Err(ds323x::Error::Comm(e)) => Err(anyhow::Error::msg(e)),
-    }
+    rtcic.datetime()
 }
 
+/// Set the chip to the system clock, measuring it first so that a small
+/// correction keeps its drift history.
 pub async fn sys_to_ic() -> Result<()> {
     let mut rtcic = get_rtcic()?;
-
-    let dts = {
-        let r = &**RTCREF.get().await;
-        DateTime::from_timestamp_micros(r.current_time_us().try_into().unwrap())
-            .expect("couldn't create DateTime")
-            .naive_utc()
+    let offset = match rtcic.lost_time() {
+        Ok(false) => chip_offset(&mut rtcic, 0).await.ok(),
+        _ => None,
     };
+    rewrite(&mut rtcic, offset, 0).await
+}
 
-    match rtcic.set_datetime(&dts) {
-        Ok(_) => Ok(()),
-        Err(_e) => Err(anyhow!("couldn't set datetime")),
+// the chip now holds the system's time, to within `slop` µs
+fn note_chip_write(slop: u64) {
+    if let Some(sysrtc) = RTCREF.try_get() {
+        let now = sysrtc.time_since_boot().as_secs();
+        let error = timesource::best(now)
+            .map_or(timesource::RTC_CHIP_ERROR, |(_, sync)| sync.error_at(now));
+        timesource::record_chip_write(now, error.saturating_add(slop));
+    }
+}
+
+/// The chip minus the system clock plus `pending` µs of slew still to come,
+/// timed to the chip's seconds ticking over so it's good to a few ms.
+async fn chip_offset(rtcic: &mut RtcIc, pending: i64) -> Result<i64> {
+    let sysrtc = &**RTCREF.get().await;
+    let first = rtcic.datetime()?;
+    let deadline = Instant::now() + Duration::from_millis(1_100);
+    while Instant::now() < deadline {
+        Timer::after(Duration::from_millis(ROLLOVER_POLL)).await;
+        let ictime = rtcic.datetime()?;
+        if ictime != first {
+            let systime = sysrtc.current_time_us() as i64 + pending;
+            // it ticked over, on average, half a poll ago
+            let ictime = ictime.and_utc().timestamp_micros() + ROLLOVER_POLL as i64 * 500;
+            return Ok(ictime - systime);
+        }
     }
+    Err(anyhow!("chip seconds never ticked over"))
+}
+
+// writing the seconds restarts the chip's countdown, so write on the second
+async fn set_on_the_second(rtcic: &mut RtcIc, pending: i64) -> Result<()> {
+    let sysrtc = &**RTCREF.get().await;
+    let now = sysrtc.current_time_us() as i64 + pending;
+    Timer::after(Duration::from_micros((1_000_000 - now % 1_000_000) as u64)).await;
+    let now = sysrtc.current_time_us() as i64 + pending;
+    let dts = DateTime::from_timestamp((now + 500_000) / 1_000_000, 0)
+        .ok_or(anyhow!("couldn't create DateTime"))?
+        .naive_utc();
+    rtcic.set_datetime(&dts)
+}
+
+/// Set the chip on the second to the system clock plus `pending` µs, having
+/// measured it `offset` µs out: a small correction is allowed for in the
+/// drift history, while a chip that was far out, stopped or couldn't be
+/// measured starts it over.
+async fn rewrite(rtcic: &mut RtcIc, offset: Option<i64>, pending: i64) -> Result<()> {
+    set_on_the_second(rtcic, pending).await?;
+    note_chip_write(ROLLOVER_POLL * 1000);
+    with_calibration(|calibration| match offset {
+        Some(offset) if offset.abs() <= CHIP_RESTART_THRESHOLD => calibration.rewritten(-offset),
+        _ => calibration.restart(),
+    });
+    Ok(())
 }
 
-pub fn micros_to_ic(micros: u64) -> Result<()> {
+/// After an NTP sync with `pending` µs still to slew, and every
+/// [`aging::SAMPLE_INTERVAL`] after that, measure the chip against the fresh
+/// system clock: feed the aging calibration, rewrite the chip if it wandered,
+/// and retrim it once the drift is known.
+pub async fn ntp_synced(pending: i64) -> Result<()> {
+    let sysrtc = &**RTCREF.get().await;
+    let at = (sysrtc.current_time_us() as i64 + pending) / 1_000_000;
+    if CHIP_CHECKED.load(Ordering::Relaxed)
+        && !with_calibration(|calibration| calibration.wants_sample(at))
+    {
+        return Ok(());
+    }
     let mut rtcic = get_rtcic()?;
-    info!("micros_to_rtc: ds3231");
+    // chips without a trim still get their drift measured, for the logs
+    let aging = rtcic.aging_offset()?;
+    with_calibration(|calibration| calibration.adopt(aging.unwrap_or(0)));
 
-    let dts = {
-        DateTime::from_timestamp_micros(micros as i64)
-            .expect("couldn't create DateTime")
-            .naive_utc()
-    };
-    info!("micros_to_rtc: dts");
+    let lost = rtcic.lost_time().unwrap_or(false);
+    let offset = chip_offset(&mut rtcic, pending).await?;
+    if lost || offset.abs() > CHIP_REWRITE_THRESHOLD {
+        info!("ntp_synced: chip off by {}ms, rewriting it", offset / 1000);
+        rewrite(&mut rtcic, (!lost).then_some(offset), pending).await?;
+    } else {
+        debug!("ntp_synced: chip off by {}us", offset);
+        with_calibration(|calibration| calibration.observe(at, offset));
+        // as good as we just measured it
+        note_chip_write(offset.unsigned_abs() + ROLLOVER_POLL * 1000);
+    }
+    CHIP_CHECKED.store(true, Ordering::Relaxed);
 
-    match rtcic.set_datetime(&dts) {
-        Ok(_) => {
-            info!("micros_to_rtc: set_datetime OK");
-            Ok(())
-        }
-        Err(_e) => Err(anyhow!("couldn't set datetime")),
+    if let Some(aging) = aging
+        && let Some(trimmed) = with_calibration(|calibration| calibration.recommend())
+    {
+        rtcic.set_aging_offset(trimmed)?;
+        with_calibration(|calibration| calibration.applied(trimmed));
+        info!("ntp_synced: aging offset {} -> {}", aging, trimmed);
     }
+    info!("ntp_synced: calibration {:?}", calibration_report());
+    Ok(())
 }
 
 pub async fn ic_to_sys() -> Result<()> {
     info!("ic_to_sys: dts");
     let mut rtcic = get_rtcic()?;
     info!("ic_to_sys: set_current_time_us");
+    if rtcic.lost_time()? {
+        return Err(anyhow!("{} lost its time", rtcic.model().name()));
+    }
     let sysrtc = &**RTCREF.get().await;
+    let ictime = rtcic.datetime()?.and_utc().timestamp_micros();
     sysrtc.set_current_time_us(
-        rtcic
-            .datetime()
-            .expect("couldn't get datetime")
-            .and_utc()
-            .timestamp_micros()
+        ictime
             .try_into()
-            .unwrap(),
+            .map_err(|_| anyhow!("chip time before 1970"))?,
     );
     TIME_SYNCED.store(true, Ordering::Relaxed);
+    crate::ntp::STEPPED_ELSEWHERE.store(true, Ordering::Relaxed);
+    let now = sysrtc.time_since_boot().as_secs();
+    timesource::record(
+        Source::RtcChip,
+        now,
+        timesource::chip_error(now),
+        None,
+        None,
+    );
 
     Ok(())
 }
 
-fn get_rtcic() -> Result<Ds323x<I2cInterface<I2c<'static, esp_hal::Async>>, DS3231>> {
+fn i2c() -> I2c<'static, esp_hal::Async> {
     #[cfg(all(feature = "selfwire", not(feature = "esp32")))]
-    return Ok(Ds323x::new_ds3231(
-        I2c::new(unsafe { I2C0::steal() }, Config::default())
-            .expect("couldn't init I2C")
-            .with_sda(unsafe { GPIO6::steal() })
-            .with_scl(unsafe { GPIO7::steal() })
-            .into_async(),
-    ));
+    return I2c::new(unsafe { I2C0::steal() }, Config::default())
+        .expect("couldn't init I2C")
+        .with_sda(unsafe { GPIO6::steal() })
+        .with_scl(unsafe { GPIO7::steal() })
+        .into_async();
 
     #[cfg(feature = "tidbyt")]
-    return Ok(Ds323x::new_ds3231(
-        I2c::new(unsafe { I2C0::steal() }, Config::default())
-            .expect("couldn't init I2C")
-            .with_sda(unsafe { GPIO13::steal() })
-            .with_scl(unsafe { GPIO14::steal() })
-            .into_async(),
-    ));
+    return I2c::new(unsafe { I2C0::steal() }, Config::default())
+        .expect("couldn't init I2C")
+        .with_sda(unsafe { GPIO13::steal() })
+        .with_scl(unsafe { GPIO14::steal() })
+        .into_async();
+}
+
+/// The RTC chip, whichever it is; the bus is probed on first use, at boot.
+fn get_rtcic() -> Result<RtcIc> {
+    let mut i2c = i2c();
+    let model = MODEL.get_or_init(|| {
+        let model = clockchip::detect(&mut i2c);
+        if let Some(model) = model {
+            info!("found {} RTC at {:#x}", model.name(), model.address());
+        } else {
+            warn!("no RTC chip found");
+        }
+        model
+    });
+    model
+        .map(|model| model.driver(i2c))
+        .ok_or(anyhow!("no RTC chip"))
 }
 
 const DESYNC_INTERVAL: u64 = 600;
-const DESYNC_THRESHOLD: TimeDelta = TimeDelta::new(10, 0).unwrap();
+// the chip only keeps whole seconds
+const DESYNC_THRESHOLD: u64 = 2_000_000;
+/// Every `DESYNC_INTERVAL`, compare the chip with the system clock and, if
+/// they disagree, overwrite whichever is backed by the worse source.
 #[embassy_executor::task]
 #[cfg(feature = "rtcchip")]
 pub async fn desync_failsafe() {
+    let sysrtc = &**RTCREF.get().await;
     loop {
-        let mut rtcic = get_rtcic().expect("couldn't get RTC IC");
-        let sysrtc = &**RTCREF.get().await;
-
-        let systime = DateTime::from_timestamp_micros(sysrtc.current_time_us().try_into().unwrap())
-            .expect("couldn't create DateTime");
-
-        let ictime = rtcic.datetime().expect("couldn't get datetime").and_utc();
-
-        // let delta = ictime - systime;
-        if (ictime - systime).abs() > DESYNC_THRESHOLD {
-            // bye
-        }
-
         Timer::after(Duration::from_secs(DESYNC_INTERVAL)).await;
+        let read = get_rtcic().and_then(|mut rtcic| Ok((rtcic.datetime()?, rtcic.lost_time()?)));
+        let (ictime, lost) = match read {
+            Ok((ictime, lost)) => (ictime.and_utc().timestamp_micros(), lost),
+            Err(e) => {
+                warn!("desync_failsafe: no RTC chip: {}", e.to_string());
+                continue;
+            }
+        };
+        let systime = sysrtc.current_time_us() as i64;
+        let delta = ictime - systime;
+        let now = sysrtc.time_since_boot().as_secs();
+        match timesource::reconcile(delta, lost, DESYNC_THRESHOLD, now) {
+            Reconcile::InSync => debug!("desync_failsafe: chip within {}ms", delta / 1000),
+            Reconcile::SystemWins => {
+                info!(
+                    "desync_failsafe: chip off by {}ms, rewriting it",
+                    -delta / 1000
+                );
+                if let Err(e) = sys_to_ic().await {
+                    error!("desync_failsafe: {}", e.to_string());
+                }
+            }
+            Reconcile::ChipWins => {
+                info!(
+                    "desync_failsafe: system clock off by {}ms, restoring from chip",
+                    delta / 1000
+                );
+                if let Err(e) = ic_to_sys().await {
+                    error!("desync_failsafe: {}", e.to_string());
+                }
+            }
+            Reconcile::Neither => debug!("desync_failsafe: chip lost its time, nothing to set it"),
+        }
     }
 }
@@ -0,0 +1,258 @@
+//! Golden-image tests: each screen state is rendered into a [`Frame`] the
+//! size of the panel and compared with a PPM of it under `fixtures/golden`.
+//!
+//! After a deliberate layout change, rerun with `BLESS=1` to rewrite the
+//! goldens, and look them over before committing. A mismatch leaves the
+//! frame it got next to the golden as `<name>.actual.ppm`.
+
+extern crate std;
+
+use alloc::{format, string::String, vec::Vec};
+use core::convert::Infallible;
+use std::path::PathBuf;
+
+use embedded_graphics::{
+    Pixel,
+    draw_target::DrawTarget,
+    geometry::{OriginDimensions, Size},
+    pixelcolor::{Rgb888, RgbColor},
+};
+use jiff::{ToSpan, Zoned};
+
+use crate::{
+    airquality::AirQualityCache,
+    clockformat::ClockFormat,
+    forecast::{WMOCode, WeatherForecast, WeatherForecastCache},
+    nightscout::BgReading,
+    timers::Timers,
+    timesource::Quality,
+    widget::{Bg, Clock, Context, DISPLAY_H, DISPLAY_W, Forecast, Widget},
+};
+
+const W: usize = DISPLAY_W as usize;
+const H: usize = DISPLAY_H as usize;
+
+/// The panel in memory; like the HUB75 framebuffer, it drops pixels that
+/// fall off the edge.
+struct Frame([Rgb888; W * H]);
+
+impl Frame {
+    fn new() -> Self {
+        Self([Rgb888::BLACK; W * H])
+    }
+
+    fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", W, H).into_bytes();
+        for color in &self.0 {
+            ppm.extend([color.r(), color.g(), color.b()]);
+        }
+        ppm
+    }
+
+    fn from_ppm(ppm: &[u8]) -> Option<Self> {
+        let header = format!("P6\n{} {}\n255\n", W, H);
+        let pixels = ppm.strip_prefix(header.as_bytes())?;
+        if pixels.len() != W * H * 3 {
+            return None;
+        }
+        let mut frame = Self::new();
+        for (color, &[r, g, b]) in frame.0.iter_mut().zip(pixels.as_chunks::<3>().0) {
+            *color = Rgb888::new(r, g, b);
+        }
+        Some(frame)
+    }
+}
+
+impl OriginDimensions for Frame {
+    fn size(&self) -> Size {
+        Size::new(DISPLAY_W, DISPLAY_H)
+    }
+}
+
+impl DrawTarget for Frame {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if (0..W as i32).contains(&point.x) && (0..H as i32).contains(&point.y) {
+                self.0[point.y as usize * W + point.x as usize] = color;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn golden(name: &str, suffix: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "fixtures", "golden"]
+        .iter()
+        .collect::<PathBuf>()
+        .join(format!("{}{}", name, suffix))
+}
+
+/// Compare `frame` with the golden called `name`, or replace it when
+/// blessing.
+fn check(name: &str, frame: &Frame) {
+    let path = golden(name, ".ppm");
+    let actual = golden(name, ".actual.ppm");
+    if std::env::var_os("BLESS").is_some() {
+        std::fs::write(&path, frame.to_ppm()).unwrap();
+        let _ = std::fs::remove_file(&actual);
+        return;
+    }
+    let expected = std::fs::read(&path)
+        .ok()
+        .and_then(|ppm| Frame::from_ppm(&ppm))
+        .unwrap_or_else(|| panic!("no golden at {}; run with BLESS=1", path.display()));
+    let diffs: Vec<_> = (0..W * H)
+        .filter(|&i| frame.0[i] != expected.0[i])
+        .collect();
+    if let Some(&first) = diffs.first() {
+        std::fs::write(&actual, frame.to_ppm()).unwrap();
+        panic!(
+            "{}: {} pixels differ, first at ({}, {}): expected {:?}, got {:?}; see {}",
+            name,
+            diffs.len(),
+            first % W,
+            first / W,
+            expected.0[first],
+            frame.0[first],
+            actual.display()
+        );
+    }
+    let _ = std::fs::remove_file(&actual);
+}
+
+fn at(s: &str) -> Zoned {
+    s.parse().unwrap()
+}
+
+// a Wednesday morning and evening in spring
+const MORNING: &str = "2026-03-18T09:41:00-07:00[-07:00]";
+const EVENING: &str = "2026-03-18T21:41:00-07:00[-07:00]";
+
+/// The hours around `now`: a sunny morning, then a drizzly night.
+fn forecasts(now: &Zoned) -> WeatherForecastCache {
+    let mut forecasts = WeatherForecastCache::new();
+    let midnight = now.start_of_day().unwrap();
+    for hour in 0..30 {
+        let timespan = midnight.checked_add(hour.hours()).unwrap();
+        let is_day = (7..19).contains(&timespan.hour());
+        let temperature = if is_day { 64.0 } else { 43.0 };
+        forecasts.add(WeatherForecast {
+            timespan,
+            temperature,
+            relative_humidity: if is_day { 48 } else { 91 },
+            apparent_temperature: temperature - 3.0,
+            precipitation: if is_day { 0.0 } else { 0.3 },
+            precipitation_probability: if is_day { 5 } else { 65 },
+            weather_code: if is_day {
+                WMOCode::MainlyClear
+            } else {
+                WMOCode::LightDrizzle
+            },
+            is_day,
+            sunshine_duration: if is_day { 3_400.0 } else { 0.0 },
+            wind_speed: 7.0,
+            wind_gusts: 12.0,
+            wind_direction: 200,
+            uv_index: if is_day { 4.0 } else { 0.0 },
+        });
+    }
+    forecasts
+}
+
+/// `widgets` drawn in turn from `now`, a BG of `bg` taken `age` minutes
+/// before it, and `forecasts`.
+fn render(
+    widgets: &[&dyn Widget<Frame>],
+    now: &Zoned,
+    bg: Option<(u64, i64)>,
+    forecasts: &WeatherForecastCache,
+) -> Frame {
+    let format = ClockFormat::default();
+    let air_quality = AirQualityCache::new();
+    let timers = Timers::new();
+    let reading = bg.map(|(bg, age)| BgReading {
+        bg,
+        units: String::from("mg/dl"),
+        timestamp: now.checked_sub(age.minutes()).unwrap(),
+    });
+    let ctx = Context {
+        now,
+        synced: true,
+        quality: Quality::Good,
+        format: &format,
+        locale: &crate::locale::ENGLISH,
+        bg: reading.as_ref(),
+        forecasts,
+        air_quality: &air_quality,
+        agenda: &[],
+        world: &[],
+        timers: &timers,
+        alarm: None,
+    };
+    let mut frame = Frame::new();
+    for widget in widgets.iter().filter(|widget| widget.fresh(&ctx)) {
+        widget.draw_clipped(&ctx, &mut frame).unwrap();
+    }
+    frame
+}
+
+#[test]
+fn logo() {
+    let mut frame = Frame::new();
+    let logo = crate::drawing::LOGO.get().frames().next().unwrap();
+    crate::drawing::draw_logo(&logo, "a1b2c3", "0.8.2", &mut frame).unwrap();
+    check("logo", &frame);
+}
+
+#[test]
+fn clock() {
+    let now = at(MORNING);
+    let empty = WeatherForecastCache::new();
+    check("clock", &render(&[&Clock, &Bg], &now, None, &empty));
+}
+
+#[test]
+fn clock_with_bg() {
+    let now = at(MORNING);
+    let empty = WeatherForecastCache::new();
+    for (name, bg, age) in [
+        ("bg_superlow", 52, 2),
+        ("bg_low", 71, 2),
+        ("bg_okay", 112, 2),
+        ("bg_high", 183, 2),
+        ("bg_superhigh", 287, 2),
+        ("bg_stale", 112, 20),
+    ] {
+        check(name, &render(&[&Clock, &Bg], &now, Some((bg, age)), &empty));
+    }
+}
+
+#[test]
+fn forecast_day() {
+    let now = at(MORNING);
+    let frame = render(
+        &[&Clock, &Bg, &Forecast],
+        &now,
+        Some((112, 2)),
+        &forecasts(&now),
+    );
+    check("forecast_day", &frame);
+}
+
+#[test]
+fn forecast_night() {
+    let now = at(EVENING);
+    let frame = render(
+        &[&Clock, &Bg, &Forecast],
+        &now,
+        Some((112, 2)),
+        &forecasts(&now),
+    );
+    check("forecast_night", &frame);
+}
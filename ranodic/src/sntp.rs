@@ -0,0 +1,233 @@
+//! The server side of SNTP (RFC 4330), so the panel can be the LAN's clock.
+//! Replies carry what [`crate::timesource`] knows: our stratum is one below
+//! the source's, the root dispersion is its current error, and a clock that
+//! has gone bad says so in the leap indicator. Until the clock is set at all,
+//! clients get a kiss-o'-death and keep looking.
+
+use core::net::IpAddr;
+
+use md5::{Digest, Md5};
+
+use crate::timesource::Quality;
+
+pub const PACKET_LEN: usize = 48;
+/// Stratum claimed when the time came from something without one: the RTC
+/// chip, an HTTP `Date:` header or RTC memory. Like an undisciplined local
+/// clock, usable only when there's nothing better.
+pub const LOCAL_STRATUM: u8 = 10;
+/// RFC 5905: stratum 16 is unsynchronized.
+pub const UNSYNCED_STRATUM: u8 = 16;
+/// The RTC counts whole µs, about 2^-20 s.
+const PRECISION: i8 = -20;
+// seconds from 1900, the NTP era, to 1970
+const UNIX_EPOCH: u64 = 2_208_988_800;
+
+const LEAP_NONE: u8 = 0;
+const LEAP_ALARM: u8 = 3;
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+
+/// The clock as the reply describes it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ServerState {
+    pub quality: Quality,
+    /// Stratum of the source we synced to; `None` for ones without.
+    pub source_stratum: Option<u8>,
+    /// Four ASCII characters naming the source.
+    pub reference_id: [u8; 4],
+    /// Unix µs when the source last set the clock.
+    pub reference: u64,
+    /// Current worst-case error, µs.
+    pub error: u64,
+}
+
+impl ServerState {
+    /// What a clock that was never set answers.
+    pub const UNSYNCED: Self = Self {
+        quality: Quality::Unsynced,
+        source_stratum: None,
+        reference_id: *b"INIT",
+        reference: 0,
+        error: 0,
+    };
+
+    pub fn stratum(&self) -> u8 {
+        match self.quality {
+            Quality::Unsynced => 0,
+            Quality::Poor => UNSYNCED_STRATUM,
+            _ => self
+                .source_stratum
+                .map_or(LOCAL_STRATUM, |stratum| stratum.saturating_add(1))
+                .min(UNSYNCED_STRATUM - 1),
+        }
+    }
+}
+
+/// The reply to `request`, received at unix µs `received` and to go out at
+/// `transmit`; `None` for anything that isn't an NTP client request.
+pub fn respond(
+    request: &[u8],
+    state: &ServerState,
+    received: u64,
+    transmit: u64,
+) -> Option<[u8; PACKET_LEN]> {
+    if request.len() < PACKET_LEN || request[0] & 0x07 != MODE_CLIENT {
+        return None;
+    }
+    let version = (request[0] >> 3) & 0x07;
+    if !(1..=4).contains(&version) {
+        return None;
+    }
+    let leap = match state.quality {
+        Quality::Unsynced | Quality::Poor => LEAP_ALARM,
+        Quality::Fair | Quality::Good => LEAP_NONE,
+    };
+    let mut reply = [0; PACKET_LEN];
+    reply[0] = leap << 6 | version << 3 | MODE_SERVER;
+    reply[1] = state.stratum();
+    // poll, copied from the request
+    reply[2] = request[2];
+    reply[3] = PRECISION as u8;
+    // root delay stays zero: we don't know the source's
+    reply[8..12].copy_from_slice(&short(state.error).to_be_bytes());
+    reply[12..16].copy_from_slice(&state.reference_id);
+    if state.quality != Quality::Unsynced {
+        reply[16..24].copy_from_slice(&timestamp(state.reference).to_be_bytes());
+    }
+    // originate is the client's transmit timestamp, echoed
+    reply[24..32].copy_from_slice(&request[40..48]);
+    reply[32..40].copy_from_slice(&timestamp(received).to_be_bytes());
+    reply[40..48].copy_from_slice(&timestamp(transmit).to_be_bytes());
+    Some(reply)
+}
+
+/// Unix µs as an NTP timestamp: 32.32 fixed-point seconds since 1900.
+pub fn timestamp(unix_us: u64) -> u64 {
+    let seconds = unix_us / 1_000_000 + UNIX_EPOCH;
+    let fraction = ((unix_us % 1_000_000) << 32) / 1_000_000;
+    seconds << 32 | fraction
+}
+
+// µs as 16.16 fixed-point seconds, saturating
+fn short(us: u64) -> u32 {
+    ((us << 16) / 1_000_000).min(u32::MAX as u64) as u32
+}
+
+/// RFC 5905's reference ID for an upstream server: its IPv4 address, or the
+/// first four bytes of the MD5 of its IPv6 one.
+pub fn reference_id(server: IpAddr) -> [u8; 4] {
+    match server {
+        IpAddr::V4(v4) => v4.octets(),
+        IpAddr::V6(v6) => {
+            let digest = Md5::digest(v6.octets());
+            [digest[0], digest[1], digest[2], digest[3]]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2026-03-18T20:15:30Z
+    const NOW: u64 = 1_773_864_930_000_000;
+
+    fn request(version: u8) -> [u8; PACKET_LEN] {
+        let mut request = [0; PACKET_LEN];
+        request[0] = version << 3 | MODE_CLIENT;
+        request[2] = 6;
+        request[40..48].copy_from_slice(&0x1234_5678_9abc_def0u64.to_be_bytes());
+        request
+    }
+
+    fn synced(quality: Quality, source_stratum: Option<u8>) -> ServerState {
+        ServerState {
+            quality,
+            source_stratum,
+            reference_id: *b"NTP\0",
+            reference: NOW - 60_000_000,
+            error: 25_000,
+        }
+    }
+
+    fn word(reply: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(reply[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn ntp_timestamps() {
+        assert_eq!(timestamp(0), UNIX_EPOCH << 32);
+        assert_eq!(timestamp(500_000), UNIX_EPOCH << 32 | 0x8000_0000);
+        assert_eq!(short(1_000_000), 0x0001_0000);
+        assert_eq!(short(25_000), 1638);
+    }
+
+    #[test]
+    fn synced_reply() {
+        let reply = respond(&request(4), &synced(Quality::Good, Some(2)), NOW, NOW + 150).unwrap();
+        // LI 0, VN 4, mode 4
+        assert_eq!(reply[0], 0x24);
+        assert_eq!(reply[1], 3);
+        assert_eq!(reply[2], 6);
+        assert_eq!(reply[3] as i8, PRECISION);
+        assert_eq!(word(&reply, 8), short(25_000));
+        assert_eq!(&reply[12..16], b"NTP\0");
+        assert_eq!(&reply[16..24], &timestamp(NOW - 60_000_000).to_be_bytes());
+        assert_eq!(&reply[24..32], &request(4)[40..48]);
+        assert_eq!(&reply[32..40], &timestamp(NOW).to_be_bytes());
+        assert_eq!(&reply[40..48], &timestamp(NOW + 150).to_be_bytes());
+    }
+
+    #[test]
+    fn version_is_echoed() {
+        let reply = respond(&request(3), &synced(Quality::Good, Some(1)), NOW, NOW).unwrap();
+        assert_eq!(reply[0], 0x1c);
+    }
+
+    #[test]
+    fn stratum_follows_the_source() {
+        assert_eq!(synced(Quality::Good, Some(0)).stratum(), 1);
+        assert_eq!(synced(Quality::Fair, None).stratum(), LOCAL_STRATUM);
+        assert_eq!(synced(Quality::Good, Some(15)).stratum(), 15);
+        assert_eq!(synced(Quality::Poor, Some(2)).stratum(), UNSYNCED_STRATUM);
+    }
+
+    #[test]
+    fn bad_clock_raises_the_alarm() {
+        let reply = respond(&request(4), &synced(Quality::Poor, Some(2)), NOW, NOW).unwrap();
+        assert_eq!(reply[0] >> 6, LEAP_ALARM);
+        assert_eq!(reply[1], UNSYNCED_STRATUM);
+    }
+
+    #[test]
+    fn unset_clock_kisses_off() {
+        let reply = respond(&request(4), &ServerState::UNSYNCED, NOW, NOW).unwrap();
+        assert_eq!(reply[0] >> 6, LEAP_ALARM);
+        assert_eq!(reply[1], 0);
+        assert_eq!(&reply[12..16], b"INIT");
+        assert_eq!(&reply[16..24], &[0; 8]);
+    }
+
+    #[test]
+    fn reference_ids_name_the_server() {
+        assert_eq!(reference_id("192.0.2.7".parse().unwrap()), [192, 0, 2, 7]);
+        assert_eq!(
+            reference_id("2001:db8::1".parse().unwrap()),
+            [57, 171, 155, 55]
+        );
+        assert_eq!(reference_id("::1".parse().unwrap()), [207, 64, 77, 200]);
+    }
+
+    #[test]
+    fn ignores_what_isnt_a_client_request() {
+        let mut server = request(4);
+        server[0] = 4 << 3 | MODE_SERVER;
+        assert_eq!(respond(&server, &ServerState::UNSYNCED, NOW, NOW), None);
+        assert_eq!(respond(&request(0), &ServerState::UNSYNCED, NOW, NOW), None);
+        assert_eq!(
+            respond(&request(4)[..40], &ServerState::UNSYNCED, NOW, NOW),
+            None
+        );
+    }
+}
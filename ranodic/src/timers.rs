@@ -0,0 +1,509 @@
+//! A countdown to a date ("14d 3h to launch"), a kitchen timer and a
+//! stopwatch, driven by line commands from the [`crate::console`]:
+//!
+//! - `countdown 2026-12-24` or `countdown 2026-12-24T18:00 launch`, in the
+//!   display zone; `countdown clear`
+//! - `timer 5m` (or `1h30m`, `90s`, or a bare number of minutes), `timer
+//!   pause`, `timer resume`, `timer cancel`
+//! - `stopwatch start`, `stopwatch stop`, `stopwatch reset`
+//! - `status`
+//!
+//! State lives here rather than in the painter so it carries on while other
+//! pages are up. When the countdown or the timer runs out the panel flashes
+//! for [`FLASH`], then it clears itself. Nothing here reads the clock; every
+//! call is handed `now`.
+
+use core::cell::RefCell;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use anyhow::{Result, anyhow};
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embedded_graphics::{
+    Drawable,
+    draw_target::DrawTarget,
+    geometry::Point,
+    mono_font::{
+        MonoTextStyle,
+        iso_8859_1::{FONT_4X6, FONT_5X8, FONT_6X10},
+    },
+    pixelcolor::{Rgb888, RgbColor},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+};
+use jiff::{
+    SignedDuration, Timestamp,
+    civil::{Date, DateTime},
+    tz::TimeZone,
+};
+
+use crate::clockformat::fit;
+
+/// How long an expired countdown or timer flashes before it goes away.
+pub const FLASH: SignedDuration = SignedDuration::from_secs(60);
+// on for half of this, off for the other half
+const FLASH_PERIOD_MS: i64 = 500;
+const DISPLAY_W: u32 = 64;
+const DISPLAY_H: i32 = 32;
+
+const COUNTDOWN: Rgb888 = Rgb888::new(255, 255, 255);
+const TIMER: Rgb888 = Rgb888::new(240, 170, 30);
+const STOPWATCH: Rgb888 = Rgb888::new(40, 180, 220);
+const STOPPED: Rgb888 = Rgb888::new(120, 120, 120);
+const FLASH_BG: Rgb888 = Rgb888::new(240, 170, 30);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Countdown {
+    pub target: Timestamp,
+    /// What it's counting down to; may be empty.
+    pub label: String,
+}
+
+impl Countdown {
+    pub fn text(&self, now: Timestamp) -> String {
+        let left = self.target.duration_since(now);
+        match (left.is_positive(), self.label.is_empty()) {
+            (true, true) => span_text(left),
+            (true, false) => format!("{} to {}", span_text(left), self.label),
+            (false, true) => "now!".to_string(),
+            (false, false) => format!("{}!", self.label),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KitchenTimer {
+    Running { end: Timestamp },
+    Paused { left: SignedDuration },
+}
+
+impl KitchenTimer {
+    pub fn left(&self, now: Timestamp) -> SignedDuration {
+        match self {
+            Self::Running { end } => end.duration_since(now),
+            Self::Paused { left } => *left,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Stopwatch {
+    /// When it was last started; `None` while stopped.
+    since: Option<Timestamp>,
+    /// Time run before that.
+    banked: SignedDuration,
+}
+
+impl Stopwatch {
+    pub fn elapsed(&self, now: Timestamp) -> SignedDuration {
+        self.banked
+            + self
+                .since
+                .map_or(SignedDuration::ZERO, |since| now.duration_since(since))
+    }
+
+    pub fn running(&self) -> bool {
+        self.since.is_some()
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Timers {
+    pub countdown: Option<Countdown>,
+    pub timer: Option<KitchenTimer>,
+    /// `None` once reset.
+    pub stopwatch: Option<Stopwatch>,
+}
+
+impl Timers {
+    pub const fn new() -> Self {
+        Self {
+            countdown: None,
+            timer: None,
+            stopwatch: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.countdown.is_none() && self.timer.is_none() && self.stopwatch.is_none()
+    }
+
+    /// Drop a countdown or timer that has finished flashing.
+    pub fn tidy(&mut self, now: Timestamp) {
+        if self
+            .countdown
+            .as_ref()
+            .is_some_and(|countdown| countdown.target.duration_since(now) <= -FLASH)
+        {
+            self.countdown = None;
+        }
+        if self.timer.is_some_and(|timer| timer.left(now) <= -FLASH) {
+            self.timer = None;
+        }
+    }
+
+    /// Something ran out within the last [`FLASH`].
+    pub fn flashing(&self, now: Timestamp) -> bool {
+        let expired = |left: SignedDuration| !left.is_positive() && left > -FLASH;
+        self.countdown
+            .as_ref()
+            .is_some_and(|countdown| expired(countdown.target.duration_since(now)))
+            || self.timer.is_some_and(|timer| expired(timer.left(now)))
+    }
+
+    /// The lit half of a flash.
+    pub fn flash_on(&self, now: Timestamp) -> bool {
+        self.flashing(now) && now.as_millisecond().rem_euclid(FLASH_PERIOD_MS) < FLASH_PERIOD_MS / 2
+    }
+
+    /// One line per timer, with the colour it's drawn in.
+    pub fn rows(&self, now: Timestamp) -> Vec<(String, Rgb888)> {
+        let mut rows = Vec::new();
+        if let Some(countdown) = &self.countdown {
+            rows.push((countdown.text(now), COUNTDOWN));
+        }
+        if let Some(timer) = &self.timer {
+            let color = match timer {
+                KitchenTimer::Running { .. } => TIMER,
+                KitchenTimer::Paused { .. } => STOPPED,
+            };
+            rows.push((clock_text(timer.left(now), false), color));
+        }
+        if let Some(stopwatch) = &self.stopwatch {
+            let color = if stopwatch.running() {
+                STOPWATCH
+            } else {
+                STOPPED
+            };
+            rows.push((clock_text(stopwatch.elapsed(now), true), color));
+        }
+        rows
+    }
+
+    /// Carry out one console command; the reply says what's running now.
+    pub fn apply(&mut self, line: &str, now: Timestamp, tz: &TimeZone) -> Result<String> {
+        self.tidy(now);
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("status");
+        let argument = words.next();
+        match (command, argument) {
+            ("status", None) => {}
+            ("countdown", Some("clear")) => self.countdown = None,
+            ("countdown", Some(when)) => {
+                let label = words.collect::<Vec<_>>().join(" ");
+                self.countdown = Some(Countdown {
+                    target: target(when, tz)?,
+                    label,
+                });
+            }
+            ("timer", Some("cancel")) => self.timer = None,
+            ("timer", Some("pause")) => {
+                if let Some(KitchenTimer::Running { end }) = self.timer
+                    && end > now
+                {
+                    self.timer = Some(KitchenTimer::Paused {
+                        left: end.duration_since(now),
+                    });
+                }
+            }
+            ("timer", Some("resume")) => {
+                if let Some(KitchenTimer::Paused { left }) = self.timer {
+                    self.timer = Some(KitchenTimer::Running { end: now + left });
+                }
+            }
+            ("timer", Some(length)) => {
+                let length = duration(length)?;
+                if !length.is_positive() {
+                    return Err(anyhow!("timer needs a length"));
+                }
+                self.timer = Some(KitchenTimer::Running { end: now + length });
+            }
+            ("stopwatch", Some("start")) => {
+                let stopwatch = self.stopwatch.get_or_insert_default();
+                stopwatch.since.get_or_insert(now);
+            }
+            ("stopwatch", Some("stop")) => {
+                if let Some(stopwatch) = &mut self.stopwatch {
+                    stopwatch.banked = stopwatch.elapsed(now);
+                    stopwatch.since = None;
+                }
+            }
+            ("stopwatch", Some("reset")) => self.stopwatch = None,
+            _ => return Err(anyhow!("unknown command {}", line.trim())),
+        }
+        Ok(self.status(now))
+    }
+
+    fn status(&self, now: Timestamp) -> String {
+        let mut lines = Vec::new();
+        if let Some(countdown) = &self.countdown {
+            lines.push(format!("countdown: {}", countdown.text(now)));
+        }
+        if let Some(timer) = &self.timer {
+            let paused = matches!(timer, KitchenTimer::Paused { .. });
+            lines.push(format!(
+                "timer: {}{}",
+                clock_text(timer.left(now), false),
+                if paused { " (paused)" } else { "" }
+            ));
+        }
+        if let Some(stopwatch) = &self.stopwatch {
+            lines.push(format!(
+                "stopwatch: {}{}",
+                clock_text(stopwatch.elapsed(now), true),
+                if stopwatch.running() {
+                    ""
+                } else {
+                    " (stopped)"
+                }
+            ));
+        }
+        if lines.is_empty() {
+            return "idle".to_string();
+        }
+        lines.join("\n")
+    }
+}
+
+/// `2026-12-24` (midnight) or `2026-12-24T18:00` in `tz`.
+fn target(when: &str, tz: &TimeZone) -> Result<Timestamp> {
+    let civil = match when.parse::<DateTime>() {
+        Ok(civil) => civil,
+        Err(_) => when
+            .parse::<Date>()
+            .map_err(|_| anyhow!("can't count down to {}", when))?
+            .to_datetime(jiff::civil::Time::midnight()),
+    };
+    Ok(civil
+        .to_zoned(tz.clone())
+        .map_err(anyhow::Error::msg)?
+        .timestamp())
+}
+
+/// `5m`, `1h30m`, `90s`, or a bare number of minutes.
+fn duration(text: &str) -> Result<SignedDuration> {
+    if let Ok(minutes) = text.parse::<i64>() {
+        return Ok(SignedDuration::from_mins(minutes));
+    }
+    text.parse().map_err(|_| anyhow!("can't time {}", text))
+}
+
+/// The two biggest units left, rounded up: `14d 3h`, `3h 12m`, `12m 05s`,
+/// `45s`.
+pub fn span_text(left: SignedDuration) -> String {
+    let secs = (left.as_millis() as i64 + 999).div_euclid(1000).max(0);
+    let (days, hours, mins) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60);
+    match (days, hours, mins) {
+        (0, 0, 0) => format!("{}s", secs),
+        (0, 0, _) => format!("{}m {:02}s", mins, secs % 60),
+        (0, _, _) => format!("{}h {:02}m", hours, mins),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
+
+/// `4:59`, `1:02:03`; with `tenths`, `4:59.3`. A timer counts up past zero
+/// as `-0:12` so it's clear how long ago it went off.
+pub fn clock_text(duration: SignedDuration, tenths: bool) -> String {
+    let sign = if duration.is_negative() { "-" } else { "" };
+    let millis = duration.as_millis().unsigned_abs() as u64;
+    // a running timer reads its starting length for the first second
+    let secs = if tenths {
+        millis / 1000
+    } else {
+        millis.div_ceil(1000)
+    };
+    let (hours, mins) = (secs / 3600, secs / 60 % 60);
+    let mut text = if hours > 0 {
+        format!("{}{}:{:02}:{:02}", sign, hours, mins, secs % 60)
+    } else {
+        format!("{}{}:{:02}", sign, mins, secs % 60)
+    };
+    if tenths {
+        text.push_str(&format!(".{}", millis / 100 % 10));
+    }
+    text
+}
+
+/// One centred row per timer across the whole display, inverted on the lit
+/// half of a flash.
+pub fn draw_timers<D>(timers: &Timers, now: Timestamp, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    let rows = timers.rows(now);
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let flash = timers.flash_on(now);
+    if flash {
+        target.clear(FLASH_BG)?;
+    }
+    let widest = rows
+        .iter()
+        .map(|(text, _)| text.as_str())
+        .max_by_key(|text| text.chars().count())
+        .unwrap_or_default();
+    let font = fit(widest, &[&FONT_6X10, &FONT_5X8, &FONT_4X6], DISPLAY_W);
+    let row_h = DISPLAY_H / rows.len() as i32;
+    let centred = TextStyleBuilder::new()
+        .alignment(Alignment::Center)
+        .baseline(Baseline::Middle)
+        .build();
+    for (idx, (text, color)) in rows.iter().enumerate() {
+        let color = if flash { Rgb888::BLACK } else { *color };
+        Text::with_text_style(
+            text,
+            Point::new(DISPLAY_W as i32 / 2, idx as i32 * row_h + row_h / 2),
+            MonoTextStyle::new(font, color),
+            centred,
+        )
+        .draw(target)?;
+    }
+    Ok(())
+}
+
+static TIMERS: Mutex<CriticalSectionRawMutex, RefCell<Timers>> =
+    Mutex::new(RefCell::new(Timers::new()));
+
+/// What's running at `now`.
+pub fn get(now: Timestamp) -> Timers {
+    TIMERS.lock(|timers| {
+        let mut timers = timers.borrow_mut();
+        timers.tidy(now);
+        timers.clone()
+    })
+}
+
+/// Run a console command against the shared timers.
+pub fn command(line: &str, now: Timestamp, tz: &TimeZone) -> Result<String> {
+    TIMERS.lock(|timers| timers.borrow_mut().apply(line, now, tz))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    fn pacific() -> TimeZone {
+        TimeZone::posix("PST8PDT,M3.2.0,M11.1.0").unwrap()
+    }
+
+    #[test]
+    fn countdown_to_a_local_time() {
+        let mut timers = Timers::new();
+        let now = at("2026-12-10T12:00:00Z");
+        let reply = timers
+            .apply("countdown 2026-12-24T18:00 launch", now, &pacific())
+            .unwrap();
+        assert_eq!(reply, "countdown: 14d 14h to launch");
+        let countdown = timers.countdown.as_ref().unwrap();
+        assert_eq!(countdown.target, at("2026-12-25T02:00:00Z"));
+        assert_eq!(
+            countdown.text(at("2026-12-25T01:47:55Z")),
+            "12m 05s to launch"
+        );
+        assert_eq!(countdown.text(at("2026-12-25T02:00:00Z")), "launch!");
+        timers
+            .apply("countdown 2027-01-01", now, &pacific())
+            .unwrap();
+        let countdown = timers.countdown.as_ref().unwrap();
+        assert_eq!(countdown.target, at("2027-01-01T08:00:00Z"));
+        assert_eq!(countdown.text(at("2026-12-31T22:00:00Z")), "10h 00m");
+    }
+
+    #[test]
+    fn spans() {
+        assert_eq!(
+            span_text(SignedDuration::from_secs(14 * 86_400 + 3 * 3600 + 59)),
+            "14d 3h"
+        );
+        assert_eq!(
+            span_text(SignedDuration::from_secs(3 * 3600 + 12 * 60)),
+            "3h 12m"
+        );
+        assert_eq!(span_text(SignedDuration::from_millis(44_100)), "45s");
+    }
+
+    #[test]
+    fn kitchen_timer_pauses_and_flashes() {
+        let mut timers = Timers::new();
+        let tz = TimeZone::UTC;
+        let start = at("2026-03-18T12:00:00Z");
+        assert_eq!(timers.apply("timer 5m", start, &tz).unwrap(), "timer: 5:00");
+        let later = start + SignedDuration::from_secs(60);
+        assert_eq!(
+            timers.apply("timer pause", later, &tz).unwrap(),
+            "timer: 4:00 (paused)"
+        );
+        // paused time doesn't count
+        let resumed = later + SignedDuration::from_secs(600);
+        timers.apply("timer resume", resumed, &tz).unwrap();
+        let end = resumed + SignedDuration::from_secs(240);
+        assert!(!timers.flashing(end - SignedDuration::from_millis(1)));
+        assert!(timers.flashing(end));
+        assert!(timers.flash_on(end));
+        assert!(!timers.flash_on(end + SignedDuration::from_millis(300)));
+        assert_eq!(
+            timers.rows(end + SignedDuration::from_secs(12))[0].0,
+            "-0:12"
+        );
+        // then it goes away by itself
+        timers.tidy(end + FLASH);
+        assert!(timers.is_empty());
+    }
+
+    #[test]
+    fn cancelling_stops_the_flash() {
+        let mut timers = Timers::new();
+        let start = at("2026-03-18T12:00:00Z");
+        timers.apply("timer 90s", start, &TimeZone::UTC).unwrap();
+        let end = start + SignedDuration::from_secs(90);
+        assert!(timers.flashing(end));
+        timers.apply("timer cancel", end, &TimeZone::UTC).unwrap();
+        assert!(!timers.flashing(end));
+    }
+
+    #[test]
+    fn stopwatch_keeps_running_time() {
+        let mut timers = Timers::new();
+        let tz = TimeZone::UTC;
+        let start = at("2026-03-18T12:00:00Z");
+        timers.apply("stopwatch start", start, &tz).unwrap();
+        let stop = start + SignedDuration::from_millis(62_340);
+        assert_eq!(
+            timers.apply("stopwatch stop", stop, &tz).unwrap(),
+            "stopwatch: 1:02.3 (stopped)"
+        );
+        let restart = stop + SignedDuration::from_secs(30);
+        timers.apply("stopwatch start", restart, &tz).unwrap();
+        let stopwatch = timers.stopwatch.unwrap();
+        assert_eq!(
+            stopwatch.elapsed(restart + SignedDuration::from_secs(3600)),
+            SignedDuration::from_millis(3_662_340)
+        );
+        assert_eq!(
+            clock_text(SignedDuration::from_millis(3_662_340), true),
+            "1:01:02.3"
+        );
+        timers.apply("stopwatch reset", restart, &tz).unwrap();
+        assert_eq!(timers.apply("status", restart, &tz).unwrap(), "idle");
+    }
+
+    #[test]
+    fn rejects_nonsense() {
+        let mut timers = Timers::new();
+        let now = at("2026-03-18T12:00:00Z");
+        let tz = TimeZone::UTC;
+        assert!(timers.apply("timer soon", now, &tz).is_err());
+        assert!(timers.apply("timer 0", now, &tz).is_err());
+        assert!(timers.apply("countdown tomorrow", now, &tz).is_err());
+        assert!(timers.apply("stopwatch lap", now, &tz).is_err());
+        assert!(timers.apply("reboot", now, &tz).is_err());
+        assert_eq!(timers.apply("timer 10", now, &tz).unwrap(), "timer: 10:00");
+    }
+}
@@ -0,0 +1,469 @@
+//! Bookkeeping for everything that can set the clock: when each source last
+//! synced, how wrong it could have been and at what stratum, so that a worse
+//! source never overrides a better one and the rest of the firmware can ask
+//! how much to trust the time.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embedded_graphics::{Pixel, pixelcolor::Rgb888, prelude::*};
+
+/// How fast our error grows between syncs, in µs per second. The RTC runs off
+/// the slow clock and drift compensation is only an estimate.
+pub const HOLDOVER_PPM: u64 = 500;
+/// Error claimed by an RTC chip we didn't write this boot: whole seconds
+/// only, and whatever it drifted since.
+pub const RTC_CHIP_ERROR: u64 = 1_000_000;
+/// DS3231 accuracy, µs per second, for how far the chip wanders once written.
+pub const RTC_CHIP_PPM: u64 = 2;
+/// Error claimed by an HTTP `Date:` header: whole seconds plus the request.
+pub const HTTP_DATE_ERROR: u64 = 1_500_000;
+/// Error claimed by a GPS receiver's NMEA sentences alone; see
+/// [`crate::nmea`].
+pub const GPS_ERROR: u64 = 500_000;
+/// Error claimed by GPS with a PPS edge: the pulse is far better than this,
+/// but we only see it once the executor gets round to it.
+pub const GPS_PPS_ERROR: u64 = 2_000;
+/// Estimated error at most this is [`Quality::Good`].
+pub const GOOD_ERROR: u64 = 100_000;
+/// Estimated error at most this is [`Quality::Fair`].
+pub const FAIR_ERROR: u64 = 2_000_000;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Source {
+    Ntp,
+    Gps,
+    RtcChip,
+    HttpDate,
+    /// Saved in RTC memory before a software or watchdog reset; see
+    /// [`crate::warmboot`].
+    Restored,
+}
+
+impl Source {
+    /// In order of preference when errors are equal.
+    pub const ALL: [Source; 5] = [
+        Source::Ntp,
+        Source::Gps,
+        Source::RtcChip,
+        Source::HttpDate,
+        Source::Restored,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Source::Ntp => "NTP",
+            Source::Gps => "GPS",
+            Source::RtcChip => "RTC",
+            Source::HttpDate => "HTTP",
+            Source::Restored => "RAM",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// One source's last sync.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LastSync {
+    /// Seconds since boot.
+    pub at: u64,
+    /// Worst-case error at that moment, µs.
+    pub error: u64,
+    /// NTP stratum of the time as received; `None` for sources without one.
+    pub stratum: Option<u8>,
+    /// Reference ID of the NTP server it came from.
+    pub reference_id: Option<[u8; 4]>,
+}
+
+impl LastSync {
+    /// Worst-case error `now` seconds since boot, having free-run since.
+    pub fn error_at(&self, now: u64) -> u64 {
+        self.error_at_rate(now, HOLDOVER_PPM)
+    }
+
+    fn error_at_rate(&self, now: u64, ppm: u64) -> u64 {
+        self.error
+            .saturating_add(now.saturating_sub(self.at).saturating_mul(ppm))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Quality {
+    /// Nothing has set the clock since boot.
+    Unsynced,
+    /// Known once, but too long ago to trust to the second.
+    Poor,
+    /// Right to a second or two: the HTTP `Date:` header, the RTC chip, GPS
+    /// without PPS, or NTP a while back.
+    Fair,
+    Good,
+}
+
+impl Quality {
+    pub fn from_error(error: u64) -> Self {
+        if error <= GOOD_ERROR {
+            Quality::Good
+        } else if error <= FAIR_ERROR {
+            Quality::Fair
+        } else {
+            Quality::Poor
+        }
+    }
+
+    /// Colour of the uncertain-time marker; `None` when there's no doubt.
+    pub fn marker_color(&self) -> Option<Rgb888> {
+        match self {
+            Quality::Good | Quality::Unsynced => None,
+            Quality::Fair => Some(Rgb888::new(255, 140, 0)),
+            Quality::Poor => Some(Rgb888::RED),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Sources {
+    syncs: [Option<LastSync>; Source::ALL.len()],
+    // when we last wrote the RTC chip, and how good the time written was
+    chip_written: Option<LastSync>,
+}
+
+impl Sources {
+    pub const fn new() -> Self {
+        Self {
+            syncs: [None; Source::ALL.len()],
+            chip_written: None,
+        }
+    }
+
+    pub fn record_chip_write(&mut self, sync: LastSync) {
+        self.chip_written = Some(sync);
+    }
+
+    /// How wrong the RTC chip's own time could be by `now`.
+    pub fn chip_error(&self, now: u64) -> u64 {
+        self.chip_written
+            .map_or(RTC_CHIP_ERROR, |sync| sync.error_at_rate(now, RTC_CHIP_PPM))
+    }
+
+    /// Which of the system clock and the RTC chip to believe when they are
+    /// `delta` µs apart. A chip that `lost` its time is never believed, nor
+    /// is one we haven't written this boot once anything has set the system
+    /// clock, nor one further off than both errors together allow: its
+    /// one-second claim is only good if it was ever set.
+    pub fn reconcile(&self, delta: i64, lost: bool, tolerance: u64, now: u64) -> Reconcile {
+        let system = self.best(now).map(|(_, sync)| sync.error_at(now));
+        if lost {
+            return match system {
+                Some(_) => Reconcile::SystemWins,
+                None => Reconcile::Neither,
+            };
+        }
+        if delta.unsigned_abs() <= tolerance {
+            return Reconcile::InSync;
+        }
+        let Some(system) = system else {
+            return Reconcile::ChipWins;
+        };
+        let chip = self.chip_error(now);
+        if self.chip_written.is_none()
+            || system <= chip
+            || delta.unsigned_abs() > system.saturating_add(chip)
+        {
+            Reconcile::SystemWins
+        } else {
+            Reconcile::ChipWins
+        }
+    }
+
+    pub fn record(&mut self, source: Source, sync: LastSync) {
+        self.syncs[source.index()] = Some(sync);
+    }
+
+    pub fn get(&self, source: Source) -> Option<LastSync> {
+        self.syncs[source.index()]
+    }
+
+    /// The source whose time is least wrong by now.
+    pub fn best(&self, now: u64) -> Option<(Source, LastSync)> {
+        Source::ALL
+            .iter()
+            .filter_map(|source| Some((*source, self.get(*source)?)))
+            .min_by_key(|(_, sync)| sync.error_at(now))
+    }
+
+    /// Whether `source`, claiming `error`, would improve on the current best;
+    /// a source may always refresh its own sync.
+    pub fn should_apply(&self, source: Source, error: u64, now: u64) -> bool {
+        match self.best(now) {
+            None => true,
+            Some((best, _)) if best == source => true,
+            Some((_, sync)) => error < sync.error_at(now),
+        }
+    }
+
+    /// Restored time is never [`Quality::Good`]: it's only as good as our
+    /// guess at how long the reset took.
+    pub fn quality(&self, now: u64) -> Quality {
+        self.best(now).map_or(Quality::Unsynced, |(source, sync)| {
+            let quality = Quality::from_error(sync.error_at(now));
+            if source == Source::Restored {
+                quality.min(Quality::Fair)
+            } else {
+                quality
+            }
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Reconcile {
+    InSync,
+    /// Rewrite the chip from the system clock.
+    SystemWins,
+    /// Restore the system clock from the chip.
+    ChipWins,
+    /// The chip lost its time and nothing has set the system clock either.
+    Neither,
+}
+
+pub static SOURCES: Mutex<CriticalSectionRawMutex, RefCell<Sources>> =
+    Mutex::new(RefCell::new(Sources::new()));
+
+pub fn record(
+    source: Source,
+    now: u64,
+    error: u64,
+    stratum: Option<u8>,
+    reference_id: Option<[u8; 4]>,
+) {
+    SOURCES.lock(|sources| {
+        sources.borrow_mut().record(
+            source,
+            LastSync {
+                at: now,
+                error,
+                stratum,
+                reference_id,
+            },
+        )
+    });
+}
+
+/// Note that the RTC chip was just written with time of `error` µs.
+pub fn record_chip_write(now: u64, error: u64) {
+    SOURCES.lock(|sources| {
+        sources.borrow_mut().record_chip_write(LastSync {
+            at: now,
+            error,
+            stratum: None,
+            reference_id: None,
+        })
+    });
+}
+
+pub fn chip_error(now: u64) -> u64 {
+    SOURCES.lock(|sources| sources.borrow().chip_error(now))
+}
+
+pub fn reconcile(delta: i64, lost: bool, tolerance: u64, now: u64) -> Reconcile {
+    SOURCES.lock(|sources| sources.borrow().reconcile(delta, lost, tolerance, now))
+}
+
+pub fn should_apply(source: Source, error: u64, now: u64) -> bool {
+    SOURCES.lock(|sources| sources.borrow().should_apply(source, error, now))
+}
+
+pub fn best(now: u64) -> Option<(Source, LastSync)> {
+    SOURCES.lock(|sources| sources.borrow().best(now))
+}
+
+pub fn quality(now: u64) -> Quality {
+    SOURCES.lock(|sources| sources.borrow().quality(now))
+}
+
+/// A dot in the top-left corner when the time shown may be off.
+pub fn draw_uncertain_marker<D>(quality: Quality, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    match quality.marker_color() {
+        Some(color) => Pixel(Point::zero(), color).draw(target),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sync(at: u64, error: u64) -> LastSync {
+        LastSync {
+            at,
+            error,
+            stratum: None,
+            reference_id: None,
+        }
+    }
+
+    #[test]
+    fn error_grows_while_free_running() {
+        let ntp = sync(100, 20_000);
+        assert_eq!(ntp.error_at(100), 20_000);
+        assert_eq!(ntp.error_at(160), 20_000 + 60 * HOLDOVER_PPM);
+        assert_eq!(ntp.error_at(50), 20_000);
+    }
+
+    #[test]
+    fn fresh_ntp_beats_rtc_chip_and_http() {
+        let mut sources = Sources::new();
+        sources.record(Source::RtcChip, sync(0, 1_000_000));
+        sources.record(Source::HttpDate, sync(10, 1_500_000));
+        assert_eq!(sources.best(10).unwrap().0, Source::RtcChip);
+        sources.record(Source::Ntp, sync(20, 15_000));
+        assert_eq!(sources.best(20).unwrap().0, Source::Ntp);
+        assert!(!sources.should_apply(Source::HttpDate, 1_500_000, 30));
+        assert!(!sources.should_apply(Source::RtcChip, 1_000_000, 30));
+    }
+
+    #[test]
+    fn stale_ntp_loses_to_fresh_http() {
+        let mut sources = Sources::new();
+        sources.record(Source::Ntp, sync(0, 15_000));
+        // 500 ppm for an hour is 1.8 s
+        assert!(!sources.should_apply(Source::HttpDate, 1_500_000, 600));
+        assert!(sources.should_apply(Source::HttpDate, 1_500_000, 3_600));
+        sources.record(Source::HttpDate, sync(3_600, 1_500_000));
+        assert_eq!(sources.best(3_600).unwrap().0, Source::HttpDate);
+    }
+
+    #[test]
+    fn gps_with_pps_outranks_ntp() {
+        let mut sources = Sources::new();
+        sources.record(Source::Ntp, sync(0, 15_000));
+        assert!(!sources.should_apply(Source::Gps, GPS_ERROR, 10));
+        assert!(sources.should_apply(Source::Gps, GPS_PPS_ERROR, 10));
+        sources.record(Source::Gps, sync(10, GPS_PPS_ERROR));
+        assert!(!sources.should_apply(Source::Ntp, 15_000, 20));
+    }
+
+    #[test]
+    fn anything_applies_to_an_unset_clock() {
+        let sources = Sources::new();
+        assert!(sources.should_apply(Source::HttpDate, u64::MAX / 2, 0));
+        assert_eq!(sources.quality(0), Quality::Unsynced);
+        assert_eq!(sources.best(0), None);
+    }
+
+    #[test]
+    fn quality_decays_from_good_to_poor() {
+        let mut sources = Sources::new();
+        sources.record(
+            Source::Ntp,
+            LastSync {
+                at: 0,
+                error: 10_000,
+                stratum: Some(2),
+                reference_id: Some([192, 0, 2, 1]),
+            },
+        );
+        assert_eq!(sources.quality(0), Quality::Good);
+        assert_eq!(sources.quality(600), Quality::Fair);
+        assert_eq!(sources.quality(7_200), Quality::Poor);
+        assert_eq!(sources.best(0).unwrap().1.stratum, Some(2));
+    }
+
+    #[test]
+    fn restored_time_is_at_best_fair() {
+        let mut sources = Sources::new();
+        sources.record(Source::Restored, sync(0, 20_000));
+        assert_eq!(sources.quality(0), Quality::Fair);
+        assert_eq!(sources.quality(7_200), Quality::Poor);
+        // and anything real replaces it
+        assert!(sources.should_apply(Source::HttpDate, 1_500_000, 3_600));
+        sources.record(Source::Ntp, sync(10, 20_000));
+        assert_eq!(sources.quality(10), Quality::Good);
+    }
+
+    #[test]
+    fn chip_error_grows_slowly_once_written() {
+        let mut sources = Sources::new();
+        assert_eq!(sources.chip_error(0), RTC_CHIP_ERROR);
+        sources.record_chip_write(sync(100, 20_000));
+        assert_eq!(
+            sources.chip_error(100 + 86_400),
+            20_000 + 86_400 * RTC_CHIP_PPM
+        );
+    }
+
+    #[test]
+    fn reconcile_believes_the_better_clock() {
+        let mut sources = Sources::new();
+        // nothing set the system clock: the chip wins any disagreement
+        assert_eq!(
+            sources.reconcile(500_000, false, 2_000_000, 0),
+            Reconcile::InSync
+        );
+        assert_eq!(
+            sources.reconcile(-30_000_000, false, 2_000_000, 0),
+            Reconcile::ChipWins
+        );
+
+        // fresh NTP beats a chip we haven't written
+        sources.record(Source::Ntp, sync(10, 20_000));
+        assert_eq!(
+            sources.reconcile(5_000_000, false, 2_000_000, 20),
+            Reconcile::SystemWins
+        );
+
+        // once written the chip holds time far better than the system clock,
+        // so after a long NTP outage it wins, by as much as the system clock
+        // could have wandered
+        sources.record_chip_write(sync(10, 20_000));
+        assert_eq!(
+            sources.reconcile(3_000_000, false, 2_000_000, 7_200),
+            Reconcile::ChipWins
+        );
+        assert_eq!(
+            sources.reconcile(30_000_000, false, 2_000_000, 7_200),
+            Reconcile::SystemWins
+        );
+    }
+
+    #[test]
+    fn reconcile_never_believes_an_unset_chip() {
+        // a chip reset to 2000-01-01, and NTP long enough ago that its error
+        // is past the chip's one second
+        const RESET: i64 = -26 * 365 * 86_400 * 1_000_000;
+        let mut sources = Sources::new();
+        sources.record(Source::Ntp, sync(0, 20_000));
+        assert!(sources.best(2_000).unwrap().1.error_at(2_000) > RTC_CHIP_ERROR);
+        assert_eq!(
+            sources.reconcile(RESET, false, 2_000_000, 2_000),
+            Reconcile::SystemWins
+        );
+        assert_eq!(
+            sources.reconcile(RESET, true, 2_000_000, 2_000),
+            Reconcile::SystemWins
+        );
+        // and with nothing else to go on, one that says it lost its time
+        assert_eq!(
+            Sources::new().reconcile(RESET, true, 2_000_000, 0),
+            Reconcile::Neither
+        );
+    }
+
+    #[test]
+    fn marker_only_when_in_doubt() {
+        assert_eq!(Quality::Good.marker_color(), None);
+        assert_eq!(Quality::Unsynced.marker_color(), None);
+        assert!(Quality::Fair.marker_color().is_some());
+        assert_eq!(Quality::Poor.marker_color(), Some(Rgb888::RED));
+    }
+}
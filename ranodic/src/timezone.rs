@@ -0,0 +1,272 @@
+use core::cell::RefCell;
+
+use anyhow::{Result, anyhow};
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use jiff::{Zoned, civil::DateTime, tz::TimeZone};
+
+// the old compile-time zone; used until something calls `set`
+const FALLBACK: TimeZone = jiff::tz::get!("PST8PDT");
+
+static ZONE: Mutex<CriticalSectionRawMutex, RefCell<Option<TimeZone>>> =
+    Mutex::new(RefCell::new(None));
+
+/// The zone all wall-clock time is displayed in.
+pub fn get() -> TimeZone {
+    ZONE.lock(|zone| zone.borrow().clone().unwrap_or(FALLBACK))
+}
+
+pub fn set(tz: TimeZone) {
+    ZONE.lock(|zone| zone.replace(Some(tz)));
+}
+
+/// Parse `spec` and make it the display zone; the current zone is kept if
+/// `spec` doesn't parse.
+pub fn set_from_str(spec: &str) -> Result<()> {
+    set(parse(spec)?);
+    Ok(())
+}
+
+/// Open-Meteo is queried with `timezone=GMT` so its naive timestamps don't
+/// depend on our zone; this places one in the display zone.
+pub fn from_utc_civil(time: &str) -> Result<Zoned> {
+    from_utc_civil_in(time, get())
+}
+
+fn from_utc_civil_in(time: &str, tz: TimeZone) -> Result<Zoned> {
+    let utc = time
+        .parse::<DateTime>()
+        .map_err(anyhow::Error::msg)?
+        .to_zoned(TimeZone::UTC)
+        .map_err(anyhow::Error::msg)?;
+    Ok(utc.with_time_zone(tz))
+}
+
+/// Accepts an IANA name from [`IANA_ZONES`] (`"Europe/Berlin"`), `"UTC"`, or a
+/// POSIX TZ string with full DST rules (`"CET-1CEST,M3.5.0,M10.5.0/3"`).
+///
+/// There is no tzdb on the device, so IANA names resolve through the table to
+/// their current POSIX rule; historical offsets are not available.
+pub fn parse(spec: &str) -> Result<TimeZone> {
+    let spec = spec.trim();
+    if matches!(spec, "UTC" | "GMT" | "Etc/UTC" | "Etc/GMT" | "Z") {
+        return Ok(TimeZone::UTC);
+    }
+    if let Some((_, posix)) = IANA_ZONES.iter().find(|(name, _)| *name == spec) {
+        return TimeZone::posix(posix).map_err(anyhow::Error::msg);
+    }
+    TimeZone::posix(spec).map_err(|e| {
+        if spec.contains('/') && !spec.contains(',') {
+            anyhow!("unknown IANA timezone {}", spec)
+        } else {
+            anyhow::Error::msg(e)
+        }
+    })
+}
+
+/// IANA name → POSIX TZ rule, for the zones people are likely to ask for.
+pub const IANA_ZONES: &[(&str, &str)] = &[
+    ("America/St_Johns", "NST3:30NDT,M3.2.0,M11.1.0"),
+    ("America/Halifax", "AST4ADT,M3.2.0,M11.1.0"),
+    ("America/New_York", "EST5EDT,M3.2.0,M11.1.0"),
+    ("America/Detroit", "EST5EDT,M3.2.0,M11.1.0"),
+    ("America/Toronto", "EST5EDT,M3.2.0,M11.1.0"),
+    ("America/Chicago", "CST6CDT,M3.2.0,M11.1.0"),
+    ("America/Winnipeg", "CST6CDT,M3.2.0,M11.1.0"),
+    ("America/Mexico_City", "CST6"),
+    ("America/Denver", "MST7MDT,M3.2.0,M11.1.0"),
+    ("America/Edmonton", "MST7MDT,M3.2.0,M11.1.0"),
+    ("America/Phoenix", "MST7"),
+    ("America/Los_Angeles", "PST8PDT,M3.2.0,M11.1.0"),
+    ("America/Vancouver", "PST8PDT,M3.2.0,M11.1.0"),
+    ("America/Anchorage", "AKST9AKDT,M3.2.0,M11.1.0"),
+    ("Pacific/Honolulu", "HST10"),
+    ("America/Sao_Paulo", "<-03>3"),
+    ("America/Argentina/Buenos_Aires", "<-03>3"),
+    ("Europe/London", "GMT0BST,M3.5.0/1,M10.5.0"),
+    ("Europe/Dublin", "IST-1GMT0,M10.5.0,M3.5.0/1"),
+    ("Europe/Lisbon", "WET0WEST,M3.5.0/1,M10.5.0"),
+    ("Europe/Amsterdam", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Berlin", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Brussels", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Budapest", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Copenhagen", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Madrid", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Oslo", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Paris", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Prague", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Rome", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Stockholm", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Vienna", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Warsaw", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Zurich", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Athens", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Bucharest", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Helsinki", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Kyiv", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Istanbul", "<+03>-3"),
+    ("Europe/Moscow", "MSK-3"),
+    ("Africa/Lagos", "WAT-1"),
+    ("Africa/Johannesburg", "SAST-2"),
+    ("Africa/Cairo", "EET-2EEST,M4.5.5/0,M10.5.4/24"),
+    ("Africa/Nairobi", "EAT-3"),
+    ("Asia/Jerusalem", "IST-2IDT,M3.4.4/26,M10.5.0"),
+    ("Asia/Dubai", "<+04>-4"),
+    ("Asia/Karachi", "PKT-5"),
+    ("Asia/Kolkata", "IST-5:30"),
+    ("Asia/Bangkok", "<+07>-7"),
+    ("Asia/Jakarta", "WIB-7"),
+    ("Asia/Singapore", "<+08>-8"),
+    ("Asia/Hong_Kong", "HKT-8"),
+    ("Asia/Shanghai", "CST-8"),
+    ("Asia/Taipei", "CST-8"),
+    ("Asia/Manila", "PST-8"),
+    ("Asia/Seoul", "KST-9"),
+    ("Asia/Tokyo", "JST-9"),
+    ("Australia/Perth", "AWST-8"),
+    ("Australia/Adelaide", "ACST-9:30ACDT,M10.1.0,M4.1.0/3"),
+    ("Australia/Brisbane", "AEST-10"),
+    ("Australia/Melbourne", "AEST-10AEDT,M10.1.0,M4.1.0/3"),
+    ("Australia/Sydney", "AEST-10AEDT,M10.1.0,M4.1.0/3"),
+    ("Pacific/Auckland", "NZST-12NZDT,M9.5.0,M4.1.0/3"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::{Timestamp, civil::date};
+
+    fn local(tz: &TimeZone, utc: &str) -> (jiff::civil::DateTime, i32) {
+        let zoned = utc.parse::<Timestamp>().unwrap().to_zoned(tz.clone());
+        (zoned.datetime(), zoned.offset().seconds() / 3600)
+    }
+
+    #[test]
+    fn los_angeles_spring_forward() {
+        let tz = parse("America/Los_Angeles").unwrap();
+        assert_eq!(
+            local(&tz, "2025-03-09T09:59:59Z"),
+            (date(2025, 3, 9).at(1, 59, 59, 0), -8)
+        );
+        assert_eq!(
+            local(&tz, "2025-03-09T10:00:00Z"),
+            (date(2025, 3, 9).at(3, 0, 0, 0), -7)
+        );
+    }
+
+    #[test]
+    fn los_angeles_fall_back() {
+        let tz = parse("America/Los_Angeles").unwrap();
+        assert_eq!(
+            local(&tz, "2025-11-02T08:59:59Z"),
+            (date(2025, 11, 2).at(1, 59, 59, 0), -7)
+        );
+        assert_eq!(
+            local(&tz, "2025-11-02T09:00:00Z"),
+            (date(2025, 11, 2).at(1, 0, 0, 0), -8)
+        );
+    }
+
+    #[test]
+    fn berlin_both_transitions() {
+        let tz = parse("Europe/Berlin").unwrap();
+        assert_eq!(
+            local(&tz, "2025-03-30T00:59:59Z"),
+            (date(2025, 3, 30).at(1, 59, 59, 0), 1)
+        );
+        assert_eq!(
+            local(&tz, "2025-03-30T01:00:00Z"),
+            (date(2025, 3, 30).at(3, 0, 0, 0), 2)
+        );
+        assert_eq!(
+            local(&tz, "2025-10-26T00:59:59Z"),
+            (date(2025, 10, 26).at(2, 59, 59, 0), 2)
+        );
+        assert_eq!(
+            local(&tz, "2025-10-26T01:00:00Z"),
+            (date(2025, 10, 26).at(2, 0, 0, 0), 1)
+        );
+    }
+
+    #[test]
+    fn sydney_southern_hemisphere() {
+        let tz = parse("Australia/Sydney").unwrap();
+        assert_eq!(
+            local(&tz, "2025-04-05T15:59:59Z"),
+            (date(2025, 4, 6).at(2, 59, 59, 0), 11)
+        );
+        assert_eq!(
+            local(&tz, "2025-04-05T16:00:00Z"),
+            (date(2025, 4, 6).at(2, 0, 0, 0), 10)
+        );
+        assert_eq!(
+            local(&tz, "2025-10-04T15:59:59Z"),
+            (date(2025, 10, 5).at(1, 59, 59, 0), 10)
+        );
+        assert_eq!(
+            local(&tz, "2025-10-04T16:00:00Z"),
+            (date(2025, 10, 5).at(3, 0, 0, 0), 11)
+        );
+    }
+
+    #[test]
+    fn posix_string_matches_iana_name() {
+        let posix = parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        let iana = parse("Europe/Paris").unwrap();
+        for utc in ["2025-01-15T12:00:00Z", "2025-07-15T12:00:00Z"] {
+            assert_eq!(local(&posix, utc), local(&iana, utc));
+        }
+    }
+
+    #[test]
+    fn fixed_and_utc_zones() {
+        assert_eq!(
+            local(&parse("UTC").unwrap(), "2025-07-01T12:00:00Z"),
+            (date(2025, 7, 1).at(12, 0, 0, 0), 0)
+        );
+        assert_eq!(
+            local(&parse("Asia/Tokyo").unwrap(), "2025-07-01T20:00:00Z"),
+            (date(2025, 7, 2).at(5, 0, 0, 0), 9)
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_zones() {
+        assert!(parse("Mars/Olympus_Mons").is_err());
+        assert!(parse("not a zone").is_err());
+    }
+
+    #[test]
+    fn every_table_entry_parses() {
+        for (name, _) in IANA_ZONES {
+            assert!(parse(name).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn utc_civil_lands_in_given_zone() {
+        // not the display zone, which `set_replaces_fallback` changes
+        let tz = parse("Asia/Tokyo").unwrap();
+        let zoned = from_utc_civil_in("2025-07-01T12:00", tz.clone()).unwrap();
+        assert_eq!(zoned.timestamp(), "2025-07-01T12:00:00Z".parse().unwrap());
+        assert_eq!(zoned.time_zone(), &tz);
+        assert_eq!(
+            from_utc_civil("2025-07-01T12:00").unwrap().timestamp(),
+            zoned.timestamp()
+        );
+        assert!(from_utc_civil("yesterday").is_err());
+    }
+
+    #[test]
+    fn set_replaces_fallback() {
+        set_from_str("Asia/Kolkata").unwrap();
+        assert_eq!(
+            local(&get(), "2025-07-01T00:00:00Z"),
+            (date(2025, 7, 1).at(5, 30, 0, 0), 5)
+        );
+        assert!(set_from_str("Nowhere/Special").is_err());
+        assert_eq!(
+            local(&get(), "2025-07-01T00:00:00Z").0,
+            date(2025, 7, 1).at(5, 30, 0, 0)
+        );
+    }
+}
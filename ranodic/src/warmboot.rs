@@ -0,0 +1,132 @@
+//! Keeping the time across our own resets. `harakiri` and the watchdogs reset
+//! the chip regularly, and RTC fast memory survives that, so once a second
+//! `ntp::tick_writer` saves the time there as a [`Tick`], and at boot it's put
+//! back with its error grown by however long the reset took.
+
+use crate::aging::checksum;
+
+/// Length of [`Tick::to_words`].
+pub const WORDS: usize = 5;
+/// A tick worse than this isn't worth restoring, µs.
+pub const MAX_ERROR: u64 = 60_000_000;
+/// How long a reset and boot takes when the RTC timer can't tell us, µs.
+pub const RESET_GAP: u64 = 1_000_000;
+// the RTC timer runs through resets on most chips; a bigger jump than this
+// means it didn't
+const MAX_MEASURED_GAP: u64 = 60_000_000;
+// between reading the clock and writing the tick
+const MEASURED_GAP_ERROR: u64 = 10_000;
+
+const MAGIC: i64 = 0x5449_434b_0000_0001;
+
+// `SocResetReason` values, the same on every ESP32, after which RTC memory
+// still holds what we wrote: software resets and the watchdogs
+const SOFT_RESETS: [u32; 10] = [
+    0x03, // CoreSw
+    0x07, // CoreMwdt0
+    0x08, // CoreMwdt1
+    0x09, // CoreRtcWdt
+    0x0b, // Cpu0Mwdt0
+    0x0c, // Cpu0Sw
+    0x0d, // Cpu0RtcWdt
+    0x10, // SysRtcWdt
+    0x11, // Cpu0Mwdt1
+    0x12, // SysSuperWdt
+];
+
+pub fn is_soft_reset(reason: u32) -> bool {
+    SOFT_RESETS.contains(&reason)
+}
+
+/// The time at one moment, and how sure of it we were.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Tick {
+    /// Unix µs.
+    pub time: i64,
+    /// RTC timer µs.
+    pub uptime: u64,
+    /// Worst-case error of `time`, µs.
+    pub error: u64,
+}
+
+impl Tick {
+    /// The time and its error at RTC timer `uptime` after a reset.
+    pub fn restore(&self, uptime: u64) -> (i64, u64) {
+        match uptime.checked_sub(self.uptime) {
+            Some(gap) if gap <= MAX_MEASURED_GAP => (
+                self.time + gap as i64,
+                self.error.saturating_add(MEASURED_GAP_ERROR),
+            ),
+            _ => (
+                self.time + RESET_GAP as i64,
+                self.error.saturating_add(RESET_GAP),
+            ),
+        }
+    }
+
+    pub fn to_words(&self) -> [i64; WORDS] {
+        let mut words = [MAGIC, self.time, self.uptime as i64, self.error as i64, 0];
+        words[WORDS - 1] = checksum(&words[..WORDS - 1]);
+        words
+    }
+
+    /// `None` for memory that was never written, or was torn by a reset
+    /// mid-write.
+    pub fn from_words(words: &[i64; WORDS]) -> Option<Self> {
+        if words[0] != MAGIC || words[WORDS - 1] != checksum(&words[..WORDS - 1]) {
+            return None;
+        }
+        Some(Self {
+            time: words[1],
+            uptime: words[2] as u64,
+            error: words[3] as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Tick = Tick {
+        time: 1_760_000_000_000_000,
+        uptime: 1_800_000_000,
+        error: 40_000,
+    };
+
+    #[test]
+    fn only_soft_resets_restore() {
+        assert!(is_soft_reset(0x03));
+        assert!(is_soft_reset(0x10));
+        // power on, deep sleep, brownout
+        assert!(!is_soft_reset(0x01));
+        assert!(!is_soft_reset(0x05));
+        assert!(!is_soft_reset(0x0f));
+    }
+
+    #[test]
+    fn measured_gap_is_trusted() {
+        let (time, error) = TICK.restore(TICK.uptime + 1_700_000);
+        assert_eq!(time, TICK.time + 1_700_000);
+        assert_eq!(error, TICK.error + MEASURED_GAP_ERROR);
+    }
+
+    #[test]
+    fn reset_timer_falls_back_to_a_guess() {
+        for uptime in [300_000, TICK.uptime + 3_600_000_000] {
+            let (time, error) = TICK.restore(uptime);
+            assert_eq!(time, TICK.time + RESET_GAP as i64);
+            assert_eq!(error, TICK.error + RESET_GAP);
+        }
+    }
+
+    #[test]
+    fn words_round_trip_and_reject_garbage() {
+        assert_eq!(Tick::from_words(&TICK.to_words()), Some(TICK));
+        assert_eq!(Tick::from_words(&[0; WORDS]), None);
+        let mut torn = TICK.to_words();
+        torn[1] += 1_000_000;
+        assert_eq!(Tick::from_words(&torn), None);
+    }
+}
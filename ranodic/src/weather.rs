@@ -15,22 +15,27 @@ use serde_json::Value;
 
 use anyhow::{Result, anyhow};
 
-use crate::{
-    forecast::{WeatherForecast, WeatherForecastCache},
-    net::NET_REQUEST_QUEUE,
-};
+use crate::forecast::{WeatherForecast, WeatherForecastCache};
+#[cfg(target_os = "none")]
+use crate::net::NET_REQUEST_QUEUE;
 
 use embedded_graphics::primitives::StyledDrawable;
 
 pub const WEATHER_URL: &str = "https://api.weather.gov/";
+#[cfg(target_os = "none")]
 pub const WEATHER_LATITUDE: &str = env!("WEATHER_LATITUDE");
+#[cfg(target_os = "none")]
 pub const WEATHER_LONGITUDE: &str = env!("WEATHER_LONGITUDE");
 
 const FORECAST_SUCCESS_INTERVAL: u64 = 3600;
 const FORECAST_FAILURE_INTERVAL: u64 = 60;
 
-static BUFFER_SZ: usize = 8192;
-// static BUFFER: Mutex<CriticalSectionRawMutex, [u8; BUFFER_SZ]> = Mutex::new([0u8; BUFFER_SZ]);
+// the five-day hourly, daily and 15-minute series come to ~10.2k of JSON,
+// and ~11.3k with the headers and chunking when every value is as wide as it
+// gets; the rest is headroom. A response that fills it is refused as cut off
+const BUFFER_SZ: usize = 16384;
+// kept out of the task's future, which would otherwise carry it between polls
+static BUFFER: Mutex<CriticalSectionRawMutex, [u8; BUFFER_SZ]> = Mutex::new([0u8; BUFFER_SZ]);
 
 pub static FORECASTS: Mutex<CriticalSectionRawMutex, WeatherForecastCache> =
     Mutex::new(WeatherForecastCache::new());
@@ -41,6 +46,7 @@ pub static QUICKTRIES: AtomicU8 = AtomicU8::new(3);
 
 // static OMETEO_URL: OnceLock<String> = OnceLock::new();
 
+#[cfg(target_os = "none")]
 #[embassy_executor::task]
 pub async fn weather_query(stack: embassy_net::Stack<'static>) {
     debug!("weather_query alive");
@@ -80,11 +86,11 @@ pub async fn weather_query(stack: embassy_net::Stack<'static>) {
     }
 }
 
+#[cfg(target_os = "none")]
 async fn get_forecasts(stack: embassy_net::Stack<'static>) -> anyhow::Result<()> {
     // debug!("url: {}", url);
-    // let mut buffer = BUFFER.lock().await;
-    let mut buffer = [0u8; BUFFER_SZ];
-    let _ = NET_REQUEST_QUEUE.lock().await;
+    let mut buffer = BUFFER.lock().await;
+    let _guard = NET_REQUEST_QUEUE.lock().await;
     let result = crate::net::WorkingClient::new(&stack)
         .request(
             HttpMethod::GET,
@@ -92,10 +98,12 @@ async fn get_forecasts(stack: embassy_net::Stack<'static>) -> anyhow::Result<()>
                             latitude={}&\
                             longitude={}&\
                             daily=sunrise,sunset,daylight_duration,sunshine_duration&\
-                            hourly=temperature_2m,relative_humidity_2m,precipitation,precipitation_probability,weather_code,is_day,sunshine_duration&\
+                            hourly=temperature_2m,relative_humidity_2m,apparent_temperature,precipitation,precipitation_probability,weather_code,is_day,sunshine_duration,wind_speed_10m,wind_gusts_10m,wind_direction_10m,uv_index&\
+                            minutely_15=precipitation&\
+                            forecast_minutely_15=12&\
                             models=best_match&\
-                            timezone=America%2FLos_Angeles&\
-                            forecast_days=2&\
+                            timezone=GMT&\
+                            forecast_days=5&\
                             wind_speed_unit=mph&\
                             temperature_unit=fahrenheit&\
                             precipitation_unit=inch", WEATHER_LATITUDE, WEATHER_LONGITUDE).as_str(),
@@ -104,7 +112,7 @@ async fn get_forecasts(stack: embassy_net::Stack<'static>) -> anyhow::Result<()>
                 HttpHeader::accept(mime_types::JSON),
             ],
             None,
-            &mut buffer,
+            &mut buffer[..],
         )
         .await;
     if let Err(e) = result {
@@ -137,7 +145,14 @@ async fn get_forecasts(stack: embassy_net::Stack<'static>) -> anyhow::Result<()>
         "get_forecasts: response body length: {}",
         response.body.len()
     );
+    if bytes_read >= BUFFER_SZ {
+        return Err(anyhow!(
+            "get_forecasts: response cut off at {} bytes; raise BUFFER_SZ",
+            bytes_read
+        ));
+    }
     if response.is_success() {
+        crate::ntp::http_date(response.get_header("Date")).await;
         if let ResponseBody::Text(jason) = response.body {
             digest_body(jason).await
         } else {
@@ -160,6 +175,7 @@ async fn get_forecasts(stack: embassy_net::Stack<'static>) -> anyhow::Result<()>
     }
 }
 
+#[cfg(target_os = "none")]
 async fn digest_body(jason: &str) -> Result<(), anyhow::Error> {
     for line in jason.lines() {
         info!("jason line:[{}]", line);
@@ -207,78 +223,116 @@ async fn digest_body(jason: &str) -> Result<(), anyhow::Error> {
                     continue;
                 };
                 debug!("digest_body: data has timezone: {}", timezone);
+                if let Some(minutely) = jobj["minutely_15"].as_object() {
+                    digest_minutely(minutely).await;
+                }
                 if let Some(hourly) = jobj["hourly"].as_object() {
                     if let (
                         Some(time),
                         Some(temperature_2m),
                         Some(relative_humidity_2m),
+                        Some(apparent_temperature),
                         Some(precipitation),
                         Some(precipitation_probability),
                         Some(weather_code),
                         Some(is_day),
                         Some(sunshine_duration),
+                        Some(wind_speed_10m),
+                        Some(wind_gusts_10m),
+                        Some(wind_direction_10m),
+                        Some(uv_index),
                     ) = (
                         hourly["time"].as_array(),
                         hourly["temperature_2m"].as_array(),
                         hourly["relative_humidity_2m"].as_array(),
+                        hourly["apparent_temperature"].as_array(),
                         hourly["precipitation"].as_array(),
                         hourly["precipitation_probability"].as_array(),
                         hourly["weather_code"].as_array(),
                         hourly["is_day"].as_array(),
                         hourly["sunshine_duration"].as_array(),
+                        hourly["wind_speed_10m"].as_array(),
+                        hourly["wind_gusts_10m"].as_array(),
+                        hourly["wind_direction_10m"].as_array(),
+                        hourly["uv_index"].as_array(),
                     ) {
                         debug!("digest_body: deserialized successfully");
                         for pivot in izip!(
                             time,
                             temperature_2m,
                             relative_humidity_2m,
+                            apparent_temperature,
                             precipitation,
                             precipitation_probability,
                             weather_code,
                             is_day,
-                            sunshine_duration
+                            sunshine_duration,
+                            wind_speed_10m,
+                            wind_gusts_10m,
+                            wind_direction_10m,
+                            uv_index
                         ) {
                             let forecast = if let (
                                 Some(time),
                                 Some(temperature),
                                 Some(relative_humidity),
+                                Some(apparent_temperature),
                                 Some(precipitation),
                                 Some(precipitation_probability),
                                 Some(weather_code),
                                 Some(is_day),
                                 Some(sunshine_duration),
+                                Some(wind_speed),
+                                Some(wind_gusts),
+                                Some(wind_direction),
+                                Some(uv_index),
                             ) = (
                                 pivot.0.as_str(),
                                 pivot.1.as_f64(),
                                 pivot.2.as_u64(),
                                 pivot.3.as_f64(),
-                                pivot.4.as_u64(),
+                                pivot.4.as_f64(),
                                 pivot.5.as_u64(),
                                 pivot.6.as_u64(),
-                                pivot.7.as_f64(),
+                                pivot.7.as_u64(),
+                                pivot.8.as_f64(),
+                                pivot.9.as_f64(),
+                                pivot.10.as_f64(),
+                                pivot.11.as_u64(),
+                                pivot.12.as_f64(),
                             ) {
                                 WeatherForecast::new(
                                     time,
                                     timezone,
                                     temperature as f32,
                                     relative_humidity.try_into().unwrap(),
+                                    apparent_temperature as f32,
                                     precipitation as f32,
                                     precipitation_probability.try_into().unwrap(),
                                     weather_code.try_into().unwrap(),
                                     is_day != 0,
                                     sunshine_duration as f32,
+                                    wind_speed as f32,
+                                    wind_gusts as f32,
+                                    wind_direction.try_into().unwrap(),
+                                    uv_index as f32,
                                 )
                             } else {
                                 debug!(
-                                    "0:{}, 1:{}, 2:{}, 3:{}, 4:{}, 5:{}, 6:{}, 7:{}",
+                                    "0:{}, 1:{}, 2:{}, 3:{}, 4:{}, 5:{}, 6:{}, 7:{}, 8:{}, 9:{}, 10:{}, 11:{}, 12:{}",
                                     pivot.0.as_str(),
                                     pivot.1.as_f64(),
                                     pivot.2.as_u64(),
                                     pivot.3.as_f64(),
-                                    pivot.4.as_u64(),
+                                    pivot.4.as_f64(),
                                     pivot.5.as_u64(),
                                     pivot.6.as_u64(),
-                                    pivot.7.as_f64(),
+                                    pivot.7.as_u64(),
+                                    pivot.8.as_f64(),
+                                    pivot.9.as_f64(),
+                                    pivot.10.as_f64(),
+                                    pivot.11.as_u64(),
+                                    pivot.12.as_f64(),
                                 );
                                 error!("digest_body: JSON parsing didn't work");
                                 return Err(anyhow!("JSON parsing didn't work"));
@@ -311,19 +365,47 @@ async fn digest_body(jason: &str) -> Result<(), anyhow::Error> {
     Err(anyhow!("digest_body: never found data"))
 }
 
+/// Loads the `minutely_15` precipitation samples for the nowcast; a bad
+/// sample is skipped rather than failing the whole forecast.
+#[cfg(target_os = "none")]
+async fn digest_minutely(minutely: &serde_json::Map<alloc::string::String, Value>) {
+    let (Some(time), Some(precipitation)) = (
+        minutely["time"].as_array(),
+        minutely["precipitation"].as_array(),
+    ) else {
+        error!("digest_minutely: missing columns");
+        return;
+    };
+    let samples = time
+        .iter()
+        .zip(precipitation)
+        .filter_map(|(time, precipitation)| {
+            let end = crate::timezone::from_utc_civil(time.as_str()?).ok()?;
+            Some((end, precipitation.as_f64()? as f32))
+        })
+        .collect();
+    FORECASTS.lock().await.set_nowcast(samples);
+}
+
 use core::fmt::Write as _;
 
 use embedded_graphics::{
-    Drawable,
+    Drawable, Pixel,
     draw_target::DrawTarget,
     geometry::{Point, Size},
-    mono_font::{MonoTextStyle, ascii::FONT_5X8},
+    mono_font::{
+        MonoTextStyle,
+        iso_8859_1::{FONT_4X6, FONT_5X8},
+    },
     pixelcolor::Rgb888,
-    primitives::{PrimitiveStyle, Rectangle},
-    text::{Baseline, Text},
+    primitives::{Line, PrimitiveStyle, Rectangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
 };
 use heapless::String;
-use jiff::Zoned;
+use jiff::{ToSpan, Zoned};
+
+use crate::clockformat::fit;
+use crate::forecast::{NOWCAST_HORIZON_MINUTES, NOWCAST_SLOT_MINUTES, NOWCAST_WET, Nowcast};
 
 // ─────────────────────────────────────────────────────────────────────────────
 // Colour palette  (Rgb888 — 5R 6G 5B)
@@ -359,6 +441,17 @@ pub mod palette {
     // Row-1 data colours
     pub const HUMID: Rgb888 = Rgb888::new(30, 190, 160); // teal
     pub const PRECIP: Rgb888 = Rgb888::new(60, 140, 220); // rain blue
+
+    // Wind
+    pub const WIND: Rgb888 = Rgb888::new(180, 200, 210); // pale steel
+    pub const GUST: Rgb888 = Rgb888::new(240, 120, 200); // gusty pink
+
+    // UV index, WHO categories
+    pub const UV_LOW: Rgb888 = Rgb888::new(40, 180, 40); // green
+    pub const UV_MODERATE: Rgb888 = Rgb888::new(250, 220, 0); // yellow
+    pub const UV_HIGH: Rgb888 = Rgb888::new(250, 120, 0); // orange
+    pub const UV_VERY_HIGH: Rgb888 = Rgb888::new(230, 20, 20); // red
+    pub const UV_EXTREME: Rgb888 = Rgb888::new(170, 60, 230); // violet
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
@@ -380,6 +473,23 @@ const ROW0_Y: i32 = 1;
 /// Top of row 1 (humidity + precip), region-relative.  +1 clears the divider.
 const ROW1_Y: i32 = CHAR_H;
 
+/// Gusts this much above the sustained wind speed (mph) are called out.
+const GUST_MARGIN: f32 = 10.0;
+
+/// What row 1 shows in the columns left of the day/night min/max.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Row1Layout {
+    /// `72% 30%` — relative humidity and precipitation probability.
+    HumidityPrecip,
+    /// `68 ↗12 5` — apparent temperature, wind arrow + speed, UV index.
+    FeelsWindUv,
+}
+
+#[cfg(not(feature = "feelslike"))]
+pub const ROW1_LAYOUT: Row1Layout = Row1Layout::HumidityPrecip;
+#[cfg(feature = "feelslike")]
+pub const ROW1_LAYOUT: Row1Layout = Row1Layout::FeelsWindUv;
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Entry point
 // ─────────────────────────────────────────────────────────────────────────────
@@ -403,9 +513,15 @@ where
     D: DrawTarget<Color = Rgb888>,
 {
     // draw_background(forecast, target)?;
-    draw_row0(now, forecasts, forecast, target)?;
+    let nowcast = forecasts.nowcast(&now);
+    draw_row0(now.clone(), forecasts, forecast, target)?;
     // draw_divider(target)?;
-    draw_row1(forecast, target)?;
+    match nowcast {
+        Nowcast::StartsIn(_) | Nowcast::StopsIn(_) => {
+            draw_nowcast(&now, nowcast, forecasts.nowcast_samples(), target)?
+        }
+        _ => draw_row1(forecast, ROW1_LAYOUT, target)?,
+    }
     Ok(())
 }
 
@@ -595,6 +711,17 @@ fn draw_divider<D: DrawTarget<Color = Rgb888>>(target: &mut D) -> Result<(), D::
 }
 
 fn draw_row1<D: DrawTarget<Color = Rgb888>>(
+    forecast: &WeatherForecast,
+    layout: Row1Layout,
+    target: &mut D,
+) -> Result<(), D::Error> {
+    match layout {
+        Row1Layout::HumidityPrecip => draw_row1_humidity_precip(forecast, target),
+        Row1Layout::FeelsWindUv => draw_row1_feels_wind_uv(forecast, target),
+    }
+}
+
+fn draw_row1_humidity_precip<D: DrawTarget<Color = Rgb888>>(
     forecast: &WeatherForecast,
     target: &mut D,
 ) -> Result<(), D::Error> {
@@ -653,6 +780,370 @@ fn draw_row1<D: DrawTarget<Color = Rgb888>>(
     Ok(())
 }
 
+// ─────────────────────────────────────────────────────────────────────────────
+// Row 1 (alternate) — feels like · wind · UV
+//
+//   px 0-10      px 12-16  px 18-25   px 28-35
+//  ┌───────────┬─────────┬──────────┬──────────┐
+//  │ "68"      │  arrow  │ "12"     │ "5"      │
+//  └───────────┴─────────┴──────────┴──────────┘
+//
+// Speed and UV use FONT_4X6 so that two-digit values still clear the
+// day/night min/max at col 6 (px 36).
+// ─────────────────────────────────────────────────────────────────────────────
+
+fn draw_row1_feels_wind_uv<D: DrawTarget<Color = Rgb888>>(
+    forecast: &WeatherForecast,
+    target: &mut D,
+) -> Result<(), D::Error> {
+    let y = REGION_TOP + ROW1_Y;
+
+    // feels like; below -9 or above 99 it takes the small font to clear the
+    // wind arrow, whose left edge is at x=12
+    let feels = format!("{:2}", forecast.apparent_temperature as i8);
+    let font = fit(&feels, &[&FONT_5X8, &FONT_4X6], 12);
+    Text::with_baseline(
+        &feels,
+        Point::new(0, y + (CHAR_H - font.character_size.height as i32) / 2),
+        MonoTextStyle::new(font, temperature_color(forecast.apparent_temperature)),
+        Baseline::Top,
+    )
+    .draw(target)?;
+
+    // wind
+    let wind_color = if forecast.wind_gusts >= forecast.wind_speed + GUST_MARGIN {
+        palette::GUST
+    } else {
+        palette::WIND
+    };
+    draw_wind_arrow(
+        Point::new(14, y + 3),
+        forecast.wind_direction,
+        wind_color,
+        target,
+    )?;
+    Text::with_baseline(
+        &(libm::roundf(forecast.wind_speed) as u8)
+            .min(99)
+            .to_string(),
+        Point::new(18, y + 1),
+        MonoTextStyle::new(&FONT_4X6, wind_color),
+        Baseline::Top,
+    )
+    .draw(target)?;
+
+    // UV
+    Text::with_baseline(
+        &(libm::roundf(forecast.uv_index) as u8).min(99).to_string(),
+        Point::new(28, y + 1),
+        MonoTextStyle::new(&FONT_4X6, uv_color(forecast.uv_index)),
+        Baseline::Top,
+    )
+    .draw(target)?;
+
+    Ok(())
+}
+
+/// Draws a 5×5 arrow centred on `center` pointing where the wind blows *to*.
+fn draw_wind_arrow<D: DrawTarget<Color = Rgb888>>(
+    center: Point,
+    wind_direction: u16,
+    color: Rgb888,
+    target: &mut D,
+) -> Result<(), D::Error> {
+    let step = wind_arrow_step(wind_direction);
+    let tip = center + step * 2;
+    let back = tip - step;
+    let perp = Point::new(-step.y, step.x);
+    let style = PrimitiveStyle::with_stroke(color, 1);
+    Line::new(center - step * 2, tip).draw_styled(&style, target)?;
+    Line::new(tip, back + perp).draw_styled(&style, target)?;
+    Line::new(tip, back - perp).draw_styled(&style, target)
+}
+
+/// Unit step (screen coordinates, y down) of the arrow for one of the eight
+/// compass sectors.  `wind_direction` is where the wind comes *from*, so the
+/// arrow points the opposite way.
+fn wind_arrow_step(wind_direction: u16) -> Point {
+    const STEPS: [Point; 8] = [
+        Point::new(0, 1),   // from N  → pointing S
+        Point::new(-1, 1),  // from NE → pointing SW
+        Point::new(-1, 0),  // from E  → pointing W
+        Point::new(-1, -1), // from SE → pointing NW
+        Point::new(0, -1),  // from S  → pointing N
+        Point::new(1, -1),  // from SW → pointing NE
+        Point::new(1, 0),   // from W  → pointing E
+        Point::new(1, 1),   // from NW → pointing SE
+    ];
+    let sector = ((wind_direction % 360) as usize * 2 + 45) / 90 % 8;
+    STEPS[sector]
+}
+
+/// WHO UV index categories.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum UvCategory {
+    Low,
+    Moderate,
+    High,
+    VeryHigh,
+    Extreme,
+}
+
+impl UvCategory {
+    pub fn from_index(uv_index: f32) -> Self {
+        match libm::roundf(uv_index) as u8 {
+            0..=2 => Self::Low,
+            3..=5 => Self::Moderate,
+            6..=7 => Self::High,
+            8..=10 => Self::VeryHigh,
+            _ => Self::Extreme,
+        }
+    }
+
+    pub const fn color(self) -> Rgb888 {
+        match self {
+            Self::Low => palette::UV_LOW,
+            Self::Moderate => palette::UV_MODERATE,
+            Self::High => palette::UV_HIGH,
+            Self::VeryHigh => palette::UV_VERY_HIGH,
+            Self::Extreme => palette::UV_EXTREME,
+        }
+    }
+}
+
+fn uv_color(uv_index: f32) -> Rgb888 {
+    UvCategory::from_index(uv_index).color()
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Row 1 (nowcast) — replaces row 1 while rain starts or stops within 2 h
+//
+//   px 0-35: "rain 25m" / "dry 40m" in FONT_4X6, with a 1 px strip under it,
+//            4 px per 15-minute slot, lit where the slot is wet.
+// ─────────────────────────────────────────────────────────────────────────────
+
+fn draw_nowcast<D: DrawTarget<Color = Rgb888>>(
+    now: &Zoned,
+    nowcast: Nowcast,
+    samples: &[(Zoned, f32)],
+    target: &mut D,
+) -> Result<(), D::Error> {
+    let y = REGION_TOP + ROW1_Y;
+
+    let message = match nowcast {
+        Nowcast::StartsIn(minutes) => format!("rain {}m", minutes.min(999)),
+        Nowcast::StopsIn(minutes) => format!("dry {}m", minutes.min(999)),
+        _ => return Ok(()),
+    };
+    Text::with_baseline(
+        &message,
+        Point::new(0, y),
+        MonoTextStyle::new(&FONT_4X6, palette::PRECIP),
+        Baseline::Top,
+    )
+    .draw(target)?;
+
+    let slots = NOWCAST_HORIZON_MINUTES / NOWCAST_SLOT_MINUTES;
+    for slot in 0..slots {
+        let start = now + (slot * NOWCAST_SLOT_MINUTES).minutes();
+        let wet = samples.iter().any(|(end, precipitation)| {
+            *precipitation >= NOWCAST_WET
+                && *end > start
+                && *end <= &start + NOWCAST_SLOT_MINUTES.minutes()
+        });
+        let color = if wet {
+            palette::PRECIP
+        } else {
+            palette::DIVIDER
+        };
+        Rectangle::new(Point::new(slot as i32 * 4, y + 7), Size::new(3, 1))
+            .draw_styled(&PrimitiveStyle::with_fill(color), target)?;
+    }
+    Ok(())
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Hourly chart — lower 16 rows, 3 px per hour
+//
+//   dim vertical line      now
+//   PRECIP bars            precipitation probability, full height at 100 %
+//   temperature line       scaled between the window's low and high,
+//                          coloured with `temperature_color`
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Hours covered by the chart; 21 × 3 px fills the 64 px width.
+pub const CHART_HOURS: i64 = 21;
+const CHART_HOUR_W: i32 = 3;
+/// Top and bottom rows of the temperature line, absolute.
+const CHART_TOP: i32 = REGION_TOP + 1;
+const CHART_BOTTOM: i32 = REGION_TOP + 14;
+
+/// Draw the next [`CHART_HOURS`] hours as a chart into the lower 16 rows.
+pub fn draw_hourly_chart<D>(
+    now: &Zoned,
+    forecasts: &WeatherForecastCache,
+    target: &mut D,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    let Ok(start) = now.round(
+        jiff::ZonedRound::new()
+            .smallest(jiff::Unit::Hour)
+            .mode(jiff::RoundMode::Trunc),
+    ) else {
+        return Ok(());
+    };
+    let mut hours = forecasts.between(&start, &(&start + (CHART_HOURS - 1).hours()));
+    hours.sort();
+    if hours.is_empty() {
+        return Ok(());
+    }
+
+    // now marker, as far into the first column as we are into the hour
+    let marker_x = now.minute() as i32 * CHART_HOUR_W / 60;
+    Rectangle::new(Point::new(marker_x, REGION_TOP), Size::new(1, 16))
+        .draw_styled(&PrimitiveStyle::with_fill(palette::DIVIDER), target)?;
+
+    let column = |forecast: &WeatherForecast| {
+        (&forecast.timespan - &start)
+            .total(jiff::Unit::Hour)
+            .map(|hours| hours as i32)
+            .unwrap_or(0)
+    };
+
+    for forecast in hours.iter() {
+        let height = 16 * forecast.precipitation_probability.min(100) as u32 / 100;
+        if height > 0 {
+            Rectangle::new(
+                Point::new(
+                    column(forecast) * CHART_HOUR_W,
+                    REGION_TOP + 16 - height as i32,
+                ),
+                Size::new(CHART_HOUR_W as u32 - 1, height),
+            )
+            .draw_styled(&PrimitiveStyle::with_fill(palette::PRECIP), target)?;
+        }
+    }
+
+    let low = hours
+        .iter()
+        .map(|x| x.temperature)
+        .reduce(f32::min)
+        .unwrap();
+    let high = hours
+        .iter()
+        .map(|x| x.temperature)
+        .reduce(f32::max)
+        .unwrap();
+    let point = |forecast: &WeatherForecast| {
+        Point::new(
+            column(forecast) * CHART_HOUR_W + 1,
+            chart_y(forecast.temperature, low, high),
+        )
+    };
+    for pair in hours.windows(2) {
+        Line::new(point(pair[0]), point(pair[1])).draw_styled(
+            &PrimitiveStyle::with_stroke(temperature_color(pair[0].temperature), 1),
+            target,
+        )?;
+    }
+    if let [only] = hours.as_slice() {
+        Pixel(point(only), temperature_color(only.temperature)).draw(target)?;
+    }
+    Ok(())
+}
+
+/// Row for `temperature` on a chart spanning `low..=high`; a flat day sits
+/// in the middle.
+fn chart_y(temperature: f32, low: f32, high: f32) -> i32 {
+    if high - low < 1.0 {
+        return (CHART_TOP + CHART_BOTTOM) / 2;
+    }
+    let t = ((temperature - low) / (high - low)).clamp(0.0, 1.0);
+    CHART_BOTTOM - libm::roundf(t * (CHART_BOTTOM - CHART_TOP) as f32) as i32
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Multi-day page — full 64×32, one column per day
+//
+//   y  0-5    weekday      "Mon"         FONT_4X6
+//   y  7-16   icon          *            FONT_6X10
+//   y 17-22   high          72           FONT_4X6
+//   y 24-29   low           51           FONT_4X6
+//   y 31      precipitation probability bar, column-wide at 100 %
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Most days the multi-day page will show.
+pub const DAILY_MAX_DAYS: usize = 5;
+/// Fewer days than this and the multi-day page isn't worth showing.
+pub const DAILY_MIN_DAYS: usize = 3;
+
+/// Draw up to [`DAILY_MAX_DAYS`] days, today first, across the whole display.
+pub fn draw_daily<D>(
+    now: &Zoned,
+    forecasts: &WeatherForecastCache,
+    target: &mut D,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    let days = forecasts.days(now, DAILY_MAX_DAYS);
+    if days.is_empty() {
+        return Ok(());
+    }
+    let col_w = DISPLAY_W / days.len() as i32;
+    let small = |color| MonoTextStyle::new(&FONT_4X6, color);
+    for (idx, day) in days.iter().enumerate() {
+        let left = idx as i32 * col_w;
+        let center = left + col_w / 2;
+
+        Text::with_text_style(
+            crate::locale::get().weekday(day.date.weekday()),
+            Point::new(center, 0),
+            small(palette::DIM),
+            TextStyleBuilder::new()
+                .alignment(Alignment::Center)
+                .baseline(Baseline::Top)
+                .build(),
+        )
+        .draw(target)?;
+
+        let icon = day.weather_code.icon();
+        let offset = if icon == '~' { -2 } else { 0 };
+        Text::with_text_style(
+            &icon.to_string(),
+            Point::new(center, 7 - offset),
+            MonoTextStyle::new(&FONT_6X10, day.weather_code.icon_color()),
+            TextStyleBuilder::new()
+                .alignment(Alignment::Center)
+                .baseline(Baseline::Top)
+                .build(),
+        )
+        .draw(target)?;
+
+        for (temperature, y) in [(day.high, 17), (day.low, 24)] {
+            Text::with_text_style(
+                &(temperature as i8).to_string(),
+                Point::new(center, y),
+                small(temperature_color(temperature)),
+                TextStyleBuilder::new()
+                    .alignment(Alignment::Center)
+                    .baseline(Baseline::Top)
+                    .build(),
+            )
+            .draw(target)?;
+        }
+
+        let bar_w = (col_w - 2) * day.precipitation_probability.min(100) as i32 / 100;
+        if bar_w > 0 {
+            Rectangle::new(Point::new(left + 1, 31), Size::new(bar_w as u32, 1))
+                .draw_styled(&PrimitiveStyle::with_fill(palette::PRECIP), target)?;
+        }
+    }
+    Ok(())
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Formatting helpers  (heapless — no heap allocation)
 // ─────────────────────────────────────────────────────────────────────────────
@@ -740,33 +1231,89 @@ fn lerp_color(a: Rgb888, b: Rgb888, t: f32) -> Rgb888 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::forecast::WMOCode;
     use embedded_graphics::mock_display::MockDisplay;
 
     fn make_forecast(is_day: bool, temp: f32, hum: u8, pp: u8, prec: f32) -> WeatherForecast {
         WeatherForecast {
-            timespan: Zoned::now(),
+            timespan: "2026-03-18T12:00:00-07:00[-07:00]".parse().unwrap(),
             temperature: temp,
             relative_humidity: hum,
+            apparent_temperature: temp,
             precipitation: prec,
             precipitation_probability: pp,
             weather_code: WMOCode::PartlyCloudy,
             is_day,
             sunshine_duration: 18_720.0,
+            wind_speed: 8.0,
+            wind_gusts: 21.0,
+            wind_direction: 270,
+            uv_index: 6.4,
         }
     }
 
+    fn render(forecast: WeatherForecast) {
+        let mut display: MockDisplay<Rgb888> = MockDisplay::new();
+        display.set_allow_overdraw(true);
+        let now = forecast.timespan.clone();
+        let mut forecasts = WeatherForecastCache::new();
+        forecasts.add(forecast);
+        let forecast = forecasts.get_forecast(&now).unwrap();
+        draw_forecast(now.clone(), &forecasts, forecast, &mut display).unwrap();
+    }
+
     #[test]
     fn renders_day_no_error() {
-        let mut display: MockDisplay<Rgb888> = MockDisplay::new();
-        display.set_allow_out_of_bounds_drawing(true);
-        draw_forecast(&make_forecast(true, 23.0, 72, 30, 0.4), &mut display).unwrap();
+        render(make_forecast(true, 23.0, 72, 30, 0.4));
     }
 
     #[test]
     fn renders_night_no_error() {
-        let mut display: MockDisplay<Rgb888> = MockDisplay::new();
-        display.set_allow_out_of_bounds_drawing(true);
-        draw_forecast(&make_forecast(false, -3.0, 88, 60, 1.2), &mut display).unwrap();
+        render(make_forecast(false, -3.0, 88, 60, 1.2));
+    }
+
+    #[test]
+    fn feels_like_clears_the_wind_arrow() {
+        for apparent in [-40.0, -12.0, 5.0, 68.0, 105.0] {
+            let mut forecast = make_forecast(true, 23.0, 72, 30, 0.4);
+            forecast.apparent_temperature = apparent;
+            let mut display: MockDisplay<Rgb888> = MockDisplay::new();
+            display.set_allow_overdraw(true);
+            draw_row1_feels_wind_uv(&forecast, &mut display).unwrap();
+            let color = temperature_color(apparent);
+            let right = (0..64)
+                .filter(|&x| (0..64).any(|y| display.get_pixel(Point::new(x, y)) == Some(color)))
+                .max();
+            assert!(right.is_some_and(|x| x < 12), "{apparent}: {right:?}");
+        }
+    }
+
+    #[test]
+    fn chart_y_scales_between_low_and_high() {
+        assert_eq!(chart_y(50.0, 50.0, 70.0), CHART_BOTTOM);
+        assert_eq!(chart_y(70.0, 50.0, 70.0), CHART_TOP);
+        assert_eq!(chart_y(60.0, 50.0, 70.0), 23);
+        assert_eq!(chart_y(55.0, 55.0, 55.4), 23);
+    }
+
+    #[test]
+    fn uv_who_categories() {
+        assert_eq!(UvCategory::from_index(0.0), UvCategory::Low);
+        assert_eq!(UvCategory::from_index(2.4), UvCategory::Low);
+        assert_eq!(UvCategory::from_index(2.6), UvCategory::Moderate);
+        assert_eq!(UvCategory::from_index(7.0), UvCategory::High);
+        assert_eq!(UvCategory::from_index(10.0), UvCategory::VeryHigh);
+        assert_eq!(UvCategory::from_index(11.0), UvCategory::Extreme);
+    }
+
+    #[test]
+    fn wind_arrow_points_downwind() {
+        assert_eq!(wind_arrow_step(0), Point::new(0, 1));
+        assert_eq!(wind_arrow_step(359), Point::new(0, 1));
+        assert_eq!(wind_arrow_step(90), Point::new(-1, 0));
+        assert_eq!(wind_arrow_step(200), Point::new(0, -1));
+        assert_eq!(wind_arrow_step(270), Point::new(1, 0));
+        assert_eq!(wind_arrow_step(315), Point::new(1, 1));
     }
 
     #[test]
@@ -0,0 +1,677 @@
+//! What the painter draws, as [`Widget`]s laid out on [`Page`]s.
+//!
+//! A widget owns a region of the panel, says whether it has anything
+//! current to show, and draws itself from a [`Context`]: a snapshot of
+//! everything the widgets read, taken once per frame so none of them wait on
+//! a lock or the network. Widgets that share a region take turns in a
+//! [`Rotation`].
+//!
+//! [`pages`] lists what's shown: the clock page, then the full-screen pages
+//! in turn, each for its dwell and only while its condition holds, with
+//! takeovers (a ringing alarm, a timer that has gone off) shown instead
+//! whenever theirs does. New content is a widget and an entry there.
+
+use alloc::{boxed::Box, string::ToString, vec, vec::Vec};
+
+use embedded_graphics::{
+    Drawable, Pixel,
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point, Size},
+    mono_font::MonoFont,
+    pixelcolor::Rgb888,
+    primitives::Rectangle,
+    text::{Alignment, Text},
+};
+use jiff::{Timestamp, Zoned};
+
+use crate::{
+    airquality::AirQualityCache,
+    clockformat::{ClockFormat, fit},
+    drawing::{BGPOINT, BIGFONT, DATEFONTB, DATEPOINT, SMOLFONT, TIMEFONT, TIMEPOINT, bgfontbase},
+    forecast::WeatherForecastCache,
+    ical::Event,
+    locale::Locale,
+    nightscout::BgReading,
+    timesource::Quality,
+    weather::{DAILY_MAX_DAYS, DAILY_MIN_DAYS},
+    worldclock::WorldZone,
+};
+
+pub const DISPLAY_W: u32 = 64;
+pub const DISPLAY_H: u32 = 32;
+
+/// The whole panel, for full-screen pages.
+pub const FULL: Rectangle = Rectangle::new(Point::zero(), Size::new(DISPLAY_W, DISPLAY_H));
+/// The top 16 rows: date, time and BG, and the two below that the bottom of
+/// their font reaches into.
+pub const UPPER: Rectangle = Rectangle::new(Point::zero(), Size::new(DISPLAY_W, 18));
+/// The bottom 16 rows, which the forecast, chart and air quality share.
+pub const LOWER: Rectangle = Rectangle::new(Point::new(0, 16), Size::new(DISPLAY_W, 16));
+/// Right of the time: the BG reading, three digits of [`bgfontbase`].
+pub const BG: Rectangle = Rectangle::new(Point::new(DISPLAY_W as i32 - 18, 8), Size::new(18, 10));
+
+/// Everything widgets draw from, as of one frame.
+pub struct Context<'a> {
+    pub now: &'a Zoned,
+    /// The clock has been right at some point since boot; until then only
+    /// the BG is shown.
+    pub synced: bool,
+    pub quality: Quality,
+    pub format: &'a ClockFormat,
+    pub locale: &'static Locale,
+    pub bg: Option<&'a BgReading>,
+    pub forecasts: &'a WeatherForecastCache,
+    pub air_quality: &'a AirQualityCache,
+    pub agenda: &'a [Event],
+    pub world: &'a [WorldZone],
+    pub timers: &'a crate::timers::Timers,
+    /// Ringing since then.
+    pub alarm: Option<Timestamp>,
+}
+
+pub trait Widget<D: DrawTarget<Color = Rgb888>> {
+    /// The part of the panel it draws in.
+    fn bounds(&self) -> Rectangle;
+
+    /// Whether it has anything current to show; stale or missing data
+    /// leaves its region blank.
+    fn fresh(&self, ctx: &Context) -> bool;
+
+    /// Anything drawn outside [`Widget::bounds`] is dropped.
+    fn draw(&self, ctx: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error>;
+
+    /// [`Widget::draw`] straight onto the panel, clipped to its bounds.
+    fn draw_clipped(&self, ctx: &Context, target: &mut D) -> Result<(), D::Error> {
+        self.draw(ctx, &mut Clip::new(target, &self.bounds()))
+    }
+}
+
+/// A widget's part of the panel: pixels outside it are dropped. Unlike
+/// embedded-graphics' `Clipped`, narrowing one keeps its type, so a
+/// [`Rotation`] can clip the widget whose turn it is in turn.
+pub struct Clip<'a, D> {
+    parent: &'a mut D,
+    area: Rectangle,
+}
+
+impl<'a, D: DrawTarget> Clip<'a, D> {
+    pub fn new(parent: &'a mut D, area: &Rectangle) -> Self {
+        let area = area.intersection(&parent.bounding_box());
+        Self { parent, area }
+    }
+
+    /// The part of this that's also in `area`.
+    pub fn narrowed(&mut self, area: &Rectangle) -> Clip<'_, D> {
+        Clip {
+            area: self.area.intersection(area),
+            parent: self.parent,
+        }
+    }
+}
+
+impl<D: DrawTarget> Dimensions for Clip<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.area
+    }
+}
+
+impl<D: DrawTarget> DrawTarget for Clip<'_, D> {
+    type Color = D::Color;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let area = self.area;
+        self.parent.draw_iter(
+            pixels
+                .into_iter()
+                .filter(move |Pixel(point, _)| area.contains(*point)),
+        )
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.parent.fill_solid(&self.area.intersection(area), color)
+    }
+}
+
+pub type Boxed<D> = Box<dyn Widget<D>>;
+
+/// Widgets sharing a region, each shown for its dwell in seconds, skipping
+/// ones that aren't [`Widget::fresh`].
+pub struct Rotation<D: DrawTarget<Color = Rgb888>> {
+    pub bounds: Rectangle,
+    pub slots: Vec<(Boxed<D>, i64)>,
+}
+
+impl<D: DrawTarget<Color = Rgb888>> Rotation<D> {
+    /// Whose turn it is at `ctx.now`.
+    pub fn current(&self, ctx: &Context) -> Option<&dyn Widget<D>> {
+        let fresh: Vec<_> = self
+            .slots
+            .iter()
+            .filter(|(widget, _)| widget.fresh(ctx))
+            .collect();
+        let cycle: i64 = fresh.iter().map(|(_, dwell)| dwell).sum();
+        if cycle == 0 {
+            return None;
+        }
+        let mut phase = ctx.now.timestamp().as_second().rem_euclid(cycle);
+        for (widget, dwell) in fresh {
+            if phase < *dwell {
+                return Some(widget.as_ref());
+            }
+            phase -= dwell;
+        }
+        None
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb888>> Widget<D> for Rotation<D> {
+    fn bounds(&self) -> Rectangle {
+        self.bounds
+    }
+
+    fn fresh(&self, ctx: &Context) -> bool {
+        self.current(ctx).is_some()
+    }
+
+    fn draw(&self, ctx: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error> {
+        match self.current(ctx) {
+            Some(widget) => widget.draw(ctx, &mut target.narrowed(&widget.bounds())),
+            None => Ok(()),
+        }
+    }
+}
+
+pub type Condition = fn(&Context) -> bool;
+
+pub struct Page<D: DrawTarget<Color = Rgb888>> {
+    pub name: &'static str,
+    pub widgets: Vec<Boxed<D>>,
+    /// Seconds in the rotation; unused by takeovers.
+    pub dwell: i64,
+    /// The page is skipped while this doesn't hold.
+    pub condition: Condition,
+    /// Shown whenever its condition holds, ahead of the rotation.
+    pub takeover: bool,
+}
+
+impl<D: DrawTarget<Color = Rgb888>> Page<D> {
+    /// Each fresh widget in turn, later ones on top, each kept to its
+    /// bounds.
+    pub fn draw(&self, ctx: &Context, target: &mut D) -> Result<(), D::Error> {
+        for widget in self.widgets.iter().filter(|widget| widget.fresh(ctx)) {
+            widget.draw_clipped(ctx, target)?;
+        }
+        Ok(())
+    }
+}
+
+/// The page to show at `ctx.now`: the first takeover whose condition holds,
+/// or else whichever of the rest has its turn.
+pub fn current<'p, D: DrawTarget<Color = Rgb888>>(
+    pages: &'p [Page<D>],
+    ctx: &Context,
+) -> Option<&'p Page<D>> {
+    if let Some(page) = pages
+        .iter()
+        .find(|page| page.takeover && (page.condition)(ctx))
+    {
+        return Some(page);
+    }
+    let shown: Vec<_> = pages
+        .iter()
+        .filter(|page| !page.takeover && (page.condition)(ctx))
+        .collect();
+    let cycle: i64 = shown.iter().map(|page| page.dwell).sum();
+    if cycle == 0 {
+        return None;
+    }
+    let mut phase = ctx.now.timestamp().as_second().rem_euclid(cycle);
+    for page in shown {
+        if phase < page.dwell {
+            return Some(page);
+        }
+        phase -= page.dwell;
+    }
+    None
+}
+
+// seconds each the forecast, the hourly chart and the air quality take in the
+// lower half
+const FORECAST_DWELL: i64 = 20;
+const CHART_DWELL: i64 = 10;
+const AIR_QUALITY_DWELL: i64 = 8;
+// seconds the clock and the full-screen multi-day forecast, world clock,
+// agenda and timers get
+const MAIN_DWELL: i64 = 45;
+const DAILY_DWELL: i64 = 6;
+const WORLD_DWELL: i64 = 8;
+const AGENDA_DWELL: i64 = 8;
+const TIMERS_DWELL: i64 = 10;
+
+/// Everything the panel shows, in order.
+pub fn pages<D: DrawTarget<Color = Rgb888> + 'static>() -> Vec<Page<D>> {
+    let lower = Rotation {
+        bounds: LOWER,
+        slots: vec![
+            (Box::new(Forecast) as Boxed<D>, FORECAST_DWELL),
+            (Box::new(Chart), CHART_DWELL),
+            (Box::new(AirQuality), AIR_QUALITY_DWELL),
+        ],
+    };
+    vec![
+        Page {
+            name: "alarm",
+            widgets: vec![Box::new(Alarm) as Boxed<D>],
+            dwell: 0,
+            condition: |ctx| ctx.synced && ctx.alarm.is_some(),
+            takeover: true,
+        },
+        Page {
+            name: "timer done",
+            widgets: vec![Box::new(Timers) as Boxed<D>],
+            dwell: 0,
+            condition: |ctx| ctx.synced && ctx.timers.flashing(ctx.now.timestamp()),
+            takeover: true,
+        },
+        Page {
+            name: "clock",
+            widgets: vec![Box::new(Clock) as Boxed<D>, Box::new(Bg), Box::new(lower)],
+            dwell: MAIN_DWELL,
+            condition: |_| true,
+            takeover: false,
+        },
+        Page {
+            name: "daily",
+            widgets: vec![Box::new(Daily) as Boxed<D>],
+            dwell: DAILY_DWELL,
+            condition: |ctx| ctx.synced && daily_present(ctx),
+            takeover: false,
+        },
+        Page {
+            name: "world clock",
+            widgets: vec![Box::new(WorldClock) as Boxed<D>],
+            dwell: WORLD_DWELL,
+            condition: |ctx| ctx.synced && !ctx.world.is_empty(),
+            takeover: false,
+        },
+        Page {
+            name: "agenda",
+            widgets: vec![Box::new(Agenda) as Boxed<D>],
+            dwell: AGENDA_DWELL,
+            condition: |ctx| ctx.synced && agenda_present(ctx),
+            takeover: false,
+        },
+        Page {
+            name: "timers",
+            widgets: vec![Box::new(Timers) as Boxed<D>],
+            dwell: TIMERS_DWELL,
+            condition: |ctx| ctx.synced && !ctx.timers.is_empty(),
+            takeover: false,
+        },
+    ]
+}
+
+fn daily_present(ctx: &Context) -> bool {
+    ctx.forecasts.days(ctx.now, DAILY_MAX_DAYS).len() >= DAILY_MIN_DAYS
+}
+
+fn agenda_present(ctx: &Context) -> bool {
+    crate::agenda::upcoming(ctx.agenda, ctx.now.timestamp())
+        .next()
+        .is_some()
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Widgets
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Date and time in the configured [`crate::clockformat`], each in the
+/// biggest font that fits: the time shares its row with the BG reading, and
+/// gets the top row too when there's no date. A pixel in the corner says
+/// when the time isn't to be trusted.
+pub struct Clock;
+
+impl<D: DrawTarget<Color = Rgb888>> Widget<D> for Clock {
+    fn bounds(&self) -> Rectangle {
+        UPPER
+    }
+
+    fn fresh(&self, ctx: &Context) -> bool {
+        ctx.synced
+    }
+
+    fn draw(&self, ctx: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error> {
+        let date = ctx.format.date_text(ctx.now, ctx.locale);
+        if let Some(date) = &date {
+            let mut style = DATEFONTB;
+            style.font = fit(date, &[DATEFONTB.font, SMOLFONT.font], DISPLAY_W);
+            Text::with_alignment(date, DATEPOINT, style, Alignment::Center).draw(target)?;
+        }
+
+        let time = ctx.format.time_text(ctx.now, ctx.locale);
+        let bgwidth = ctx.bg.map_or(0, |reading| {
+            reading.bg.to_string().len() as u32 * bgfontbase().build().font.character_size.width + 1
+        });
+        let fonts: &[&MonoFont] = if date.is_some() {
+            &[TIMEFONT.font, DATEFONTB.font, SMOLFONT.font]
+        } else {
+            &[BIGFONT.font, TIMEFONT.font, DATEFONTB.font, SMOLFONT.font]
+        };
+        let mut style = TIMEFONT;
+        style.font = fit(&time, fonts, DISPLAY_W - bgwidth);
+        Text::with_alignment(&time, TIMEPOINT, style, Alignment::Left).draw(target)?;
+
+        crate::timesource::draw_uncertain_marker(ctx.quality, target)
+    }
+}
+
+/// The latest BG reading, coloured by range and staleness.
+pub struct Bg;
+
+impl<D: DrawTarget<Color = Rgb888>> Widget<D> for Bg {
+    fn bounds(&self) -> Rectangle {
+        BG
+    }
+
+    fn fresh(&self, ctx: &Context) -> bool {
+        ctx.bg.is_some()
+    }
+
+    fn draw(&self, ctx: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error> {
+        let Some(reading) = ctx.bg else {
+            return Ok(());
+        };
+        let style = crate::nightscout::get_style(reading, ctx.now);
+        Text::with_alignment(&reading.bg.to_string(), BGPOINT, style, Alignment::Right)
+            .draw(target)?;
+        Ok(())
+    }
+}
+
+/// The forecast for this hour and the next; see [`crate::weather::draw_forecast`].
+pub struct Forecast;
+
+impl<D: DrawTarget<Color = Rgb888>> Widget<D> for Forecast {
+    fn bounds(&self) -> Rectangle {
+        LOWER
+    }
+
+    fn fresh(&self, ctx: &Context) -> bool {
+        ctx.synced && ctx.forecasts.get_forecast(ctx.now).is_some()
+    }
+
+    fn draw(&self, ctx: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error> {
+        match ctx.forecasts.get_forecast(ctx.now) {
+            Some(forecast) => {
+                crate::weather::draw_forecast(ctx.now.clone(), ctx.forecasts, forecast, target)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Hourly temperature and precipitation; see
+/// [`crate::weather::draw_hourly_chart`].
+pub struct Chart;
+
+impl<D: DrawTarget<Color = Rgb888>> Widget<D> for Chart {
+    fn bounds(&self) -> Rectangle {
+        LOWER
+    }
+
+    fn fresh(&self, ctx: &Context) -> bool {
+        ctx.synced && ctx.forecasts.get_forecast(ctx.now).is_some()
+    }
+
+    fn draw(&self, ctx: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error> {
+        crate::weather::draw_hourly_chart(ctx.now, ctx.forecasts, target)
+    }
+}
+
+/// AQI, PM2.5 and pollen for this hour; see
+/// [`crate::airquality::draw_air_quality`].
+pub struct AirQuality;
+
+impl<D: DrawTarget<Color = Rgb888>> Widget<D> for AirQuality {
+    fn bounds(&self) -> Rectangle {
+        LOWER
+    }
+
+    fn fresh(&self, ctx: &Context) -> bool {
+        ctx.synced && ctx.air_quality.get(ctx.now).is_some()
+    }
+
+    fn draw(&self, ctx: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error> {
+        match ctx.air_quality.get(ctx.now) {
+            Some(reading) => crate::airquality::draw_air_quality(reading, target),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The multi-day forecast; see [`crate::weather::draw_daily`].
+pub struct Daily;
+
+impl<D: DrawTarget<Color = Rgb888>> Widget<D> for Daily {
+    fn bounds(&self) -> Rectangle {
+        FULL
+    }
+
+    fn fresh(&self, ctx: &Context) -> bool {
+        daily_present(ctx)
+    }
+
+    fn draw(&self, ctx: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error> {
+        crate::weather::draw_daily(ctx.now, ctx.forecasts, target)
+    }
+}
+
+/// See [`crate::worldclock`].
+pub struct WorldClock;
+
+impl<D: DrawTarget<Color = Rgb888>> Widget<D> for WorldClock {
+    fn bounds(&self) -> Rectangle {
+        FULL
+    }
+
+    fn fresh(&self, ctx: &Context) -> bool {
+        !ctx.world.is_empty()
+    }
+
+    fn draw(&self, ctx: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error> {
+        crate::worldclock::draw_world_clock(ctx.now, ctx.world, ctx.format.hour12, target)
+    }
+}
+
+/// See [`crate::agenda`].
+pub struct Agenda;
+
+impl<D: DrawTarget<Color = Rgb888>> Widget<D> for Agenda {
+    fn bounds(&self) -> Rectangle {
+        FULL
+    }
+
+    fn fresh(&self, ctx: &Context) -> bool {
+        agenda_present(ctx)
+    }
+
+    fn draw(&self, ctx: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error> {
+        crate::agenda::draw_agenda(ctx.now, ctx.agenda, ctx.format.hour12, ctx.locale, target)
+    }
+}
+
+/// See [`crate::timers`].
+pub struct Timers;
+
+impl<D: DrawTarget<Color = Rgb888>> Widget<D> for Timers {
+    fn bounds(&self) -> Rectangle {
+        FULL
+    }
+
+    fn fresh(&self, ctx: &Context) -> bool {
+        !ctx.timers.is_empty()
+    }
+
+    fn draw(&self, ctx: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error> {
+        crate::timers::draw_timers(ctx.timers, ctx.now.timestamp(), target)
+    }
+}
+
+/// A ringing alarm; see [`crate::alarms`].
+pub struct Alarm;
+
+impl<D: DrawTarget<Color = Rgb888>> Widget<D> for Alarm {
+    fn bounds(&self) -> Rectangle {
+        FULL
+    }
+
+    fn fresh(&self, ctx: &Context) -> bool {
+        ctx.alarm.is_some()
+    }
+
+    fn draw(&self, ctx: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error> {
+        let Some(since) = ctx.alarm else {
+            return Ok(());
+        };
+        let time = ctx.format.time_text(ctx.now, ctx.locale);
+        let on = crate::alarms::flash_on(since, ctx.now.timestamp());
+        crate::alarms::draw_alarm(&time, on, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::{mock_display::MockDisplay, pixelcolor::RgbColor};
+
+    type Mock = MockDisplay<Rgb888>;
+
+    fn at(s: &str) -> Zoned {
+        s.parse().unwrap()
+    }
+
+    fn check<R>(
+        now: &Zoned,
+        synced: bool,
+        world: &[WorldZone],
+        run: impl FnOnce(&Context) -> R,
+    ) -> R {
+        let format = ClockFormat::parse("").unwrap();
+        let forecasts = WeatherForecastCache::new();
+        let air_quality = AirQualityCache::new();
+        let timers = crate::timers::Timers::new();
+        let ctx = Context {
+            now,
+            synced,
+            quality: Quality::Good,
+            format: &format,
+            locale: &crate::locale::ENGLISH,
+            bg: None,
+            forecasts: &forecasts,
+            air_quality: &air_quality,
+            agenda: &[],
+            world,
+            timers: &timers,
+            alarm: None,
+        };
+        run(&ctx)
+    }
+
+    fn name(now: &Zoned, synced: bool, world: &[WorldZone]) -> Option<&'static str> {
+        let pages = pages::<Mock>();
+        check(now, synced, world, |ctx| {
+            current(&pages, ctx).map(|page| page.name)
+        })
+    }
+
+    #[test]
+    fn clock_alone_without_other_content() {
+        let now = at("2026-03-18T12:00:50Z[+00:00]");
+        assert_eq!(name(&now, true, &[]), Some("clock"));
+        assert_eq!(name(&now, false, &[]), Some("clock"));
+    }
+
+    #[test]
+    fn pages_follow_the_clock_in_turn() {
+        let world = crate::worldclock::parse("Asia/Tokyo").unwrap();
+        // a 45 + 8 second cycle, starting on the clock
+        let cycle_start = 53 * 33_000_000;
+        let second = |offset: i64| {
+            Timestamp::from_second(cycle_start + offset)
+                .unwrap()
+                .to_zoned(jiff::tz::TimeZone::UTC)
+        };
+        assert_eq!(name(&second(44), true, &world), Some("clock"));
+        assert_eq!(name(&second(45), true, &world), Some("world clock"));
+        assert_eq!(name(&second(52), true, &world), Some("world clock"));
+        assert_eq!(name(&second(53), true, &world), Some("clock"));
+        // nothing but the clock until the time is known
+        assert_eq!(name(&second(45), false, &world), Some("clock"));
+    }
+
+    /// Paints the whole panel, whatever its bounds say.
+    struct Spill(Rectangle);
+
+    impl<D: DrawTarget<Color = Rgb888>> Widget<D> for Spill {
+        fn bounds(&self) -> Rectangle {
+            self.0
+        }
+
+        fn fresh(&self, _: &Context) -> bool {
+            true
+        }
+
+        fn draw(&self, _: &Context, target: &mut Clip<'_, D>) -> Result<(), D::Error> {
+            target.fill_solid(&FULL, Rgb888::RED)?;
+            Pixel(Point::new(40, 20), Rgb888::RED).draw(target)
+        }
+    }
+
+    #[test]
+    fn widgets_are_clipped_to_their_bounds() {
+        let now = at("2026-03-18T12:00:50Z[+00:00]");
+        let square = |x, y, size| Rectangle::new(Point::new(x, y), Size::new(size, size));
+        let page = |widget: Boxed<Mock>| Page {
+            name: "spill",
+            widgets: vec![widget],
+            dwell: 1,
+            condition: |_| true,
+            takeover: false,
+        };
+        check(&now, true, &[], |ctx| {
+            let mut display = Mock::new();
+            page(Box::new(Spill(square(1, 1, 2))))
+                .draw(ctx, &mut display)
+                .unwrap();
+            assert_eq!(display.affected_area(), square(1, 1, 2));
+
+            // and a rotation keeps its turn to both their bounds
+            let mut display = Mock::new();
+            let rotation = Rotation {
+                bounds: square(0, 0, 4),
+                slots: vec![(Box::new(Spill(square(2, 2, 4))) as Boxed<Mock>, 1)],
+            };
+            page(Box::new(rotation)).draw(ctx, &mut display).unwrap();
+            assert_eq!(display.affected_area(), square(2, 2, 2));
+        });
+    }
+
+    #[test]
+    fn widgets_stay_on_the_panel() {
+        let pages = pages::<Mock>();
+        for page in &pages {
+            for widget in &page.widgets {
+                let bounds = widget.bounds();
+                assert!(
+                    FULL.contains(bounds.top_left) && FULL.contains(bounds.bottom_right().unwrap()),
+                    "{} has a widget off the panel",
+                    page.name
+                );
+            }
+        }
+    }
+}
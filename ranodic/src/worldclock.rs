@@ -0,0 +1,251 @@
+//! The world clock page: the time in up to [`MAX_ZONES`] other places, one
+//! row each, with how many days they are ahead of or behind us, e.g.
+//! `TKY 09:14 +1`. Set at boot from `WORLD_CLOCK`, a `;`-separated list of
+//! zones as [`crate::timezone::parse`] takes them, each optionally labelled:
+//! `TKY=Asia/Tokyo;Europe/London`. Unlabelled zones are called by the first
+//! three letters of their city (`LON`).
+
+use core::cell::RefCell;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use anyhow::{Result, anyhow};
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
+use embedded_graphics::{
+    Drawable,
+    draw_target::DrawTarget,
+    geometry::Point,
+    mono_font::{
+        MonoTextStyle,
+        iso_8859_1::{FONT_4X6, FONT_5X8},
+    },
+    pixelcolor::Rgb888,
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+};
+use jiff::{Zoned, tz::TimeZone};
+
+use crate::clockformat::fit;
+
+/// More rows than this don't fit the panel legibly.
+pub const MAX_ZONES: usize = 3;
+const LABEL_LEN: usize = 3;
+const DISPLAY_W: u32 = 64;
+const DISPLAY_H: i32 = 32;
+
+const LABEL: Rgb888 = Rgb888::new(120, 120, 120);
+const TIME: Rgb888 = Rgb888::new(255, 255, 255);
+const DAYS: Rgb888 = Rgb888::new(240, 170, 30);
+
+#[derive(Clone, Debug)]
+pub struct WorldZone {
+    pub label: String,
+    pub tz: TimeZone,
+}
+
+impl WorldZone {
+    /// `LABEL=zone` or just `zone`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (label, zone) = match spec.split_once('=') {
+            Some((label, zone)) => (label.trim().to_string(), zone.trim()),
+            None => (city_label(spec.trim()), spec.trim()),
+        };
+        if label.is_empty() {
+            return Err(anyhow!("no label for world clock zone {}", zone));
+        }
+        Ok(Self {
+            label,
+            tz: crate::timezone::parse(zone)?,
+        })
+    }
+
+    /// The time there, as the local clock would show it, and how many days
+    /// its date is ahead of `now`'s.
+    pub fn at(&self, now: &Zoned, hour12: bool) -> (String, i32) {
+        let there = now.with_time_zone(self.tz.clone());
+        let pattern = if hour12 { "%-I:%M%P" } else { "%H:%M" };
+        let time = jiff::fmt::strtime::format(pattern, &there).unwrap_or_default();
+        let days = now
+            .date()
+            .until(there.date())
+            .map_or(0, |span| span.get_days());
+        (time, days)
+    }
+}
+
+// "America/Los_Angeles" → "LOS", "UTC" → "UTC"
+fn city_label(zone: &str) -> String {
+    let city = zone.rsplit('/').next().unwrap_or(zone);
+    city.chars()
+        .filter(char::is_ascii_alphabetic)
+        .take(LABEL_LEN)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// `"+1"`, `"-1"`, or nothing for the same day.
+pub fn day_offset(days: i32) -> String {
+    match days {
+        0 => String::new(),
+        days => format!("{:+}", days),
+    }
+}
+
+pub fn parse(spec: &str) -> Result<Vec<WorldZone>> {
+    let zones = spec
+        .split(';')
+        .map(str::trim)
+        .filter(|zone| !zone.is_empty())
+        .map(WorldZone::parse)
+        .collect::<Result<Vec<_>>>()?;
+    if zones.len() > MAX_ZONES {
+        return Err(anyhow!(
+            "{} world clock zones, at most {} fit",
+            zones.len(),
+            MAX_ZONES
+        ));
+    }
+    Ok(zones)
+}
+
+/// One row per zone across the whole display: label, time, and the day
+/// offset on the right, all in the biggest font the longest row fits.
+pub fn draw_world_clock<D>(
+    now: &Zoned,
+    zones: &[WorldZone],
+    hour12: bool,
+    target: &mut D,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    if zones.is_empty() {
+        return Ok(());
+    }
+    let rows: Vec<_> = zones
+        .iter()
+        .map(|zone| {
+            let (time, days) = zone.at(now, hour12);
+            (zone.label.as_str(), time, day_offset(days))
+        })
+        .collect();
+    let widest = rows
+        .iter()
+        .map(|(label, time, days)| format!("{} {} {:>2}", label, time, days))
+        .max_by_key(|line| line.chars().count())
+        .unwrap_or_default();
+    let font = fit(&widest, &[&FONT_5X8, &FONT_4X6], DISPLAY_W);
+    let char_w = (font.character_size.width + font.character_spacing) as i32;
+    let row_h = DISPLAY_H / rows.len() as i32;
+    let left = TextStyleBuilder::new()
+        .alignment(Alignment::Left)
+        .baseline(Baseline::Middle)
+        .build();
+    let right = TextStyleBuilder::new()
+        .alignment(Alignment::Right)
+        .baseline(Baseline::Middle)
+        .build();
+    for (idx, (label, time, days)) in rows.iter().enumerate() {
+        let y = idx as i32 * row_h + row_h / 2;
+        Text::with_text_style(
+            label,
+            Point::new(0, y),
+            MonoTextStyle::new(font, LABEL),
+            left,
+        )
+        .draw(target)?;
+        let time_x = (label.chars().count() as i32 + 1) * char_w;
+        Text::with_text_style(
+            time,
+            Point::new(time_x, y),
+            MonoTextStyle::new(font, TIME),
+            left,
+        )
+        .draw(target)?;
+        Text::with_text_style(
+            days,
+            Point::new(DISPLAY_W as i32, y),
+            MonoTextStyle::new(font, DAYS),
+            right,
+        )
+        .draw(target)?;
+    }
+    Ok(())
+}
+
+static ZONES: Mutex<CriticalSectionRawMutex, RefCell<Vec<WorldZone>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+pub fn get() -> Vec<WorldZone> {
+    ZONES.lock(|zones| zones.borrow().clone())
+}
+
+pub fn set(zones: Vec<WorldZone>) {
+    ZONES.lock(|cell| cell.replace(zones));
+}
+
+/// Parse `spec` and show those zones; the page stays off if it doesn't
+/// parse.
+pub fn set_from_str(spec: &str) -> Result<()> {
+    set(parse(spec)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> Zoned {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn labels() {
+        let zones = parse("TKY=Asia/Tokyo; Europe/London;America/Los_Angeles").unwrap();
+        let labels: Vec<_> = zones.iter().map(|zone| zone.label.as_str()).collect();
+        assert_eq!(labels, ["TKY", "LON", "LOS"]);
+        assert_eq!(parse("UTC").unwrap()[0].label, "UTC");
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_nonsense() {
+        assert!(parse("Mars/Olympus_Mons").is_err());
+        assert!(parse("=Asia/Tokyo").is_err());
+        assert!(parse("UTC;Asia/Tokyo;Europe/London;Europe/Berlin").is_err());
+    }
+
+    #[test]
+    fn times_and_days_from_pacific() {
+        // 17:14 PDT on Wednesday
+        let now = at("2026-03-18T17:14:30-07:00[-07:00]");
+        let zones = parse("TKY=Asia/Tokyo;LON=Europe/London;HNL=Pacific/Honolulu").unwrap();
+        let seen: Vec<_> = zones.iter().map(|zone| zone.at(&now, false)).collect();
+        assert_eq!(seen[0], ("09:14".into(), 1));
+        assert_eq!(seen[1], ("00:14".into(), 1));
+        assert_eq!(seen[2], ("14:14".into(), 0));
+        assert_eq!(zones[0].at(&now, true).0, "9:14am");
+    }
+
+    #[test]
+    fn days_behind() {
+        // 08:00 in Tokyo on Thursday is still Wednesday in New York
+        let now = at("2026-03-19T08:00:00+09:00[+09:00]");
+        let nyc = WorldZone::parse("NYC=America/New_York").unwrap();
+        assert_eq!(nyc.at(&now, false), ("19:00".into(), -1));
+        assert_eq!(day_offset(-1), "-1");
+        assert_eq!(day_offset(1), "+1");
+        assert_eq!(day_offset(0), "");
+    }
+
+    #[test]
+    fn follows_dst_there() {
+        let zone = WorldZone::parse("Europe/Berlin").unwrap();
+        let winter = at("2026-03-28T12:00:00+00:00[+00:00]");
+        let summer = at("2026-03-30T12:00:00+00:00[+00:00]");
+        assert_eq!(zone.at(&winter, false).0, "13:00");
+        assert_eq!(zone.at(&summer, false).0, "14:00");
+    }
+}